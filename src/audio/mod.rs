@@ -1,40 +1,334 @@
-use rodio::{self, Sink, Source};
+use rodio::{self, Sample, Sink, Source};
+use std::collections::HashMap;
+use std::error::Error as StdError;
+use std::fmt::{self, Display, Formatter};
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{self, BufReader};
+use std::time::Duration;
+
+use nphysics::math::Point;
+
+use config::AudioConfig;
+use engine::{GameEvent, N};
+
+// How far to the side, in metres, a sound has to be to come only out of that side.
+const PAN_RANGE: N = 12.0;
+// Sounds further than this from the listener, in metres, can't be heard.
+const HEARING_RANGE: N = 30.0;
+
+#[derive(Debug)]
+pub enum AudioError {
+    IoError(io::Error),
+    /// The file isn't in a format rodio can decode.
+    UnrecognizedFormat,
+}
+
+impl Display for AudioError {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            AudioError::IoError(ref e) => write!(f, "{}", e),
+            AudioError::UnrecognizedFormat => write!(f, "unrecognised audio format"),
+        }
+    }
+}
+
+impl StdError for AudioError {
+    fn description(&self) -> &str {
+        "audio error"
+    }
+
+    fn cause(&self) -> Option<&StdError> {
+        match *self {
+            AudioError::IoError(ref e) => Some(e),
+            AudioError::UnrecognizedFormat => None,
+        }
+    }
+}
+
+impl From<io::Error> for AudioError {
+    fn from(err: io::Error) -> AudioError {
+        AudioError::IoError(err)
+    }
+}
 
 pub struct Sound {
     source: rodio::source::Buffered<rodio::Decoder<BufReader<File>>>,
 }
 
 impl Sound {
-    pub fn new(path: &str) -> Self {
-        let file = File::open(format!("media/{}", path)).unwrap();
-        Sound {
-            source: rodio::Decoder::new(BufReader::new(file))
-                .unwrap()
-                .buffered(),
-        }
+    /// Loads a sound from the media directory.
+    pub fn new(path: &str) -> Result<Self, AudioError> {
+        let file = File::open(format!("media/{}", path))?;
+        let decoder = rodio::Decoder::new(BufReader::new(file)).map_err(|_| AudioError::UnrecognizedFormat)?;
+        Ok(Sound {
+            source: decoder.buffered(),
+        })
     }
 
-    pub fn play(&self) {
-        let endpoint = rodio::get_default_endpoint().unwrap();
-        let sink = Sink::new(&endpoint);
+    /// `volume` is from 0 to 1.
+    pub fn play(&self, endpoint: &rodio::Endpoint, volume: f32) {
+        self.play_panned(endpoint, volume, 0.0);
+    }
 
-        sink.append(self.source.clone());
+    /// `pan` is from -1 (left only) to 1 (right only).
+    pub fn play_panned(&self, endpoint: &rodio::Endpoint, volume: f32, pan: f32) {
+        let sink = Sink::new(endpoint);
+        sink.set_volume(volume);
+
+        sink.append(Panned::new(self.source.clone(), pan));
         sink.detach();
     }
 }
 
-pub fn play(path: &str) {
-    let endpoint = rodio::get_default_endpoint().unwrap();
-    let file = File::open(format!("media/{}", path)).unwrap();
+/// Plays the left and right channels of a source at different volumes. Mono sources are left alone.
+struct Panned<S> {
+    input: S,
+    left: f32,
+    right: f32,
+    /// The channel of the next sample.
+    channel: u16,
+}
 
-    let source = rodio::Decoder::new(BufReader::new(file)).unwrap();
+impl<S> Panned<S> {
+    fn new(input: S, pan: f32) -> Self {
+        let pan = pan.max(-1.0).min(1.0);
+        Panned {
+            input,
+            left: (1.0 - pan).min(1.0),
+            right: (1.0 + pan).min(1.0),
+            channel: 0,
+        }
+    }
+}
+
+impl<S> Iterator for Panned<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    type Item = S::Item;
+
+    fn next(&mut self) -> Option<S::Item> {
+        let sample = match self.input.next() {
+            Some(sample) => sample,
+            None => return None,
+        };
+
+        let channels = self.input.channels();
+        let gain = match (channels, self.channel) {
+            (1, _) => 1.0,
+            (_, 0) => self.left,
+            (_, 1) => self.right,
+            _ => 1.0,
+        };
+        self.channel = (self.channel + 1) % channels.max(1);
 
-    // source = ();
-    // source.buffered() = ();
-    // rodio::play_raw(&endpoint, source.convert_samples());
-    // rodio::play_raw(&endpoint, source.convert_samples());
+        Some(sample.amplify(gain))
+    }
 }
 
-pub fn init() {}
+impl<S> Source for Panned<S>
+where
+    S: Source,
+    S::Item: Sample,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn samples_rate(&self) -> u32 {
+        self.input.samples_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+}
+
+/// Which volume setting a sound follows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SoundCategory {
+    Effect,
+    Music,
+}
+
+impl SoundCategory {
+    pub fn volume(self, config: &AudioConfig) -> f32 {
+        match self {
+            SoundCategory::Effect => config.sfx(),
+            SoundCategory::Music => config.music(),
+        }
+    }
+}
+
+/// A track looping in the background. It stops when dropped.
+struct Music {
+    /// The file in `media/music/`.
+    track: String,
+    sink: Sink,
+}
+
+impl Music {
+    // Decoded as it plays, and kept once decoded so each loop carries straight on from the last.
+    fn new(endpoint: &rodio::Endpoint, track: &str, volume: f32) -> Result<Self, AudioError> {
+        let file = File::open(format!("media/music/{}", track))?;
+        let decoder = rodio::Decoder::new(BufReader::new(file)).map_err(|_| AudioError::UnrecognizedFormat)?;
+
+        let sink = Sink::new(endpoint);
+        sink.set_volume(volume);
+        sink.append(decoder.repeat_infinite());
+
+        Ok(Music {
+            track: track.to_owned(),
+            sink,
+        })
+    }
+}
+
+/// Plays the sounds for gameplay events, and the music. Sounds are loaded the first time they're needed.
+/// Without an audio device everything is silently skipped, so the game still runs on headless machines.
+pub struct AudioManager {
+    /// None when there's no audio device to play on.
+    endpoint: Option<rodio::Endpoint>,
+    /// None for sounds which failed to load, so the error is only reported once.
+    sounds: HashMap<String, Option<Sound>>,
+    music: Option<Music>,
+    /// A track which failed to load, so it isn't tried again every frame.
+    failed_track: Option<String>,
+    /// Copied from the config, and applied to each sound as it starts playing.
+    volumes: AudioConfig,
+}
+
+impl AudioManager {
+    pub fn new() -> Self {
+        let endpoint = rodio::get_default_endpoint();
+        if endpoint.is_none() {
+            eprintln!("Warning: no audio output device found, so the game will be silent");
+        }
+
+        AudioManager {
+            endpoint,
+            sounds: HashMap::new(),
+            music: None,
+            failed_track: None,
+            volumes: AudioConfig::default(),
+        }
+    }
+
+    /// Takes on new volume settings. Sounds already playing keep their volume, apart from the music.
+    pub fn set_volumes(&mut self, volumes: &AudioConfig) {
+        self.volumes = volumes.clone();
+        if let Some(ref music) = self.music {
+            music.sink.set_volume(self.volumes.music());
+        }
+    }
+
+    /// Call every update with the current level's track. Switches track when it changes,
+    /// and stops when there isn't one.
+    pub fn update_music(&mut self, track: Option<&str>) {
+        let volume = self.volumes.music();
+        let endpoint = match self.endpoint {
+            Some(ref endpoint) => endpoint,
+            None => return,
+        };
+
+        let changed = self.music.as_ref().map(|music| &music.track[..]) != track;
+        if changed {
+            // dropping the sink stops the old track
+            self.music = None;
+
+            if let Some(track) = track {
+                if self.failed_track.as_ref().map(|failed| &failed[..]) != Some(track) {
+                    match Music::new(endpoint, track, volume) {
+                        Ok(music) => self.music = Some(music),
+                        Err(err) => {
+                            eprintln!("Error loading music `{}`: {}", track, err);
+                            self.failed_track = Some(track.to_owned());
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Plays whatever sound goes with the event, if any. Events that happen somewhere in particular
+    /// are panned and quietened according to where that is from the `listener`.
+    pub fn handle_event(&mut self, event: &GameEvent, listener: (N, N)) {
+        let path = match *event {
+            GameEvent::KnifeThrown => "sounds/knife_throw.ogg",
+            GameEvent::Damaged { .. } => "sounds/hit.ogg",
+            GameEvent::Jumped => "sounds/jump.ogg",
+            GameEvent::CoinCollected { .. } => "sounds/coin.ogg",
+            GameEvent::PickupCollected { .. } => "sounds/pickup.ogg",
+            GameEvent::TimeStopStarted => "sounds/time_stop.ogg",
+            GameEvent::TimeStopEnded => "sounds/time_start.ogg",
+            GameEvent::Impact { .. } => "sounds/explosion.ogg",
+            GameEvent::PlaySound { ref path } => path,
+            _ => return,
+        };
+
+        match event_position(event) {
+            Some(position) => self.play_at(path, position, listener),
+            None => self.play(path, SoundCategory::Effect),
+        }
+    }
+
+    /// Plays a sound effect which comes from `position` in the world.
+    pub fn play_at(&mut self, path: &str, position: Point<N>, listener: (N, N)) {
+        let (dx, dy) = (position.x - listener.0, position.y - listener.1);
+        let attenuation = 1.0 - (dx * dx + dy * dy).sqrt() / HEARING_RANGE;
+        let volume = SoundCategory::Effect.volume(&self.volumes) * attenuation;
+        if volume <= 0.0 {
+            return;
+        }
+
+        let endpoint = match self.endpoint {
+            Some(ref endpoint) => endpoint,
+            None => return,
+        };
+        if let Some(sound) = cached_sound(&mut self.sounds, path) {
+            sound.play_panned(endpoint, volume, dx / PAN_RANGE);
+        }
+    }
+
+    pub fn play(&mut self, path: &str, category: SoundCategory) {
+        let volume = category.volume(&self.volumes);
+        if volume <= 0.0 {
+            return;
+        }
+
+        let endpoint = match self.endpoint {
+            Some(ref endpoint) => endpoint,
+            None => return,
+        };
+        if let Some(sound) = cached_sound(&mut self.sounds, path) {
+            sound.play(endpoint, volume);
+        }
+    }
+}
+
+// Takes the cache rather than the manager, so it can be borrowed alongside the endpoint.
+fn cached_sound<'a>(sounds: &'a mut HashMap<String, Option<Sound>>, path: &str) -> Option<&'a Sound> {
+    sounds
+        .entry(path.to_owned())
+        .or_insert_with(|| match Sound::new(path) {
+            Ok(sound) => Some(sound),
+            Err(err) => {
+                eprintln!("Error loading sound `{}`: {}", path, err);
+                None
+            }
+        })
+        .as_ref()
+}
+
+// Where in the world an event happened, for the ones that happen somewhere in particular.
+fn event_position(event: &GameEvent) -> Option<Point<N>> {
+    match *event {
+        GameEvent::Damaged { position, .. } | GameEvent::Impact { position, .. } => Some(position),
+        _ => None,
+    }
+}
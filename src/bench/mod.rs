@@ -0,0 +1,147 @@
+use std::time::{Duration, Instant};
+
+use nphysics::math::Vector;
+
+use engine::*;
+use levels::{Level, LevelEntity};
+
+const NUM_CRATES: usize = 400;
+const NUM_PARTICLES: usize = 300;
+const WARMUP_STEPS: usize = 120;
+const MEASURED_STEPS: usize = 1200;
+const ROUND_TRIPS: usize = 10000;
+
+/// Runs the world headlessly on a synthetic level and prints how long ticks take.
+/// Started with `--bench`; used to measure the cost of talking to the physics thread.
+pub fn run() {
+    let mut world = synthetic_level().to_world_with_seed(0);
+
+    for _ in 0..NUM_PARTICLES {
+        world.new_particle(Rect::new(0.0, -10.0, 0.03, 0.03), Vector::new(0.0, -2.0), 1000.0, [1.0, 0.0, 0.0, 1.0]);
+    }
+
+    for _ in 0..WARMUP_STEPS {
+        world.step(FIXED_TIMESTEP);
+    }
+
+    let mut steps = Vec::with_capacity(MEASURED_STEPS);
+    let mut physics = Vec::with_capacity(MEASURED_STEPS);
+    let mut dispatch = Vec::with_capacity(MEASURED_STEPS);
+    let mut spawns = Vec::with_capacity(MEASURED_STEPS);
+
+    for _ in 0..MEASURED_STEPS {
+        let start = Instant::now();
+        world.step(FIXED_TIMESTEP);
+        steps.push(micros(start.elapsed()));
+
+        let timings = world.last_step_timings();
+        physics.push(micros(timings.physics));
+        dispatch.push(micros(timings.dispatch));
+        spawns.push(micros(timings.spawns));
+    }
+
+    let mut round_trips = Vec::with_capacity(ROUND_TRIPS);
+    {
+        let body_id = world.player_rigid_body_id();
+        let link = world.physics_thread_link();
+        let physics = link.lock().unwrap();
+
+        for _ in 0..ROUND_TRIPS {
+            let start = Instant::now();
+            physics.get_position(body_id).unwrap();
+            round_trips.push(micros(start.elapsed()));
+        }
+    }
+
+    println!(
+        "{} crates, {} particles, {} steps of {}s",
+        NUM_CRATES,
+        NUM_PARTICLES,
+        MEASURED_STEPS,
+        FIXED_TIMESTEP
+    );
+    report("step", &mut steps);
+    report("  physics", &mut physics);
+    report("  dispatch", &mut dispatch);
+    report("  spawns", &mut spawns);
+    report("round trip", &mut round_trips);
+
+    world.shutdown();
+}
+
+fn synthetic_level() -> Level {
+    let mut entities = vec![
+        LevelEntity::Ground {
+            rect: Rect::new(0.0, 0.5, 30.0, 0.5),
+            layer: None,
+        },
+        LevelEntity::Ground {
+            rect: Rect::new(-30.5, -20.0, 0.5, 20.0),
+            layer: None,
+        },
+        LevelEntity::Ground {
+            rect: Rect::new(30.5, -20.0, 0.5, 20.0),
+            layer: None,
+        },
+    ];
+
+    let columns = 40;
+    for i in 0..NUM_CRATES {
+        let x = (i % columns) as N * 1.2 - 24.0;
+        let y = -1.0 - (i / columns) as N * 1.2;
+        let material = if i % 2 == 0 {
+            CrateMaterial::Wood
+        } else {
+            CrateMaterial::Steel
+        };
+
+        entities.push(LevelEntity::Crate {
+            rect: Rect::new(x, y, 0.5, 0.5),
+            material,
+            hitpoints: None,
+            contents: Vec::new(),
+            collision_groups: None,
+            sprite: None,
+            layer: None,
+        });
+    }
+
+    Level {
+        name: String::from("Benchmark"),
+        seed: Some(0),
+        player_start_pos: (-28.0, -2.0),
+        entities,
+        tile_layers: Vec::new(),
+        time_stop: None,
+        player: None,
+        player_sprite: None,
+        player_animation: None,
+        camera_bounds: None,
+        ambient_light: None,
+        player_light: None,
+        music: None,
+    }
+}
+
+fn micros(d: Duration) -> f64 {
+    d.as_secs() as f64 * 1_000_000.0 + d.subsec_nanos() as f64 / 1_000.0
+}
+
+// Prints the mean, median, 99th percentile and worst of a set of timings, in microseconds.
+fn report(name: &str, samples: &mut Vec<f64>) {
+    samples.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+    let mean = samples.iter().sum::<f64>() / samples.len() as f64;
+    let median = samples[samples.len() / 2];
+    let p99 = samples[samples.len() * 99 / 100];
+    let max = samples[samples.len() - 1];
+
+    println!(
+        "{:<12} mean {:>9.1}us  median {:>9.1}us  p99 {:>9.1}us  max {:>9.1}us",
+        name,
+        mean,
+        median,
+        p99,
+        max
+    );
+}
@@ -0,0 +1,352 @@
+use std::error::Error as StdError;
+use std::fmt::{self, Display, Formatter};
+use std::fs::File;
+use std::io::{self, ErrorKind, Read, Write};
+
+use piston_window::Key;
+use toml;
+
+const FILENAME: &'static str = "config.toml";
+
+#[derive(Debug)]
+pub enum ConfigError {
+    IoError(io::Error),
+    TomlDeError(toml::de::Error),
+    TomlSerError(toml::ser::Error),
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            ConfigError::IoError(ref e) => write!(f, "{}: {}", FILENAME, e),
+            ConfigError::TomlDeError(ref e) => write!(f, "{}: {}", FILENAME, e),
+            ConfigError::TomlSerError(ref e) => write!(f, "{}: {}", FILENAME, e),
+        }
+    }
+}
+
+impl StdError for ConfigError {
+    fn description(&self) -> &str {
+        "config error"
+    }
+
+    fn cause(&self) -> Option<&StdError> {
+        match *self {
+            ConfigError::IoError(ref e) => Some(e),
+            ConfigError::TomlDeError(ref e) => Some(e),
+            ConfigError::TomlSerError(ref e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for ConfigError {
+    fn from(err: io::Error) -> ConfigError {
+        ConfigError::IoError(err)
+    }
+}
+
+impl From<toml::de::Error> for ConfigError {
+    fn from(err: toml::de::Error) -> ConfigError {
+        ConfigError::TomlDeError(err)
+    }
+}
+
+impl From<toml::ser::Error> for ConfigError {
+    fn from(err: toml::ser::Error) -> ConfigError {
+        ConfigError::TomlSerError(err)
+    }
+}
+
+/// Player settings, kept in `config.toml` next to the stats file.
+/// Anything missing from the file gets its default, so old files keep working.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub window: WindowConfig,
+    pub video: VideoConfig,
+    pub audio: AudioConfig,
+    pub hud: HudConfig,
+    pub controls: Controls,
+}
+
+impl Config {
+    /// Reads the config file, writing out the defaults if there isn't one yet.
+    pub fn load() -> Result<Config, ConfigError> {
+        let mut text = String::new();
+        match File::open(FILENAME) {
+            Ok(mut file) => {
+                file.read_to_string(&mut text)?;
+            }
+            Err(ref err) if err.kind() == ErrorKind::NotFound => {
+                let config = Config::default();
+                config.save()?;
+                return Ok(config);
+            }
+            Err(err) => return Err(err.into()),
+        }
+
+        Ok(toml::from_str(&text)?)
+    }
+
+    /// Like `load`, but falls back to the defaults when the file is broken, after saying why.
+    pub fn load_or_default() -> Config {
+        Config::load().unwrap_or_else(|err| {
+            eprintln!("Error loading config, using the defaults: {}", err);
+            Config::default()
+        })
+    }
+
+    pub fn save(&self) -> Result<(), ConfigError> {
+        let text = toml::to_string(self)?;
+        File::create(FILENAME)?.write_all(text.as_bytes())?;
+        Ok(())
+    }
+}
+
+/// The size and position of the window when it was last closed or made fullscreen,
+/// so it comes back the same way.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowConfig {
+    pub width: u32,
+    pub height: u32,
+    /// Left to the window manager until there's a position to remember.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub x: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub y: Option<i32>,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        WindowConfig {
+            width: 800,
+            height: 600,
+            x: None,
+            y: None,
+        }
+    }
+}
+
+/// Vsync and multisampling only take effect when the window is created, i.e. on the next start.
+/// Fullscreen rebuilds the window straight away.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VideoConfig {
+    pub vsync: bool,
+    /// Multisampling samples per pixel; 0 turns antialiasing off.
+    pub samples: u8,
+    pub fullscreen: bool,
+}
+
+impl Default for VideoConfig {
+    fn default() -> Self {
+        VideoConfig {
+            vsync: true,
+            samples: 4,
+            fullscreen: false,
+        }
+    }
+}
+
+/// Volumes are from 0 to 1.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioConfig {
+    pub master_volume: f32,
+    pub sfx_volume: f32,
+    pub music_volume: f32,
+    /// Silences everything without losing the volumes, toggled with M.
+    pub muted: bool,
+}
+
+impl AudioConfig {
+    /// The volume to play sound effects at, after the master volume.
+    pub fn sfx(&self) -> f32 {
+        self.master() * self.sfx_volume
+    }
+
+    pub fn music(&self) -> f32 {
+        self.master() * self.music_volume
+    }
+
+    fn master(&self) -> f32 {
+        if self.muted {
+            0.0
+        } else {
+            self.master_volume
+        }
+    }
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        AudioConfig {
+            master_volume: 1.0,
+            sfx_volume: 1.0,
+            music_volume: 0.7,
+            muted: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HudConfig {
+    /// Whether the knife pips and time stop ring follow the player around, rather than sitting in a corner.
+    pub widgets_follow_player: bool,
+}
+
+impl Default for HudConfig {
+    fn default() -> Self {
+        HudConfig {
+            widgets_follow_player: true,
+        }
+    }
+}
+
+/// Which key does what. Keys are saved by name, e.g. "Space" or "LShift".
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Controls {
+    #[serde(with = "key_serde")]
+    pub move_left: Key,
+    #[serde(with = "key_serde")]
+    pub move_right: Key,
+    #[serde(with = "key_serde")]
+    pub jump: Key,
+    #[serde(with = "key_serde")]
+    pub alt_jump: Key,
+    #[serde(with = "key_serde")]
+    pub pick_up: Key,
+    #[serde(with = "key_serde")]
+    pub stop_time: Key,
+    #[serde(with = "key_serde")]
+    pub grenade: Key,
+    #[serde(with = "key_serde")]
+    pub spawn_bullet: Key,
+    #[serde(with = "key_serde")]
+    pub restart: Key,
+}
+
+impl Default for Controls {
+    fn default() -> Self {
+        Controls {
+            move_left: Key::A,
+            move_right: Key::D,
+            jump: Key::Space,
+            alt_jump: Key::W,
+            pick_up: Key::C,
+            stop_time: Key::F,
+            grenade: Key::G,
+            spawn_bullet: Key::E,
+            restart: Key::R,
+        }
+    }
+}
+
+/// Something the player can bind a key to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Control {
+    MoveLeft,
+    MoveRight,
+    Jump,
+    AltJump,
+    PickUp,
+    StopTime,
+    Grenade,
+    SpawnBullet,
+    Restart,
+}
+
+pub const ALL_CONTROLS: &'static [Control] = &[
+    Control::MoveLeft,
+    Control::MoveRight,
+    Control::Jump,
+    Control::AltJump,
+    Control::PickUp,
+    Control::StopTime,
+    Control::Grenade,
+    Control::SpawnBullet,
+    Control::Restart,
+];
+
+impl Control {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Control::MoveLeft => "Move left",
+            Control::MoveRight => "Move right",
+            Control::Jump => "Jump",
+            Control::AltJump => "Jump (other key)",
+            Control::PickUp => "Pick up / throw",
+            Control::StopTime => "Stop time",
+            Control::Grenade => "Throw grenade",
+            Control::SpawnBullet => "Spawn bullet",
+            Control::Restart => "Restart",
+        }
+    }
+}
+
+impl Controls {
+    pub fn key(&self, control: Control) -> Key {
+        match control {
+            Control::MoveLeft => self.move_left,
+            Control::MoveRight => self.move_right,
+            Control::Jump => self.jump,
+            Control::AltJump => self.alt_jump,
+            Control::PickUp => self.pick_up,
+            Control::StopTime => self.stop_time,
+            Control::Grenade => self.grenade,
+            Control::SpawnBullet => self.spawn_bullet,
+            Control::Restart => self.restart,
+        }
+    }
+
+    pub fn key_mut(&mut self, control: Control) -> &mut Key {
+        match control {
+            Control::MoveLeft => &mut self.move_left,
+            Control::MoveRight => &mut self.move_right,
+            Control::Jump => &mut self.jump,
+            Control::AltJump => &mut self.alt_jump,
+            Control::PickUp => &mut self.pick_up,
+            Control::StopTime => &mut self.stop_time,
+            Control::Grenade => &mut self.grenade,
+            Control::SpawnBullet => &mut self.spawn_bullet,
+            Control::Restart => &mut self.restart,
+        }
+    }
+}
+
+/// Keys that can be bound to controls. The rest are either taken (e.g. Q to quit, M to mute, the number
+/// keys for weapons and F3 for the debug overlay) or unlikely to be wanted.
+pub const BINDABLE_KEYS: &'static [Key] = &[
+    Key::A, Key::B, Key::C, Key::D, Key::E, Key::F, Key::G, Key::H, Key::I, Key::J, Key::K, Key::L,
+    Key::N, Key::O, Key::P, Key::R, Key::S, Key::T, Key::U, Key::V, Key::W, Key::X, Key::Y, Key::Z,
+    Key::Space, Key::Tab, Key::Return, Key::Backspace,
+    Key::LShift, Key::RShift, Key::LCtrl, Key::RCtrl, Key::LAlt, Key::RAlt,
+    Key::Up, Key::Down, Key::Left, Key::Right,
+];
+
+pub fn key_name(key: Key) -> String {
+    format!("{:?}", key)
+}
+
+pub fn parse_key(name: &str) -> Option<Key> {
+    BINDABLE_KEYS.iter().cloned().find(|&key| key_name(key) == name)
+}
+
+// For `#[serde(with)]`.
+mod key_serde {
+    use piston_window::Key;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use serde::de::Error;
+
+    pub fn serialize<S: Serializer>(key: &Key, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&super::key_name(*key))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Key, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        super::parse_key(&name).ok_or_else(|| D::Error::custom(format!("unknown or unbindable key {:?}", name)))
+    }
+}
@@ -0,0 +1,36 @@
+use std::mem;
+
+use super::*;
+
+/// A scripted effect described in level data, run by `World` at the end of the step it was queued in.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Action {
+    SpawnEnemy {
+        rect: Rect,
+        #[serde(default)]
+        kind: EnemyKind,
+    },
+    /// Sends a signal to linked entities, e.g. to open a door.
+    Signal { target: LinkId, signal: Signal },
+    /// Shows a line of text at the top of the screen for `duration` seconds.
+    ShowText { text: String, duration: N },
+    /// Plays a sound from the media directory.
+    PlaySound { path: String },
+    StopTime { duration: N },
+}
+
+/// Actions queued by systems, like `SpawnBuffer` but for anything else that needs the whole `World`.
+#[derive(Default)]
+pub struct ActionQueue {
+    actions: Vec<Action>,
+}
+
+impl ActionQueue {
+    pub fn extend<I: IntoIterator<Item = Action>>(&mut self, it: I) {
+        self.actions.extend(it);
+    }
+
+    pub fn drain(&mut self) -> Vec<Action> {
+        mem::replace(&mut self.actions, Vec::new())
+    }
+}
@@ -0,0 +1,103 @@
+use super::*;
+
+use specs::{Component, DenseVecStorage};
+
+/// What an animated entity is doing, which picks the clip to play.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AnimationState {
+    Idle,
+    Run,
+    Jump,
+    Dead,
+}
+
+impl Default for AnimationState {
+    fn default() -> Self {
+        AnimationState::Idle
+    }
+}
+
+/// One row of frames in a sprite sheet.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnimationClip {
+    pub state: AnimationState,
+    /// Which row of the sheet the frames are in, from the top.
+    pub row: u32,
+    pub frames: u32,
+    pub fps: N,
+    /// Clips that don't loop stay on their last frame.
+    #[serde(default)]
+    pub looping: bool,
+}
+
+/// Steps through a sprite sheet, picking the clip from what the entity is doing.
+/// Updated by `AnimationSystem`, which points the entity's sprites at the current frame.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Animation {
+    /// Size of a single frame in the sheet, in pixels.
+    pub frame_w: u32,
+    pub frame_h: u32,
+    pub clips: Vec<AnimationClip>,
+    #[serde(default)]
+    pub state: AnimationState,
+    #[serde(skip)]
+    time: N,
+}
+
+impl Animation {
+    pub fn new(frame_w: u32, frame_h: u32, clips: Vec<AnimationClip>) -> Self {
+        Animation {
+            frame_w,
+            frame_h,
+            clips,
+            state: AnimationState::Idle,
+            time: 0.0,
+        }
+    }
+
+    /// Switches clip, starting it from the first frame. Does nothing if it's already playing.
+    pub fn set_state(&mut self, state: AnimationState) {
+        if state != self.state {
+            self.state = state;
+            self.time = 0.0;
+        }
+    }
+
+    pub fn advance(&mut self, dt: N) {
+        self.time += dt;
+    }
+
+    fn clip(&self) -> Option<&AnimationClip> {
+        self.clips.iter().find(|clip| clip.state == self.state)
+    }
+
+    pub fn frame(&self) -> u32 {
+        let clip = match self.clip() {
+            Some(clip) if clip.frames > 0 => clip,
+            _ => return 0,
+        };
+
+        let frame = (self.time * clip.fps) as u32;
+        if clip.looping {
+            frame % clip.frames
+        } else {
+            frame.min(clip.frames - 1)
+        }
+    }
+
+    /// The part of the sheet to draw, as [x, y, w, h] in pixels.
+    /// Falls back to the first frame if there is no clip for the current state.
+    pub fn source_rect(&self) -> [N; 4] {
+        let row = self.clip().map_or(0, |clip| clip.row);
+        [
+            (self.frame() * self.frame_w) as N,
+            (row * self.frame_h) as N,
+            self.frame_w as N,
+            self.frame_h as N,
+        ]
+    }
+}
+
+impl Component for Animation {
+    type Storage = DenseVecStorage<Self>;
+}
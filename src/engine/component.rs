@@ -1,9 +1,12 @@
 use super::*;
 
 use std::cmp;
+use std::collections::HashMap;
+use std::mem;
 
 use specs::{self, Component, DenseVecStorage, Entity, HashMapStorage, VecStorage};
 use nphysics::math::{Orientation, Vector};
+use num::Zero;
 
 pub fn register_components(world: &mut specs::World) {
     macro_rules! register_components {
@@ -15,20 +18,58 @@ pub fn register_components(world: &mut specs::World) {
     register_components! {
         world,
         RigidBodyID,
+        SensorID,
         Renderable,
         Player,
+        Inventory,
+        GroundSensor,
         TimeStopStore,
         Hitpoints,
         Knife,
+        Grenade,
         Remove,
         TimedRemove,
+        Particle,
+        HitFlash,
+        Invulnerable,
+        Team,
+        StatusEffects,
         Name,
         BasicEnemy,
+        Facing,
+        Patrol,
+        Aggro,
+        Spawner,
+        GravityScale,
+        VelocityLimit,
+        Animation,
+        Flyer,
+        Boss,
+        SpawnedBy,
         Bullet,
+        MovingPlatform,
+        Hazard,
+        Coin,
+        Door,
+        Switch,
+        Key,
+        Trigger,
+        Crate,
+        Pickup,
+        Carryable,
+        Thrown,
+        GravityZone,
+        ForceField,
+        ContactDamage,
+        RangedAttacker,
+        Corpse,
+        TextFade,
+        Goal,
+        Light,
     }
 }
 
-#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct RigidBodyID(u32);
 
 impl RigidBodyID {
@@ -41,23 +82,51 @@ impl Component for RigidBodyID {
     type Storage = VecStorage<Self>;
 }
 
+/// The sensor owned by an entity, removed from the physics world along with the entity.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SensorID(u32);
+
+impl SensorID {
+    pub fn new(x: u32) -> Self {
+        SensorID(x)
+    }
+}
+
+impl Component for SensorID {
+    type Storage = HashMapStorage<Self>;
+}
+
+/// Draw layers, from back to front. Anything in between works too.
+pub const LAYER_GROUND: i32 = -10;
+pub const LAYER_DEFAULT: i32 = 0;
+pub const LAYER_PARTICLES: i32 = 10;
+pub const LAYER_TEXT: i32 = 20;
+
 #[derive(Debug, Clone)]
 pub struct RenderItem {
     pub rel_rotation: N,
     pub rel_x: N,
     pub rel_y: N,
     pub color: [f32; 4],
+    /// Added to the layer of the `Renderable` it belongs to.
+    pub layer: i32,
 
     pub kind: RenderItemKind,
 }
 
 impl RenderItem {
+    pub fn on_layer(mut self, layer: i32) -> Self {
+        self.layer = layer;
+        self
+    }
+
     pub fn rectangle(rel_x: N, rel_y: N, w: N, h: N, rel_rotation: N, color: [f32; 4]) -> Self {
         RenderItem {
             rel_x,
             rel_y,
             rel_rotation,
             color,
+            layer: 0,
             kind: RenderItemKind::Rectangle { w, h },
         }
     }
@@ -68,40 +137,219 @@ impl RenderItem {
             rel_y,
             rel_rotation,
             color,
+            layer: LAYER_TEXT,
             kind: RenderItemKind::Text {
                 text: text.into(),
                 size,
+                style: TextStyle::default(),
             },
         }
     }
 
+    /// Changes how a text item is drawn. Other kinds of item are left alone.
+    pub fn with_text_style(mut self, text_style: TextStyle) -> Self {
+        if let RenderItemKind::Text { ref mut style, .. } = self.kind {
+            *style = text_style;
+        }
+        self
+    }
+
     pub fn info(rel_x: N, rel_y: N, rel_rotation: N, color: [f32; 4]) -> Self {
         RenderItem {
             rel_x,
             rel_y,
             rel_rotation,
             color,
+            layer: LAYER_TEXT,
             kind: RenderItemKind::Info,
         }
     }
 
+    /// `points` are relative to (rel_x, rel_y).
+    pub fn polygon(rel_x: N, rel_y: N, points: Vec<[N; 2]>, rel_rotation: N, color: [f32; 4]) -> Self {
+        RenderItem {
+            rel_x,
+            rel_y,
+            rel_rotation,
+            color,
+            layer: 0,
+            kind: RenderItemKind::Polygon { points },
+        }
+    }
+
     pub fn ellipse(rel_x: N, rel_y: N, w: N, h: N, rel_rotation: N, color: [f32; 4]) -> Self {
         RenderItem {
             rel_x,
             rel_y,
             rel_rotation,
             color,
+            layer: 0,
             kind: RenderItemKind::Ellipse { w, h },
         }
     }
+
+    /// A line from (rel_x, rel_y) to `end`, which is relative to the same point as (rel_x, rel_y).
+    pub fn line(rel_x: N, rel_y: N, end: [N; 2], width: N, rel_rotation: N, color: [f32; 4]) -> Self {
+        RenderItem {
+            rel_x,
+            rel_y,
+            rel_rotation,
+            color,
+            layer: 0,
+            kind: RenderItemKind::Line {
+                end: [end[0] - rel_x, end[1] - rel_y],
+                width,
+            },
+        }
+    }
+
+    /// Part of a ring centred on (rel_x, rel_y), starting at `start` radians (clockwise from the
+    /// right) and going `sweep` radians clockwise. A sweep of 2π draws the whole ring.
+    pub fn arc(rel_x: N, rel_y: N, radius: N, thickness: N, start: N, sweep: N, rel_rotation: N, color: [f32; 4]) -> Self {
+        RenderItem {
+            rel_x,
+            rel_y,
+            rel_rotation,
+            color,
+            layer: 0,
+            kind: RenderItemKind::Arc {
+                radius,
+                thickness,
+                start,
+                sweep,
+            },
+        }
+    }
+
+    /// `image` is a path in the media directory. The image is tinted by `color`, so use white to
+    /// draw it unchanged.
+    pub fn sprite<S: Into<String>>(rel_x: N, rel_y: N, image: S, w: N, h: N, flip_x: bool, rel_rotation: N, color: [f32; 4]) -> Self {
+        RenderItem {
+            rel_x,
+            rel_y,
+            rel_rotation,
+            color,
+            layer: 0,
+            kind: RenderItemKind::Sprite {
+                image: image.into(),
+                w,
+                h,
+                flip_x,
+                source: None,
+            },
+        }
+    }
+
+    /// Half the width and height of a box around (rel_x, rel_y) that the item stays inside, when drawn
+    /// at `rotation`. `None` for text, whose size on screen doesn't depend on the camera.
+    pub fn half_extents(&self, rotation: N) -> Option<(N, N)> {
+        // anything that isn't round gets a circle around it, which covers it at any rotation
+        let bound = |hw: N, hh: N| if rotation == 0.0 {
+            (hw, hh)
+        } else {
+            let r = (hw * hw + hh * hh).sqrt();
+            (r, r)
+        };
+
+        match self.kind {
+            RenderItemKind::Rectangle { w, h } | RenderItemKind::Ellipse { w, h } | RenderItemKind::Sprite { w, h, .. } => {
+                Some(bound(w / 2.0, h / 2.0))
+            }
+            RenderItemKind::Polygon { ref points } => {
+                let r = points
+                    .iter()
+                    .map(|p| (p[0] * p[0] + p[1] * p[1]).sqrt())
+                    .fold(0.0, N::max);
+                Some((r, r))
+            }
+            RenderItemKind::Line { end, width } => {
+                let r = (end[0] * end[0] + end[1] * end[1]).sqrt() + width / 2.0;
+                Some((r, r))
+            }
+            RenderItemKind::Arc { radius, thickness, .. } => {
+                let r = radius + thickness / 2.0;
+                Some((r, r))
+            }
+            RenderItemKind::Text { .. } | RenderItemKind::Info => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub enum RenderItemKind {
     Rectangle { w: N, h: N },
-    Text { text: String, size: u32 },
+    Text { text: String, size: u32, style: TextStyle },
     Info,
     Ellipse { w: N, h: N },
+    /// Filled as a triangle fan from the first point, so the outline should be convex
+    /// (or at least visible in full from its first point).
+    Polygon { points: Vec<[N; 2]> },
+    /// `end` is relative to the item's position.
+    Line { end: [N; 2], width: N },
+    Arc {
+        radius: N,
+        thickness: N,
+        start: N,
+        sweep: N,
+    },
+    /// An image stretched over a w by h rectangle. Entities with a `Facing` also flip it when facing left.
+    /// `source` is the part of the image to draw, as [x, y, w, h] in pixels, or the whole image if `None`.
+    Sprite {
+        image: String,
+        w: N,
+        h: N,
+        flip_x: bool,
+        source: Option<[N; 4]>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FontWeight {
+    Regular,
+    Bold,
+}
+
+/// How a piece of text is drawn. The default is plain bold text.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextStyle {
+    pub weight: FontWeight,
+    /// Colour of a thin outline around the letters.
+    pub outline: Option<[f32; 4]>,
+    /// Colour of a drop shadow, down and to the right of the text.
+    pub shadow: Option<[f32; 4]>,
+}
+
+impl TextStyle {
+    pub fn regular() -> Self {
+        TextStyle {
+            weight: FontWeight::Regular,
+            ..TextStyle::default()
+        }
+    }
+
+    pub fn bold() -> Self {
+        TextStyle::default()
+    }
+
+    pub fn with_outline(mut self, color: [f32; 4]) -> Self {
+        self.outline = Some(color);
+        self
+    }
+
+    pub fn with_shadow(mut self, color: [f32; 4]) -> Self {
+        self.shadow = Some(color);
+        self
+    }
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        TextStyle {
+            weight: FontWeight::Bold,
+            outline: None,
+            shadow: None,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -110,6 +358,13 @@ pub struct Renderable {
     pub y: N,
     pub rotation: N,
     pub items: Vec<RenderItem>,
+    /// Higher layers are drawn on top. See `LAYER_DEFAULT` and friends.
+    pub layer: i32,
+
+    // the transform at the start of the last World::tick
+    pub prev_x: N,
+    pub prev_y: N,
+    pub prev_rotation: N,
 }
 
 impl Renderable {
@@ -119,7 +374,44 @@ impl Renderable {
             y,
             rotation,
             items: Vec::new(),
+            layer: LAYER_DEFAULT,
+
+            prev_x: x,
+            prev_y: y,
+            prev_rotation: rotation,
+        }
+    }
+
+    pub fn set_transform(&mut self, x: N, y: N, rotation: N) {
+        self.x = x;
+        self.y = y;
+        self.rotation = rotation;
+    }
+
+    /// Remembers the current transform as the one to interpolate from.
+    pub fn store_previous_transform(&mut self) {
+        self.prev_x = self.x;
+        self.prev_y = self.y;
+        self.prev_rotation = self.rotation;
+    }
+
+    /// Returns the (x, y, rotation) alpha of the way from the previous transform to the current one.
+    pub fn interpolated(&self, alpha: N) -> (N, N, N) {
+        use std::f32::consts::PI;
+
+        // take the short way around
+        let mut rotation_diff = self.rotation - self.prev_rotation;
+        if rotation_diff > PI {
+            rotation_diff -= 2.0 * PI;
+        } else if rotation_diff < -PI {
+            rotation_diff += 2.0 * PI;
         }
+
+        (
+            self.prev_x + (self.x - self.prev_x) * alpha,
+            self.prev_y + (self.y - self.prev_y) * alpha,
+            self.prev_rotation + rotation_diff * alpha,
+        )
     }
 
     pub fn push(&mut self, item: RenderItem) {
@@ -130,6 +422,11 @@ impl Renderable {
         self.push(item);
         self
     }
+
+    pub fn with_layer(mut self, layer: i32) -> Self {
+        self.layer = layer;
+        self
+    }
 }
 
 impl Component for Renderable {
@@ -140,59 +437,229 @@ impl Component for Renderable {
 pub struct Player {
     pub moving_right: bool,
     pub moving_left: bool,
-    pub touching_ground: bool,
     pub release_jump: bool,
     pub picking_up: bool,
-
-    num_knives: usize,
-    max_num_knives: usize,
-
-    sensor_id: SensorID,
+    pub is_dead: bool,
+    pub carrying: Option<Carried>,
+    /// How many times thrown knives bounce off walls before they embed.
+    pub knife_bounces: u8,
+    /// Serrated knives make what they hit bleed.
+    pub serrated_knives: bool,
+    /// Whether jump was held last tick, so that air jumps need a fresh press.
+    pub jump_held: bool,
+    /// Jumps made since last touching the ground.
+    pub jumps_used: u8,
 }
 
 impl Player {
-    pub fn new(sensor_id: SensorID, max_num_knives: usize) -> Self {
+    pub fn new() -> Self {
         Player {
             moving_right: false,
             moving_left: false,
-            touching_ground: false,
             release_jump: false,
             picking_up: false,
+            is_dead: false,
+            carrying: None,
+            knife_bounces: 0,
+            serrated_knives: false,
+            jump_held: false,
+            jumps_used: 0,
+        }
+    }
+}
 
-            num_knives: max_num_knives,
-            max_num_knives,
+impl Component for Player {
+    type Storage = DenseVecStorage<Self>;
+}
 
-            sensor_id,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Weapon {
+    Knife,
+    Grenade,
+    Melee,
+}
+
+impl Weapon {
+    pub fn name(self) -> &'static str {
+        match self {
+            Weapon::Knife => "Knife",
+            Weapon::Grenade => "Grenade",
+            Weapon::Melee => "Melee",
         }
     }
+}
 
-    pub fn sensor_id(&self) -> SensorID {
-        self.sensor_id
+#[derive(Debug, Clone, Copy)]
+pub struct Ammo {
+    pub current: usize,
+    pub max: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct WeaponSlot {
+    pub weapon: Weapon,
+    /// None for weapons which never run out.
+    pub ammo: Option<Ammo>,
+}
+
+impl WeaponSlot {
+    pub fn limited(weapon: Weapon, max: usize) -> Self {
+        WeaponSlot {
+            weapon,
+            ammo: Some(Ammo { current: max, max }),
+        }
+    }
+
+    pub fn unlimited(weapon: Weapon) -> Self {
+        WeaponSlot { weapon, ammo: None }
+    }
+}
+
+/// The weapons an entity carries, and which one is in hand.
+#[derive(Debug, Clone)]
+pub struct Inventory {
+    slots: Vec<WeaponSlot>,
+    selected: usize,
+}
+
+impl Inventory {
+    pub fn new(slots: Vec<WeaponSlot>) -> Self {
+        assert!(!slots.is_empty(), "an inventory needs at least one weapon");
+        Inventory { slots, selected: 0 }
+    }
+
+    pub fn slots(&self) -> &[WeaponSlot] {
+        &self.slots
+    }
+
+    pub fn selected_index(&self) -> usize {
+        self.selected
+    }
+
+    pub fn selected(&self) -> Weapon {
+        self.slots[self.selected].weapon
     }
 
-    pub fn dec_knives(&mut self) {
-        if self.num_knives >= 1 {
-            self.num_knives -= 1;
+    /// Out of range indices are ignored.
+    pub fn select(&mut self, index: usize) {
+        if index < self.slots.len() {
+            self.selected = index;
         }
     }
 
-    pub fn inc_knives(&mut self) {
-        if self.num_knives < self.max_num_knives {
-            self.num_knives += 1;
+    /// The index `step` slots away from the selected one, wrapping around.
+    pub fn offset_index(&self, step: isize) -> usize {
+        let len = self.slots.len() as isize;
+        (((self.selected as isize + step) % len + len) % len) as usize
+    }
+
+    pub fn ammo(&self, weapon: Weapon) -> Option<Ammo> {
+        self.slot(weapon).and_then(|slot| slot.ammo)
+    }
+
+    /// Uses up one shot of `weapon`. Returns false if it isn't held or has run out.
+    pub fn take(&mut self, weapon: Weapon) -> bool {
+        match self.slot_mut(weapon) {
+            Some(&mut WeaponSlot { ammo: Some(ref mut ammo), .. }) => if ammo.current > 0 {
+                ammo.current -= 1;
+                true
+            } else {
+                false
+            },
+            Some(_) => true,
+            None => false,
+        }
+    }
+
+    pub fn refill(&mut self, weapon: Weapon, amount: usize) {
+        if let Some(&mut WeaponSlot { ammo: Some(ref mut ammo), .. }) = self.slot_mut(weapon) {
+            ammo.current = cmp::min(ammo.current + amount, ammo.max);
         }
     }
 
-    pub fn num_knives(&self) -> usize {
-        self.num_knives
+    fn slot(&self, weapon: Weapon) -> Option<&WeaponSlot> {
+        self.slots.iter().find(|slot| slot.weapon == weapon)
     }
 
-    pub fn max_num_knives(&self) -> usize {
-        self.max_num_knives
+    fn slot_mut(&mut self, weapon: Weapon) -> Option<&mut WeaponSlot> {
+        self.slots.iter_mut().find(|slot| slot.weapon == weapon)
     }
 }
 
-impl Component for Player {
-    type Storage = DenseVecStorage<Self>;
+impl Component for Inventory {
+    type Storage = HashMapStorage<Self>;
+}
+
+/// Explodes when its fuse (a `TimedRemove`) runs out, hurting and pushing everything within `radius`.
+#[derive(Debug, Clone)]
+pub struct Grenade {
+    pub radius: N,
+    /// Damage at the centre of the explosion, falling off to nothing at the edge.
+    pub damage: u16,
+    /// Put on everything the explosion hurts.
+    pub effect: Option<StatusEffect>,
+}
+
+impl Component for Grenade {
+    type Storage = HashMapStorage<Self>;
+}
+
+/// A body held above the player's head by a joint.
+#[derive(Debug, Clone, Copy)]
+pub struct Carried {
+    pub entity: Entity,
+    pub body_id: RigidBodyID,
+    pub joint: JointID,
+    /// The body is made light while carried, so it doesn't drag the player around; this is what it was before.
+    pub inv_mass: N,
+}
+
+/// Marks small bodies the player can pick up and throw.
+#[derive(Debug, Clone)]
+pub struct Carryable;
+
+impl Component for Carryable {
+    type Storage = HashMapStorage<Self>;
+}
+
+/// A body thrown by `thrower`, which damages what it hits according to its momentum.
+#[derive(Debug, Clone)]
+pub struct Thrown {
+    pub thrower: Entity,
+    /// Velocity as of the previous step, since by the time we see a contact the impact has already slowed it down.
+    pub last_velocity: Vector<N>,
+}
+
+impl Component for Thrown {
+    type Storage = HashMapStorage<Self>;
+}
+
+/// A sensor just below an entity's body, used to tell whether it's standing on something.
+#[derive(Debug, Clone)]
+pub struct GroundSensor {
+    pub touching_ground: bool,
+    /// Velocity of the moving platform being stood on, if any.
+    pub ground_velocity: Vector<N>,
+
+    sensor_id: SensorID,
+}
+
+impl GroundSensor {
+    pub fn new(sensor_id: SensorID) -> Self {
+        GroundSensor {
+            touching_ground: false,
+            ground_velocity: Vector::zero(),
+            sensor_id,
+        }
+    }
+
+    pub fn sensor_id(&self) -> SensorID {
+        self.sensor_id
+    }
+}
+
+impl Component for GroundSensor {
+    type Storage = HashMapStorage<Self>;
 }
 
 
@@ -215,6 +682,23 @@ impl Component for TimeStopStore {
 #[derive(Debug)]
 pub struct Knife {
     pub stuck_into_entity: Option<Entity>,
+    pub joint: Option<JointID>,
+    /// Remaining ricochets. While this is above zero the knife bounces off static geometry,
+    /// and hurts enemies without getting stuck in them.
+    pub bounces_left: u8,
+    /// Put on whatever the knife hits.
+    pub effect: Option<StatusEffect>,
+}
+
+impl Knife {
+    pub fn new(bounces: u8) -> Self {
+        Knife {
+            stuck_into_entity: None,
+            joint: None,
+            bounces_left: bounces,
+            effect: None,
+        }
+    }
 }
 
 impl Component for Knife {
@@ -270,6 +754,14 @@ impl Component for Remove {
     type Storage = HashMapStorage<Self>;
 }
 
+/// Marks the short-lived bits of debris thrown out by explosions and hits.
+#[derive(Debug, Clone, Default)]
+pub struct Particle;
+
+impl Component for Particle {
+    type Storage = HashMapStorage<Self>;
+}
+
 #[derive(Debug, Clone)]
 pub struct TimedRemove(pub N);
 
@@ -277,6 +769,144 @@ impl Component for TimedRemove {
     type Storage = HashMapStorage<Self>;
 }
 
+/// Who an entity fights for. Damage from an entity's own team is ignored, except among neutrals.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Team {
+    Player,
+    Enemy,
+    Neutral,
+}
+
+impl Team {
+    pub fn is_friendly_to(self, other: Team) -> bool {
+        self == other && self != Team::Neutral
+    }
+}
+
+impl Component for Team {
+    type Storage = VecStorage<Self>;
+}
+
+/// Seconds left of an entity flashing white after being hit.
+#[derive(Debug, Clone, Copy)]
+pub struct HitFlash(pub N);
+
+impl Component for HitFlash {
+    type Storage = HashMapStorage<Self>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum StatusKind {
+    /// Loses `damage` hitpoints every second.
+    Bleed { damage: u16 },
+    /// Can't move or attack.
+    Stun,
+    /// Moves at `factor` times normal speed.
+    Slow { factor: N },
+}
+
+/// A timed effect on whatever it's applied to, e.g. by a weapon.
+#[derive(Debug, Clone, Copy)]
+pub struct StatusEffect {
+    pub kind: StatusKind,
+    pub remaining: N,
+    // time since bleeding last did damage
+    since_tick: N,
+}
+
+impl StatusEffect {
+    pub fn new(kind: StatusKind, duration: N) -> Self {
+        StatusEffect {
+            kind,
+            remaining: duration,
+            since_tick: 0.0,
+        }
+    }
+}
+
+const BLEED_INTERVAL: N = 1.0;
+
+/// The status effects currently on an entity. At most one of each kind is active at once.
+#[derive(Debug, Clone, Default)]
+pub struct StatusEffects {
+    effects: Vec<StatusEffect>,
+}
+
+impl StatusEffects {
+    /// Adds the effect, or refreshes it if one of the same kind is already active.
+    pub fn add(&mut self, effect: StatusEffect) {
+        let same_kind = self.effects
+            .iter_mut()
+            .find(|active| mem::discriminant(&active.kind) == mem::discriminant(&effect.kind));
+
+        match same_kind {
+            Some(active) => {
+                active.kind = effect.kind;
+                active.remaining = active.remaining.max(effect.remaining);
+            }
+            None => self.effects.push(effect),
+        }
+    }
+
+    pub fn effects(&self) -> &[StatusEffect] {
+        &self.effects
+    }
+
+    pub fn is_stunned(&self) -> bool {
+        self.effects.iter().any(|effect| effect.kind == StatusKind::Stun)
+    }
+
+    /// What to multiply movement speeds by.
+    pub fn speed_multiplier(&self) -> N {
+        self.effects.iter().fold(1.0, |multiplier, effect| match effect.kind {
+            StatusKind::Stun => 0.0,
+            StatusKind::Slow { factor } => multiplier.min(factor),
+            StatusKind::Bleed { .. } => multiplier,
+        })
+    }
+
+    /// Advances the effects by `time`, dropping those which have run out.
+    /// Returns how much bleeding damage is due.
+    pub fn tick(&mut self, time: N) -> u16 {
+        let mut damage = 0;
+
+        for effect in &mut self.effects {
+            effect.remaining -= time;
+
+            if let StatusKind::Bleed { damage: bleed } = effect.kind {
+                effect.since_tick += time;
+                if effect.since_tick >= BLEED_INTERVAL {
+                    effect.since_tick -= BLEED_INTERVAL;
+                    damage += bleed;
+                }
+            }
+        }
+
+        self.effects.retain(|effect| effect.remaining > 0.0);
+
+        damage
+    }
+}
+
+impl Component for StatusEffects {
+    type Storage = HashMapStorage<Self>;
+}
+
+/// Seconds left during which an entity ignores damage.
+#[derive(Debug, Clone, Copy)]
+pub struct Invulnerable(pub N);
+
+impl Invulnerable {
+    /// Whether it should be hidden this frame, so that it blinks.
+    pub fn blink_hidden(&self) -> bool {
+        (self.0 / 0.08) as u32 % 2 == 1
+    }
+}
+
+impl Component for Invulnerable {
+    type Storage = HashMapStorage<Self>;
+}
+
 #[derive(Debug, Clone)]
 pub struct Name(pub String);
 
@@ -299,10 +929,799 @@ impl Component for BasicEnemy {
     type Storage = HashMapStorage<Self>;
 }
 
-// TODO: CCD
-#[derive(Debug, Clone)]
-pub struct Bullet;
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EnemyKind {
+    /// Only hurts by touching.
+    Melee,
+    /// Also shoots at the player.
+    Ranged,
+    /// Hovers above the player, ignoring gravity, and dives at them.
+    Flying,
+}
 
-impl Component for Bullet {
+impl Default for EnemyKind {
+    fn default() -> Self {
+        EnemyKind::Melee
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Facing {
+    Left,
+    Right,
+}
+
+impl Facing {
+    pub fn from_direction(x: N) -> Self {
+        if x < 0.0 {
+            Facing::Left
+        } else {
+            Facing::Right
+        }
+    }
+
+    pub fn sign(self) -> N {
+        match self {
+            Facing::Left => -1.0,
+            Facing::Right => 1.0,
+        }
+    }
+}
+
+impl Default for Facing {
+    fn default() -> Self {
+        Facing::Right
+    }
+}
+
+impl Component for Facing {
+    type Storage = HashMapStorage<Self>;
+}
+
+/// Walks back and forth along a path of waypoints, only caring about their x positions.
+#[derive(Debug, Clone)]
+pub struct Patrol {
+    pub waypoints: Vec<Vector<N>>,
+    pub speed: N,
+
+    next: usize,
+}
+
+impl Patrol {
+    pub fn new(waypoints: Vec<Vector<N>>, speed: N) -> Self {
+        assert!(!waypoints.is_empty());
+
+        Patrol {
+            waypoints,
+            speed,
+            next: 0,
+        }
+    }
+
+    pub fn target(&self) -> Vector<N> {
+        self.waypoints[self.next]
+    }
+
+    pub fn advance(&mut self) {
+        self.next = (self.next + 1) % self.waypoints.len();
+    }
+}
+
+impl Component for Patrol {
+    type Storage = HashMapStorage<Self>;
+}
+
+/// Multiplies the gravity a body feels, e.g. 0 for things that fly.
+/// Applied by `GravityScaleSystem` while the entity is alive (or has no hitpoints).
+#[derive(Debug, Clone, Copy)]
+pub struct GravityScale(pub N);
+
+impl Component for GravityScale {
+    type Storage = HashMapStorage<Self>;
+}
+
+/// Caps how fast a body may move and spin. Enforced by `VelocityLimitSystem` at the end of
+/// every step, so that stacked impulses can't fling the body through walls.
+#[derive(Debug, Clone, Copy)]
+pub struct VelocityLimit {
+    pub max_lin: N,
+    pub max_ang: N,
+}
+
+impl VelocityLimit {
+    pub fn new(max_lin: N, max_ang: N) -> Self {
+        VelocityLimit { max_lin, max_ang }
+    }
+
+    pub fn clamp_lin(&self, vel: Vector<N>) -> Vector<N> {
+        let speed = vel.norm();
+        if speed > self.max_lin {
+            vel * (self.max_lin / speed)
+        } else {
+            vel
+        }
+    }
+
+    pub fn clamp_ang(&self, vel: Orientation<N>) -> Orientation<N> {
+        if vel.x.abs() > self.max_ang {
+            Orientation::new(vel.x.signum() * self.max_ang)
+        } else {
+            vel
+        }
+    }
+}
+
+impl Component for VelocityLimit {
+    type Storage = DenseVecStorage<Self>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FlyerState {
+    /// Keeping station above the player, waiting to dive.
+    Hovering { remaining: N },
+    /// Heading for where the player was when the dive started.
+    Diving { target: Vector<N>, remaining: N },
+    /// Climbing back up after a dive.
+    Recovering { remaining: N },
+}
+
+/// Steering for flying enemies.
+#[derive(Debug, Clone)]
+pub struct Flyer {
+    pub hover_height: N,
+    pub speed: N,
+    pub dive_speed: N,
+    pub dive_interval: N,
+    pub state: FlyerState,
+}
+
+impl Flyer {
+    pub fn new(hover_height: N, speed: N, dive_speed: N, dive_interval: N) -> Self {
+        Flyer {
+            hover_height,
+            speed,
+            dive_speed,
+            dive_interval,
+            state: FlyerState::Hovering { remaining: dive_interval },
+        }
+    }
+}
+
+impl Component for Flyer {
+    type Storage = HashMapStorage<Self>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BossState {
+    /// Resting between attacks.
+    Idle { remaining: N },
+    Charging { direction: N, remaining: N },
+}
+
+/// A big enemy which gets more dangerous as it is hurt.
+/// Phase 1 only charges, phase 2 also fires spreads of bullets, and phase 3 throws crates too and attacks faster.
+#[derive(Debug, Clone)]
+pub struct Boss {
+    pub name: String,
+    pub phase: u8,
+    pub state: BossState,
+    pub half_height: N,
+}
+
+impl Boss {
+    pub fn new(name: String, half_height: N) -> Self {
+        Boss {
+            name,
+            phase: 1,
+            state: BossState::Idle { remaining: 2.0 },
+            half_height,
+        }
+    }
+
+    /// Which phase the boss should be in, given its hitpoints.
+    pub fn phase_for(hitpoints: &Hitpoints) -> u8 {
+        let fraction = hitpoints.current() as N / hitpoints.max() as N;
+        if fraction > 2.0 / 3.0 {
+            1
+        } else if fraction > 1.0 / 3.0 {
+            2
+        } else {
+            3
+        }
+    }
+}
+
+impl Component for Boss {
+    type Storage = HashMapStorage<Self>;
+}
+
+/// Periodically spawns enemies, up to a cap on how many are alive at once.
+#[derive(Debug, Clone)]
+pub struct Spawner {
+    pub rect: Rect,
+    pub kind: EnemyKind,
+    pub interval: N,
+    pub max_alive: usize,
+    /// How many more enemies it will spawn in total; None for no limit.
+    pub remaining: Option<u32>,
+    /// The spawner waits for the player to enter this before starting.
+    pub trigger_sensor: Option<SensorID>,
+    pub active: bool,
+
+    timer: N,
+}
+
+impl Spawner {
+    pub fn new(rect: Rect, kind: EnemyKind, interval: N, max_alive: usize, remaining: Option<u32>, trigger_sensor: Option<SensorID>) -> Self {
+        Spawner {
+            rect,
+            kind,
+            interval,
+            max_alive,
+            remaining,
+            trigger_sensor,
+            active: trigger_sensor.is_none(),
+            // the first enemy comes straight away
+            timer: 0.0,
+        }
+    }
+
+    /// Counts down, returning true when it's time to spawn (if there's room).
+    pub fn tick(&mut self, time: N) -> bool {
+        self.timer = (self.timer - time).max(0.0);
+        self.timer == 0.0 && self.remaining != Some(0)
+    }
+
+    pub fn spawned(&mut self) {
+        self.timer = self.interval;
+        if let Some(ref mut remaining) = self.remaining {
+            *remaining -= 1;
+        }
+    }
+}
+
+impl Component for Spawner {
+    type Storage = HashMapStorage<Self>;
+}
+
+/// Links an enemy to the `Spawner` that created it, so the spawner can count how many are still alive.
+#[derive(Debug, Clone, Copy)]
+pub struct SpawnedBy(pub Entity);
+
+impl Component for SpawnedBy {
+    type Storage = HashMapStorage<Self>;
+}
+
+/// Notices the player within `radius` and goes after them until they get well away.
+/// Enemies with an `Aggro` only shoot or chase while engaged.
+#[derive(Debug, Clone)]
+pub struct Aggro {
+    pub radius: N,
+    pub engaged: bool,
+}
+
+impl Aggro {
+    pub fn new(radius: N) -> Self {
+        Aggro {
+            radius,
+            engaged: false,
+        }
+    }
+}
+
+impl Component for Aggro {
+    type Storage = HashMapStorage<Self>;
+}
+
+// TODO: CCD
+#[derive(Debug, Clone)]
+pub struct Bullet;
+
+impl Component for Bullet {
+    type Storage = HashMapStorage<Self>;
+}
+
+/// What a moving platform does after reaching the last waypoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LoopMode {
+    /// Go back through the waypoints in reverse.
+    PingPong,
+    /// Head straight back to the first waypoint.
+    Loop,
+    /// Stop at the last waypoint.
+    Once,
+}
+
+impl Default for LoopMode {
+    fn default() -> Self {
+        LoopMode::PingPong
+    }
+}
+
+/// Drives a kinematic body along a path of waypoints.
+#[derive(Debug, Clone)]
+pub struct MovingPlatform {
+    pub waypoints: Vec<Vector<N>>,
+    pub speed: N,
+    pub loop_mode: LoopMode,
+
+    next: usize,
+    forwards: bool,
+    finished: bool,
+}
+
+impl MovingPlatform {
+    pub fn new(waypoints: Vec<Vector<N>>, speed: N, loop_mode: LoopMode) -> Self {
+        assert!(!waypoints.is_empty());
+
+        MovingPlatform {
+            next: if waypoints.len() > 1 { 1 } else { 0 },
+            waypoints,
+            speed,
+            loop_mode,
+            forwards: true,
+            finished: false,
+        }
+    }
+
+    /// The waypoint currently being travelled to, or None once a `LoopMode::Once` platform has arrived.
+    pub fn target(&self) -> Option<Vector<N>> {
+        if self.finished {
+            None
+        } else {
+            Some(self.waypoints[self.next])
+        }
+    }
+
+    /// Moves on to the waypoint after the current target.
+    pub fn advance(&mut self) {
+        let last = self.waypoints.len() - 1;
+        if last == 0 {
+            self.finished = true;
+            return;
+        }
+
+        match self.loop_mode {
+            LoopMode::PingPong => {
+                if self.forwards && self.next == last {
+                    self.forwards = false;
+                } else if !self.forwards && self.next == 0 {
+                    self.forwards = true;
+                }
+
+                if self.forwards {
+                    self.next += 1;
+                } else {
+                    self.next -= 1;
+                }
+            }
+            LoopMode::Loop => self.next = (self.next + 1) % self.waypoints.len(),
+            LoopMode::Once => if self.next == last {
+                self.finished = true;
+            } else {
+                self.next += 1;
+            },
+        }
+    }
+}
+
+impl Component for MovingPlatform {
+    type Storage = HashMapStorage<Self>;
+}
+
+/// Dynamic bodies inside the sensor fall with this gravity instead of the world's.
+#[derive(Debug, Clone)]
+pub struct GravityZone {
+    pub sensor_id: SensorID,
+    pub gravity: Vector<N>,
+}
+
+impl Component for GravityZone {
+    type Storage = HashMapStorage<Self>;
+}
+
+/// Pushes dynamic bodies inside the sensor with a constant force.
+#[derive(Debug, Clone)]
+pub struct ForceField {
+    pub sensor_id: SensorID,
+    pub force: Vector<N>,
+}
+
+impl Component for ForceField {
+    type Storage = HashMapStorage<Self>;
+}
+
+/// Damages entities with `Hitpoints` that touch this one, at most once every `cooldown` seconds.
+/// Entities that deal contact damage don't hurt each other.
+#[derive(Debug, Clone)]
+pub struct ContactDamage {
+    pub damage: u16,
+    pub cooldown: N,
+    /// If set, the component is removed after the first hit (e.g. for bullets).
+    pub single_use: bool,
+
+    remaining: N,
+}
+
+impl ContactDamage {
+    pub fn new(damage: u16, cooldown: N) -> Self {
+        ContactDamage {
+            damage,
+            cooldown,
+            single_use: false,
+            remaining: 0.0,
+        }
+    }
+
+    pub fn single_use(damage: u16) -> Self {
+        ContactDamage {
+            single_use: true,
+            ..ContactDamage::new(damage, 0.0)
+        }
+    }
+
+    pub fn ready(&self) -> bool {
+        self.remaining <= 0.0
+    }
+
+    pub fn tick(&mut self, time: N) {
+        self.remaining = (self.remaining - time).max(0.0);
+    }
+
+    pub fn reset(&mut self) {
+        self.remaining = self.cooldown;
+    }
+}
+
+impl Component for ContactDamage {
+    type Storage = HashMapStorage<Self>;
+}
+
+/// Periodically fires a bullet at the player while they are in range, aiming ahead of where they are moving.
+#[derive(Debug, Clone)]
+pub struct RangedAttacker {
+    pub interval: N,
+    pub range: N,
+    pub bullet_speed: N,
+    pub bullet_radius: N,
+    pub bullet_damage: u16,
+    /// How far from the centre of the body bullets are spawned, so they don't hit the shooter.
+    pub muzzle_distance: N,
+
+    remaining: N,
+}
+
+impl RangedAttacker {
+    pub fn new(interval: N, range: N, bullet_speed: N, bullet_radius: N, muzzle_distance: N) -> Self {
+        RangedAttacker {
+            interval,
+            range,
+            bullet_speed,
+            bullet_radius,
+            bullet_damage: 1,
+            muzzle_distance,
+            remaining: interval,
+        }
+    }
+
+    /// Counts down the interval, returning true (and restarting it) when it's time to fire.
+    pub fn tick(&mut self, time: N) -> bool {
+        self.remaining -= time;
+        if self.remaining <= 0.0 {
+            self.remaining = self.interval;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl Component for RangedAttacker {
+    type Storage = HashMapStorage<Self>;
+}
+
+/// A dead entity, which fades out and is removed once `remaining` runs out.
+#[derive(Debug, Clone)]
+pub struct Corpse {
+    pub remaining: N,
+    pub fade_time: N,
+}
+
+impl Corpse {
+    pub fn new(remaining: N, fade_time: N) -> Self {
+        Corpse {
+            remaining,
+            fade_time,
+        }
+    }
+
+    /// From 1 (fully visible) down to 0, over the last `fade_time` seconds.
+    pub fn alpha(&self) -> f32 {
+        (self.remaining / self.fade_time).max(0.0).min(1.0)
+    }
+}
+
+impl Component for Corpse {
+    type Storage = HashMapStorage<Self>;
+}
+
+/// Fades the text items of a renderable in, holds them, then fades them out again.
+#[derive(Debug, Clone)]
+pub struct TextFade {
+    pub fade_in: N,
+    pub hold: N,
+    pub fade_out: N,
+    pub elapsed: N,
+    /// Whether the entity is removed once the text has faded out.
+    pub remove_when_done: bool,
+}
+
+impl TextFade {
+    pub fn new(fade_in: N, hold: N, fade_out: N) -> Self {
+        TextFade {
+            fade_in,
+            hold,
+            fade_out,
+            elapsed: 0.0,
+            remove_when_done: false,
+        }
+    }
+
+    pub fn removing(mut self) -> Self {
+        self.remove_when_done = true;
+        self
+    }
+
+    /// From 0 up to 1 while fading in, then back down to 0 while fading out.
+    pub fn alpha(&self) -> f32 {
+        if self.elapsed < self.fade_in {
+            return self.elapsed / self.fade_in;
+        }
+
+        let fade_out_start = self.fade_in + self.hold;
+        if self.elapsed < fade_out_start {
+            1.0
+        } else if self.fade_out > 0.0 {
+            (1.0 - (self.elapsed - fade_out_start) / self.fade_out).max(0.0)
+        } else {
+            0.0
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.elapsed >= self.fade_in + self.hold + self.fade_out
+    }
+}
+
+impl Component for TextFade {
+    type Storage = HashMapStorage<Self>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HazardKind {
+    Spikes,
+    Lava,
+}
+
+/// Hurts anything with hitpoints that touches the body.
+/// Each victim has its own cooldown, so standing on spikes hurts repeatedly but not every frame.
+#[derive(Debug, Clone)]
+pub struct Hazard {
+    pub kind: HazardKind,
+    /// None kills instantly.
+    pub damage: Option<u16>,
+    pub cooldown: N,
+
+    victims: HashMap<Entity, N>,
+}
+
+impl Hazard {
+    pub fn new(kind: HazardKind, damage: Option<u16>, cooldown: N) -> Self {
+        Hazard {
+            kind,
+            damage,
+            cooldown,
+            victims: HashMap::new(),
+        }
+    }
+
+    pub fn tick(&mut self, time: N) {
+        for remaining in self.victims.values_mut() {
+            *remaining -= time;
+        }
+        self.victims.retain(|_, remaining| *remaining > 0.0);
+    }
+
+    /// Returns whether the entity can be hurt now, starting its cooldown if so.
+    pub fn try_hit(&mut self, entity: Entity) -> bool {
+        if self.victims.contains_key(&entity) {
+            false
+        } else {
+            self.victims.insert(entity, self.cooldown);
+            true
+        }
+    }
+}
+
+impl Component for Hazard {
+    type Storage = HashMapStorage<Self>;
+}
+
+/// Adds `value` to the score when the player touches it, then disappears.
+#[derive(Debug, Clone)]
+pub struct Coin {
+    pub sensor_id: SensorID,
+    pub value: u32,
+}
+
+impl Component for Coin {
+    type Storage = HashMapStorage<Self>;
+}
+
+/// A static body which opens (letting everything through) and closes when signalled.
+#[derive(Debug, Clone)]
+pub struct Door {
+    pub id: LinkId,
+    pub open: bool,
+}
+
+impl Component for Door {
+    type Storage = HashMapStorage<Self>;
+}
+
+/// Opens its target when the player touches it or a knife hits it.
+#[derive(Debug, Clone)]
+pub struct Switch {
+    pub sensor_id: SensorID,
+    pub target: LinkId,
+    pub pressed: bool,
+}
+
+impl Component for Switch {
+    type Storage = HashMapStorage<Self>;
+}
+
+/// Opens its target when the player picks it up.
+#[derive(Debug, Clone)]
+pub struct Key {
+    pub sensor_id: SensorID,
+    pub target: LinkId,
+}
+
+impl Component for Key {
+    type Storage = HashMapStorage<Self>;
+}
+
+/// Runs its actions when the player enters the sensor; only the first time, unless `repeat` is set.
+#[derive(Debug, Clone)]
+pub struct Trigger {
+    pub sensor_id: SensorID,
+    pub actions: Vec<Action>,
+    pub repeat: bool,
+    pub fired: bool,
+}
+
+impl Component for Trigger {
+    type Storage = HashMapStorage<Self>;
+}
+
+/// Something dropped by a destroyed crate.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum Loot {
+    /// A knife lying free, to be picked up like a thrown one.
+    Knife,
+    Health(u16),
+    /// This many coins, each worth 1.
+    Coins(u32),
+}
+
+/// A crate with hitpoints, which bursts when they run out.
+#[derive(Debug, Clone)]
+pub struct Crate {
+    pub material: CrateMaterial,
+    pub contents: Vec<Loot>,
+}
+
+impl Component for Crate {
+    type Storage = HashMapStorage<Self>;
+}
+
+/// What a pickup gives the player.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum PickupKind {
+    Health(u16),
+    Knives(usize),
+    Grenades(usize),
+    /// Seconds of time stop charge.
+    TimeStopEnergy(N),
+    /// Makes thrown knives bounce off walls this many times before they embed.
+    Ricochet(u8),
+    /// Makes thrown knives serrated, so they cause bleeding.
+    Serrated,
+}
+
+/// Gives the player something when they touch it, bobbing up and down until then.
+#[derive(Debug, Clone)]
+pub struct Pickup {
+    pub sensor_id: SensorID,
+    pub kind: PickupKind,
+    /// The resting position, which the pickup bobs around.
+    pub origin: Vector<N>,
+    pub bob_phase: N,
+    /// How long the pickup takes to come back once collected. Without this it's gone for good.
+    pub respawn_time: Option<N>,
+    /// Time left until it's back, while it's been collected.
+    pub respawn_remaining: Option<N>,
+    /// The pickup's render items, put away while it waits to respawn.
+    pub hidden_items: Vec<RenderItem>,
+}
+
+impl Pickup {
+    pub fn new(sensor_id: SensorID, kind: PickupKind, origin: Vector<N>, respawn_time: Option<N>) -> Self {
+        Pickup {
+            sensor_id,
+            kind,
+            origin,
+            bob_phase: 0.0,
+            respawn_time,
+            respawn_remaining: None,
+            hidden_items: Vec::new(),
+        }
+    }
+
+    pub fn is_available(&self) -> bool {
+        self.respawn_remaining.is_none()
+    }
+}
+
+impl Component for Pickup {
+    type Storage = HashMapStorage<Self>;
+}
+
+/// Completes the level when the player enters the sensor.
+#[derive(Debug, Clone)]
+pub struct Goal {
+    pub sensor_id: SensorID,
+    pub reached: bool,
+}
+
+impl Component for Goal {
+    type Storage = HashMapStorage<Self>;
+}
+
+/// Lights up the area around the entity's renderable in dark levels.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Light {
+    pub radius: N,
+    /// How much the light brightens things at its centre, where 1 is fully lit.
+    pub intensity: f32,
+    #[serde(default = "default_light_color")]
+    pub color: [f32; 3],
+}
+
+fn default_light_color() -> [f32; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+impl Light {
+    pub fn new(radius: N, intensity: f32, color: [f32; 3]) -> Self {
+        Light {
+            radius,
+            intensity,
+            color,
+        }
+    }
+
+    /// How much light reaches a point `distance` away, falling off smoothly to nothing at the radius.
+    pub fn falloff(&self, distance: N) -> f32 {
+        if distance >= self.radius {
+            return 0.0;
+        }
+        let t = 1.0 - distance / self.radius;
+        self.intensity * t * t
+    }
+}
+
+impl Component for Light {
     type Storage = HashMapStorage<Self>;
 }
@@ -0,0 +1,65 @@
+use std::mem;
+
+use specs::Entity;
+use nphysics::math::{Point, Vector};
+use num::Zero;
+
+use super::{StatusEffect, Team, N};
+
+/// A hit waiting to be dealt by `DamageSystem`.
+#[derive(Debug, Clone, Copy)]
+pub struct DamageEvent {
+    pub target: Entity,
+    pub amount: u16,
+    /// Applied to the target's rigid body, e.g. to knock it away from whatever hit it.
+    pub impulse: Vector<N>,
+    pub position: Point<N>,
+    /// Put on the target along with the damage.
+    pub effect: Option<StatusEffect>,
+    /// The team of whatever dealt the damage. Nothing is hurt by its own team.
+    pub source_team: Option<Team>,
+}
+
+impl DamageEvent {
+    pub fn new(target: Entity, amount: u16, position: Point<N>) -> Self {
+        DamageEvent {
+            target,
+            amount,
+            impulse: Vector::zero(),
+            position,
+            effect: None,
+            source_team: None,
+        }
+    }
+
+    pub fn with_impulse(mut self, impulse: Vector<N>) -> Self {
+        self.impulse = impulse;
+        self
+    }
+
+    pub fn with_effect(mut self, effect: Option<StatusEffect>) -> Self {
+        self.effect = effect;
+        self
+    }
+
+    pub fn with_source_team(mut self, team: Option<Team>) -> Self {
+        self.source_team = team;
+        self
+    }
+}
+
+/// Collects `DamageEvent`s pushed during a step, so that all damage goes through one place.
+#[derive(Default)]
+pub struct DamageEvents {
+    events: Vec<DamageEvent>,
+}
+
+impl DamageEvents {
+    pub fn push(&mut self, event: DamageEvent) {
+        self.events.push(event);
+    }
+
+    pub fn drain(&mut self) -> Vec<DamageEvent> {
+        mem::replace(&mut self.events, Vec::new())
+    }
+}
@@ -0,0 +1,89 @@
+use super::*;
+
+use nphysics::math::{Point, Vector};
+
+/// A purely cosmetic particle, moved on the game thread without a physics body.
+#[derive(Debug, Clone, Copy)]
+pub struct EffectParticle {
+    pub pos: Point<N>,
+    pub prev_pos: Point<N>,
+    pub velocity: Vector<N>,
+    pub size: N,
+    /// Seconds left before it disappears.
+    pub ttl: N,
+    pub color: [f32; 4],
+    /// The particle comes to rest when it falls to this height, standing in for the ground.
+    pub floor: Option<N>,
+}
+
+impl EffectParticle {
+    pub fn new(pos: Point<N>, velocity: Vector<N>, size: N, ttl: N, color: [f32; 4]) -> Self {
+        EffectParticle {
+            pos,
+            prev_pos: pos,
+            velocity,
+            size,
+            ttl,
+            color,
+            floor: None,
+        }
+    }
+
+    pub fn with_floor(mut self, floor: N) -> Self {
+        self.floor = Some(floor);
+        self
+    }
+
+    /// Where to draw the particle, `alpha` of the way from its previous position to its current one.
+    pub fn interpolated(&self, alpha: N) -> Point<N> {
+        self.prev_pos + (self.pos - self.prev_pos) * alpha
+    }
+}
+
+/// Cheap particles for blood, sparks and the like, which don't need to push anything around.
+/// Debris that matters to gameplay is still made of physics bodies; see `World::new_particle`.
+#[derive(Debug, Default)]
+pub struct EffectParticles {
+    particles: Vec<EffectParticle>,
+}
+
+impl EffectParticles {
+    pub fn push(&mut self, particle: EffectParticle) {
+        self.particles.push(particle);
+    }
+
+    pub fn extend<I: IntoIterator<Item = EffectParticle>>(&mut self, it: I) {
+        self.particles.extend(it);
+    }
+
+    pub fn iter(&self) -> ::std::slice::Iter<EffectParticle> {
+        self.particles.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.particles.len()
+    }
+
+    pub fn store_previous_positions(&mut self) {
+        for particle in &mut self.particles {
+            particle.prev_pos = particle.pos;
+        }
+    }
+
+    pub fn update(&mut self, dt: N, gravity: Vector<N>) {
+        for particle in &mut self.particles {
+            particle.ttl -= dt;
+            particle.velocity += gravity * dt;
+            particle.pos += particle.velocity * dt;
+
+            if let Some(floor) = particle.floor {
+                if particle.pos.y >= floor {
+                    particle.pos.y = floor;
+                    particle.velocity = Vector::new(0.0, 0.0);
+                }
+            }
+        }
+
+        self.particles.retain(|particle| particle.ttl > 0.0);
+    }
+}
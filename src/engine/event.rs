@@ -0,0 +1,47 @@
+use std::mem;
+
+use specs::Entity;
+use nphysics::math::Point;
+
+use super::{N, PickupKind};
+
+/// Something that happened during a step which code outside the engine (e.g. stats) may care about.
+#[derive(Debug, Clone)]
+pub enum GameEvent {
+    EntityDied { entity: Entity },
+    /// Something with hitpoints was hurt, e.g. for playing a sound or spawning particles where it happened.
+    Damaged {
+        entity: Entity,
+        damage: u16,
+        position: Point<N>,
+    },
+    PlayerDied,
+    CoinCollected { value: u32 },
+    PickupCollected { kind: PickupKind },
+    /// A boss lost enough hitpoints to move on to its next phase (numbered from 1).
+    BossPhaseChanged { entity: Entity, phase: u8 },
+    PlaySound { path: String },
+    KnifeThrown,
+    Jumped,
+    TimeStopStarted,
+    TimeStopEnded,
+    /// Something heavy went off, such as an explosion. `strength` is from 0 to 1.
+    Impact { position: Point<N>, strength: N },
+    LevelComplete,
+}
+
+/// Collects `GameEvent`s pushed by systems until `World::drain_events` is called.
+#[derive(Default)]
+pub struct GameEvents {
+    events: Vec<GameEvent>,
+}
+
+impl GameEvents {
+    pub fn push(&mut self, event: GameEvent) {
+        self.events.push(event);
+    }
+
+    pub fn drain(&mut self) -> Vec<GameEvent> {
+        mem::replace(&mut self.events, Vec::new())
+    }
+}
@@ -0,0 +1,27 @@
+use std::mem;
+
+/// Names an entity in the level so that others (switches, keys, ...) can act on it.
+pub type LinkId = String;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Signal {
+    Open,
+    Close,
+    Toggle,
+}
+
+/// Signals sent to linked entities during a step, handled by the systems owning those entities.
+#[derive(Default)]
+pub struct LinkSignals {
+    signals: Vec<(LinkId, Signal)>,
+}
+
+impl LinkSignals {
+    pub fn send(&mut self, target: &str, signal: Signal) {
+        self.signals.push((target.to_owned(), signal));
+    }
+
+    pub fn drain(&mut self) -> Vec<(LinkId, Signal)> {
+        mem::replace(&mut self.signals, Vec::new())
+    }
+}
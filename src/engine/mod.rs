@@ -13,6 +13,48 @@ pub use self::system::*;
 mod physics;
 pub use self::physics::*;
 
+mod rng;
+pub use self::rng::*;
+
+mod registry;
+pub use self::registry::*;
+
+mod spawn;
+pub use self::spawn::*;
+
+mod event;
+pub use self::event::*;
+
+mod tile;
+pub use self::tile::*;
+
+mod score;
+pub use self::score::*;
+
+mod link;
+pub use self::link::*;
+
+mod action;
+pub use self::action::*;
+
+mod time_stop;
+pub use self::time_stop::*;
+
+mod damage;
+pub use self::damage::*;
+
+mod player_config;
+pub use self::player_config::*;
+
+mod shape;
+pub use self::shape::*;
+
+mod animation;
+pub use self::animation::*;
+
+mod effects;
+pub use self::effects::*;
+
 
 #[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 pub struct Rect {
@@ -20,10 +62,28 @@ pub struct Rect {
     y: N,
     hw: N,
     hh: N,
+    /// Clockwise, in radians, around the centre.
+    #[serde(default)]
+    rotation: N,
 }
 
 impl Rect {
     pub fn new(x: N, y: N, hw: N, hh: N) -> Self {
-        Rect { x, y, hw, hh }
+        Rect {
+            x,
+            y,
+            hw,
+            hh,
+            rotation: 0.0,
+        }
+    }
+
+    pub fn rotated(self, rotation: N) -> Self {
+        Rect { rotation, ..self }
+    }
+
+    /// The edges as (left, top, right, bottom), ignoring rotation.
+    pub fn edges(&self) -> (N, N, N, N) {
+        (self.x - self.hw, self.y - self.hh, self.x + self.hw, self.y + self.hh)
     }
 }
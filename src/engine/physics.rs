@@ -1,16 +1,23 @@
 use super::*;
 
-use std::collections::HashMap;
+use std::collections::{hash_map, HashMap};
+use std::cell::RefCell;
+use std::error::Error as StdError;
+use std::fmt::{self, Display, Formatter};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 
 use na::geometry::Translation;
-use ncollide::shape::ShapeHandle;
-use ncollide::bounding_volume::{HasBoundingVolume, AABB};
+use ncollide::shape::{Compound, Polyline, ShapeHandle};
+use ncollide::bounding_volume::{BoundingVolume, HasBoundingVolume, AABB};
 use ncollide::world::CollisionGroups;
+use ncollide::query::Ray;
 use nphysics;
 use nphysics::math::{AngularInertia, Isometry, Orientation, Point, Vector};
-use nphysics::object::{RigidBodyCollisionGroups, Sensor, SensorCollisionGroups, STATIC_GROUP_ID};
-use nphysics::detection::joint::{Anchor, Fixed};
+use nphysics::object::{RigidBodyCollisionGroups, Sensor, SensorCollisionGroups, WorldObject, STATIC_GROUP_ID};
+use nphysics::detection::joint::{Anchor, BallInSocket, Fixed};
 use specs::Entity;
+use num::Zero;
 
 use chan;
 
@@ -21,73 +28,164 @@ pub const PLAYER_GROUP_ID: usize = 1;
 pub const GENERIC_DYNAMIC_GROUP_ID: usize = 2;
 pub const DEAD_ENEMY_GROUP_ID: usize = 3;
 pub const PARTICLE_GROUP_ID: usize = 4;
+pub const PROJECTILE_GROUP_ID: usize = 5;
+pub const CARRIED_GROUP_ID: usize = 6;
 
-#[derive(Debug, Clone, Copy)]
+/// The collision groups set up for each kind of body in the game.
+/// These are presets for `CollisionGroupsSpec`, which can describe any other combination.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum CollisionGroupsKind {
     Particle,
     GenericDynamic,
     GenericStatic,
     EmbeddedKnife,
     Knife,
+    Projectile,
     Player,
+    /// Held by the player, so it mustn't push them around.
+    Carried,
     DeadEnemy,
+    OpenDoor,
 }
 
 impl CollisionGroupsKind {
     pub fn to_collision_groups(self) -> RigidBodyCollisionGroups {
-        use self::CollisionGroupsKind::*;
+        CollisionGroupsSpec::from(self).to_collision_groups()
+    }
 
-        let mut g = RigidBodyCollisionGroups::new_dynamic();
-        g.set_membership(&[]);
+    /// Collision groups for querying the world as if we were a body of this kind.
+    pub fn to_query_groups(self) -> CollisionGroups {
+        *self.to_collision_groups().as_collision_groups()
+    }
+}
 
-        match self {
-            Particle => {
-                g.modify_membership(PARTICLE_GROUP_ID, true);
-                g.enable_interaction_with_static();
-                g
-            }
-            GenericDynamic => {
-                g.modify_membership(GENERIC_DYNAMIC_GROUP_ID, true);
-                g.enable_interaction_with_sensors();
-                g.modify_blacklist(PARTICLE_GROUP_ID, true);
-                g.modify_whitelist(DEAD_ENEMY_GROUP_ID, false);
-                g.enable_interaction_with_static();
-                g
-            }
-            GenericStatic => {
-                let mut g = RigidBodyCollisionGroups::new_static();
-                g.set_membership(&[]);
-                g.modify_whitelist(GENERIC_DYNAMIC_GROUP_ID, true);
-                g.modify_whitelist(PARTICLE_GROUP_ID, true);
-                // g.modify_whitelist(DEAD_ENEMY_GROUP_ID, true);
-                g.enable_interaction_with_sensors();
-                g
-            }
-            EmbeddedKnife => {
-                g.enable_interaction_with_static();
-                g
+/// Which collision groups a body belongs to and which it may touch.
+/// Group ids go from 0 up to, but not including, nphysics' `STATIC_GROUP_ID`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CollisionGroupsSpec {
+    pub is_static: bool,
+    pub membership: Vec<usize>,
+    /// Groups this is allowed to touch. `None` allows all of them.
+    pub whitelist: Option<Vec<usize>>,
+    /// Groups this never touches, whatever either whitelist says.
+    pub blacklist: Vec<usize>,
+    /// Whether this touches static bodies. `None` leaves the nphysics default.
+    pub interacts_with_static: Option<bool>,
+    /// Whether sensors notice this. `None` leaves the nphysics default.
+    pub interacts_with_sensors: Option<bool>,
+}
+
+impl Default for CollisionGroupsSpec {
+    fn default() -> Self {
+        CollisionGroupsSpec {
+            is_static: false,
+            membership: Vec::new(),
+            whitelist: None,
+            blacklist: Vec::new(),
+            interacts_with_static: None,
+            interacts_with_sensors: None,
+        }
+    }
+}
+
+impl CollisionGroupsSpec {
+    pub fn to_collision_groups(&self) -> RigidBodyCollisionGroups {
+        let mut g = if self.is_static {
+            RigidBodyCollisionGroups::new_static()
+        } else {
+            RigidBodyCollisionGroups::new_dynamic()
+        };
+
+        g.set_membership(&self.membership);
+        if let Some(ref whitelist) = self.whitelist {
+            g.set_whitelist(whitelist);
+        }
+        g.set_blacklist(&self.blacklist);
+
+        // static bodies never touch each other
+        if !self.is_static {
+            match self.interacts_with_static {
+                Some(true) => g.enable_interaction_with_static(),
+                Some(false) => g.disable_interaction_with_static(),
+                None => {}
             }
+        }
+        match self.interacts_with_sensors {
+            Some(true) => g.enable_interaction_with_sensors(),
+            Some(false) => g.disable_interaction_with_sensors(),
+            None => {}
+        }
+
+        g
+    }
+}
+
+impl From<CollisionGroupsKind> for CollisionGroupsSpec {
+    fn from(kind: CollisionGroupsKind) -> Self {
+        use self::CollisionGroupsKind::*;
+
+        match kind {
+            Particle => CollisionGroupsSpec {
+                membership: vec![PARTICLE_GROUP_ID],
+                interacts_with_static: Some(true),
+                ..CollisionGroupsSpec::default()
+            },
+            GenericDynamic => CollisionGroupsSpec {
+                membership: vec![GENERIC_DYNAMIC_GROUP_ID],
+                whitelist: Some((0..STATIC_GROUP_ID).filter(|&id| id != DEAD_ENEMY_GROUP_ID).collect()),
+                blacklist: vec![PARTICLE_GROUP_ID],
+                interacts_with_static: Some(true),
+                interacts_with_sensors: Some(true),
+                ..CollisionGroupsSpec::default()
+            },
+            GenericStatic => CollisionGroupsSpec {
+                is_static: true,
+                interacts_with_sensors: Some(true),
+                ..CollisionGroupsSpec::default()
+            },
+            EmbeddedKnife => CollisionGroupsSpec {
+                interacts_with_static: Some(true),
+                ..CollisionGroupsSpec::default()
+            },
             Knife => {
-                let mut g = GenericDynamic.to_collision_groups();
-                g.modify_blacklist(PLAYER_GROUP_ID, true);
-                // g.modify_whitelist(DEAD_ENEMY_GROUP_ID, true);
-                g
+                let mut spec = CollisionGroupsSpec::from(GenericDynamic);
+                spec.membership.push(PROJECTILE_GROUP_ID);
+                spec.blacklist.push(PLAYER_GROUP_ID);
+                spec
+            }
+            Projectile => {
+                // small and fast, so ground sensors are told to ignore these
+                let mut spec = CollisionGroupsSpec::from(GenericDynamic);
+                spec.membership.push(PROJECTILE_GROUP_ID);
+                spec
             }
             Player => {
-                let mut g = GenericDynamic.to_collision_groups();
-                g.modify_membership(PLAYER_GROUP_ID, true);
-                g
+                let mut spec = CollisionGroupsSpec::from(GenericDynamic);
+                spec.membership.push(PLAYER_GROUP_ID);
+                spec
             }
-            DeadEnemy => {
-                // g.set_whitelist(&[]);
-                // g.enable_interaction_with_static();
-                // g.disable_interaction_with_sensors();
-                // g.modify_membership(DEAD_ENEMY_GROUP_ID, true);
-                // g.modify_blacklist(PARTICLE_GROUP_ID, true);
-                // g
-
-                GenericDynamic.to_collision_groups()
+            Carried => {
+                let mut spec = CollisionGroupsSpec::from(GenericDynamic);
+                spec.membership.push(CARRIED_GROUP_ID);
+                spec.blacklist.push(PLAYER_GROUP_ID);
+                spec
             }
+            // corpses only rest on the level, so they don't get in the player's way
+            DeadEnemy => CollisionGroupsSpec {
+                membership: vec![DEAD_ENEMY_GROUP_ID],
+                blacklist: vec![PARTICLE_GROUP_ID, PLAYER_GROUP_ID, GENERIC_DYNAMIC_GROUP_ID],
+                interacts_with_static: Some(true),
+                interacts_with_sensors: Some(false),
+                ..CollisionGroupsSpec::default()
+            },
+            // stays in the world so it can be closed again, but nothing touches it
+            OpenDoor => CollisionGroupsSpec {
+                is_static: true,
+                blacklist: vec![PLAYER_GROUP_ID, GENERIC_DYNAMIC_GROUP_ID, DEAD_ENEMY_GROUP_ID, PARTICLE_GROUP_ID],
+                interacts_with_sensors: Some(false),
+                ..CollisionGroupsSpec::default()
+            },
         }
     }
 }
@@ -104,22 +202,22 @@ impl PhysicsThreadLink {
         self.recv.recv().unwrap().unwrap_finish_step();
     }
 
-    pub fn get_position(&self, id: RigidBodyID) -> Isometry<N> {
+    pub fn get_position(&self, id: RigidBodyID) -> Result<Isometry<N>, PhysicsError> {
         self.send.send(GetPosition(id));
         self.recv.recv().unwrap().unwrap_position()
     }
 
-    pub fn get_half_extents(&self, id: RigidBodyID) -> (N, N) {
+    pub fn get_half_extents(&self, id: RigidBodyID) -> Result<(N, N), PhysicsError> {
         self.send.send(GetHalfExtents(id));
         self.recv.recv().unwrap().unwrap_half_extents()
     }
 
-    pub fn get_rotation(&self, id: RigidBodyID) -> N {
+    pub fn get_rotation(&self, id: RigidBodyID) -> Result<N, PhysicsError> {
         self.send.send(GetRotation(id));
         self.recv.recv().unwrap().unwrap_rotation()
     }
 
-    pub fn get_lin_vel(&self, id: RigidBodyID) -> Vector<N> {
+    pub fn get_lin_vel(&self, id: RigidBodyID) -> Result<Vector<N>, PhysicsError> {
         self.send.send(GetLinVel(id));
         self.recv.recv().unwrap().unwrap_lin_vel()
     }
@@ -128,7 +226,13 @@ impl PhysicsThreadLink {
         self.send.send(SetLinVel(id, x));
     }
 
-    pub fn get_ang_vel(&self, id: RigidBodyID) -> Orientation<N> {
+    /// Clamps each body's current velocities to its limit. Done on the physics thread so it sees
+    /// the impulses sent earlier this step. Unknown bodies are skipped.
+    pub fn clamp_velocities(&self, limits: Vec<(RigidBodyID, VelocityLimit)>) {
+        self.send.send(ClampVelocities(limits));
+    }
+
+    pub fn get_ang_vel(&self, id: RigidBodyID) -> Result<Orientation<N>, PhysicsError> {
         self.send.send(GetAngVel(id));
         self.recv.recv().unwrap().unwrap_ang_vel()
     }
@@ -137,7 +241,7 @@ impl PhysicsThreadLink {
         self.send.send(SetAngVel(id, x));
     }
 
-    pub fn get_inv_mass(&self, id: RigidBodyID) -> N {
+    pub fn get_inv_mass(&self, id: RigidBodyID) -> Result<N, PhysicsError> {
         self.send.send(GetInvMass(id));
         self.recv.recv().unwrap().unwrap_inv_mass()
     }
@@ -146,10 +250,24 @@ impl PhysicsThreadLink {
         self.send.send(SetInvMass(id, x));
     }
 
+    pub fn set_friction(&self, id: RigidBodyID, x: N) {
+        self.send.send(SetFriction(id, x));
+    }
+
+    pub fn set_restitution(&self, id: RigidBodyID, x: N) {
+        self.send.send(SetRestitution(id, x));
+    }
+
     pub fn set_rotation(&self, id: RigidBodyID, x: nphysics::math::Rotation<N>) {
         self.send.send(SetRotation(id, x));
     }
 
+    /// Moves a body straight to a new position, e.g. for respawning or teleporting.
+    /// Its velocity is left alone.
+    pub fn set_translation(&self, id: RigidBodyID, x: Vector<N>) {
+        self.send.send(SetTranslation(id, x));
+    }
+
     pub fn append_lin_force(&self, id: RigidBodyID, x: Vector<N>) {
         self.send.send(AppendLinForce(id, x));
     }
@@ -166,16 +284,30 @@ impl PhysicsThreadLink {
         self.send.send(SetGravity(g));
     }
 
-    pub fn add_sensor(&self, id: SensorID, shape: ShapeHandle<Point<N>, Isometry<N>>, parent: Option<RigidBodyID>, rel_pos: Option<Isometry<N>>) {
+    /// Adds a sensor. If `ignore_projectiles` is set, bodies in the projectile group (bullets,
+    /// grenades, thrown knives) will never be reported as intersecting it.
+    pub fn add_sensor(
+        &self,
+        id: SensorID,
+        shape: ShapeHandle<Point<N>, Isometry<N>>,
+        parent: Option<RigidBodyID>,
+        rel_pos: Option<Isometry<N>>,
+        ignore_projectiles: bool,
+    ) {
         self.send.send(AddSensor {
             id,
             shape,
             parent,
             rel_pos,
+            ignore_projectiles,
         });
     }
 
-    pub fn get_bodies_intersecting_sensor(&self, id: SensorID) -> Vec<UserData> {
+    pub fn remove_sensor(&self, id: SensorID) {
+        self.send.send(RemoveSensor(id));
+    }
+
+    pub fn get_bodies_intersecting_sensor(&self, id: SensorID) -> Result<Vec<UserData>, PhysicsError> {
         self.send.send(GetBodiesIntersectingSensor(id));
         self.recv
             .recv()
@@ -192,27 +324,129 @@ impl PhysicsThreadLink {
         self.send.send(RemoveRigidBody(id));
     }
 
-    pub fn add_fixed_joint(&self, body1: RigidBodyID, body2: RigidBodyID, pos1: Isometry<N>, pos2: Isometry<N>) {
+    pub fn add_fixed_joint(&self, body1: RigidBodyID, body2: RigidBodyID, pos1: Isometry<N>, pos2: Isometry<N>) -> Result<JointID, PhysicsError> {
         self.send.send(AddFixedJoint {
             body1,
             body2,
             pos1,
             pos2,
         });
+        self.recv.recv().unwrap().unwrap_joint_added()
+    }
+
+    pub fn set_kinematic_lin_vel(&self, id: RigidBodyID, x: Vector<N>) {
+        self.send.send(SetKinematicLinVel(id, x));
+    }
+
+    /// Pins the two bodies together at the given points (relative to each body), leaving them free to rotate.
+    /// If body2 is None, pos2 is a fixed point in the world.
+    pub fn add_revolute_joint(&self, body1: RigidBodyID, body2: Option<RigidBodyID>, pos1: Point<N>, pos2: Point<N>) -> Result<JointID, PhysicsError> {
+        self.send.send(AddRevoluteJoint {
+            body1,
+            body2,
+            pos1,
+            pos2,
+        });
+        self.recv.recv().unwrap().unwrap_joint_added()
+    }
+
+    pub fn add_spring_joint(&self, body1: RigidBodyID, end2: SpringEnd, rest_length: N, stiffness: N, damping: N) -> Result<JointID, PhysicsError> {
+        self.send.send(AddSpringJoint {
+            body1,
+            end2,
+            rest_length,
+            stiffness,
+            damping,
+        });
+        self.recv.recv().unwrap().unwrap_joint_added()
+    }
+
+    pub fn remove_joint(&self, id: JointID) {
+        self.send.send(RemoveJoint(id));
     }
 
     pub fn set_collision_groups_kind(&self, id: RigidBodyID, kind: CollisionGroupsKind) {
-        self.send.send(SetCollisionGroupsKind(id, kind));
+        self.set_collision_groups(id, kind.into());
+    }
+
+    pub fn set_collision_groups(&self, id: RigidBodyID, spec: CollisionGroupsSpec) {
+        self.send.send(SetCollisionGroups(id, spec));
+    }
+
+    /// Turns motion clamping on (`Some(threshold)`) or off (`None`) for an existing body,
+    /// with the same meaning as `ccd` in `AddRigidBody`.
+    pub fn enable_ccd(&self, id: RigidBodyID, threshold: Option<N>) {
+        self.send.send(EnableCcd(id, threshold));
     }
 
-    pub fn get_shape_handle(&self, id: RigidBodyID) -> ShapeHandle<Point<N>, Isometry<N>> {
+    pub fn get_shape_handle(&self, id: RigidBodyID) -> Result<ShapeHandle<Point<N>, Isometry<N>>, PhysicsError> {
         self.send.send(GetShapeHandle(id));
         self.recv.recv().unwrap().unwrap_shape_handle()
     }
+
+    /// Returns the closest rigid body hit by the ray (ignoring sensors), the point of impact and the surface normal.
+    pub fn raycast(&self, origin: Point<N>, dir: Vector<N>, max_dist: N, groups: CollisionGroups) -> Option<(UserData, Point<N>, Vector<N>)> {
+        self.send.send(Raycast {
+            origin,
+            dir,
+            max_dist,
+            groups,
+        });
+        self.recv.recv().unwrap().unwrap_raycast_hit()
+    }
+
+    /// Returns every rigid body whose bounding box overlaps the rectangle from min to max.
+    pub fn query_aabb(&self, min: Point<N>, max: Point<N>) -> Vec<UserData> {
+        self.send.send(QueryAABB(min, max));
+        self.recv.recv().unwrap().unwrap_bodies_in_aabb()
+    }
+
+    /// Pushes every dynamic body whose centre is within `radius` of `center` directly away from it,
+    /// changing its speed by `strength` (scaled by the falloff) regardless of its mass.
+    /// Returns the bodies pushed, along with the falloff factor each one got.
+    pub fn apply_radial_impulse(&self, center: Point<N>, radius: N, strength: N, falloff: Falloff) -> Vec<(UserData, N)> {
+        self.send.send(ApplyRadialImpulse {
+            center,
+            radius,
+            strength,
+            falloff,
+        });
+        self.recv.recv().unwrap().unwrap_radial_impulse_hits()
+    }
+
+    /// Returns counters describing how busy the physics thread was over the last step.
+    pub fn get_stats(&self) -> PhysicsStats {
+        self.send.send(GetStats);
+        self.recv.recv().unwrap().unwrap_stats()
+    }
+
+    /// Returns the outline of every body and sensor, for the debug overlay.
+    pub fn get_debug_shapes(&self) -> Vec<DebugShape> {
+        self.send.send(GetDebugShapes);
+        self.recv.recv().unwrap().unwrap_debug_shapes()
+    }
+
+    /// Returns the contacts which started or stopped since this was last called.
+    pub fn get_contact_events(&self) -> Vec<ContactEvent> {
+        self.send.send(GetContactEvents);
+        self.recv.recv().unwrap().unwrap_contact_events()
+    }
+
+    /// Returns the bodies which entered or exited sensors since this was last called.
+    pub fn get_sensor_events(&self) -> Vec<SensorEvent> {
+        self.send.send(GetSensorEvents);
+        self.recv.recv().unwrap().unwrap_sensor_events()
+    }
+
+    pub fn get_snapshot(&self) -> PhysicsSnapshot {
+        self.send.send(GetSnapshot);
+        self.recv.recv().unwrap().unwrap_snapshot()
+    }
 }
 
 pub enum MessageToPhysicsThread {
     Step(N),
+    Shutdown,
     AddRigidBody {
         id: RigidBodyID,
         entity: Entity,
@@ -221,20 +455,27 @@ pub enum MessageToPhysicsThread {
         restitution: N,
         friction: N,
         translation: Vector<N>,
-        collision_groups_kind: CollisionGroupsKind,
+        rotation: N,
+        collision_groups: CollisionGroupsSpec,
         ccd: Option<N>, // Some(threshold) means clamping if more then threshold movement *in a single step*
+        kinematic: bool, // kinematic bodies ignore forces and only move with the velocity set by set_kinematic_lin_vel
     },
     RemoveRigidBody(RigidBodyID),
     GetPosition(RigidBodyID),
     GetHalfExtents(RigidBodyID), // XXX rename GetBoundingHalfExtents
     GetRotation(RigidBodyID),
     SetRotation(RigidBodyID, nphysics::math::Rotation<N>),
+    SetTranslation(RigidBodyID, Vector<N>),
     GetLinVel(RigidBodyID),
     SetLinVel(RigidBodyID, Vector<N>),
+    SetKinematicLinVel(RigidBodyID, Vector<N>),
+    ClampVelocities(Vec<(RigidBodyID, VelocityLimit)>),
     GetAngVel(RigidBodyID),
     SetAngVel(RigidBodyID, Orientation<N>),
     GetInvMass(RigidBodyID),
     SetInvMass(RigidBodyID, N),
+    SetFriction(RigidBodyID, N),
+    SetRestitution(RigidBodyID, N),
     AppendLinForce(RigidBodyID, Vector<N>),
     ClearLinForce(RigidBodyID),
     SetGravity(Vector<N>),
@@ -245,22 +486,58 @@ pub enum MessageToPhysicsThread {
         pos1: Isometry<N>,
         pos2: Isometry<N>,
     },
-    SetCollisionGroupsKind(RigidBodyID, CollisionGroupsKind),
+    AddRevoluteJoint {
+        body1: RigidBodyID,
+        body2: Option<RigidBodyID>,
+        pos1: Point<N>,
+        pos2: Point<N>,
+    },
+    AddSpringJoint {
+        body1: RigidBodyID,
+        end2: SpringEnd,
+        rest_length: N,
+        stiffness: N,
+        damping: N,
+    },
+    RemoveJoint(JointID),
+    SetCollisionGroups(RigidBodyID, CollisionGroupsSpec),
+    EnableCcd(RigidBodyID, Option<N>),
 
     AddSensor {
         id: SensorID,
         shape: ShapeHandle<Point<N>, Isometry<N>>,
         parent: Option<RigidBodyID>,
         rel_pos: Option<Isometry<N>>,
+        ignore_projectiles: bool,
     },
+    RemoveSensor(SensorID),
     GetBodiesIntersectingSensor(SensorID),
 
     GetContacts,
     GetShapeHandle(RigidBodyID),
+    GetSnapshot,
+    Raycast {
+        origin: Point<N>,
+        dir: Vector<N>,
+        max_dist: N,
+        groups: CollisionGroups,
+    },
+    QueryAABB(Point<N>, Point<N>),
+    ApplyRadialImpulse {
+        center: Point<N>,
+        radius: N,
+        strength: N,
+        falloff: Falloff,
+    },
+    GetContactEvents,
+    GetSensorEvents,
+    GetStats,
+    GetDebugShapes,
 }
 
 pub enum MessageFromPhysicsThread {
     FinishStep,
+    Error(PhysicsError),
     Position(Isometry<N>),
     HalfExtents(N, N),
     Rotation(N),
@@ -270,6 +547,15 @@ pub enum MessageFromPhysicsThread {
     BodiesIntersectingSensor(Vec<UserData>),
     Contacts(Vec<Contact>),
     ShapeHandle(ShapeHandle<Point<N>, Isometry<N>>),
+    Snapshot(PhysicsSnapshot),
+    RaycastHit(Option<(UserData, Point<N>, Vector<N>)>),
+    BodiesInAABB(Vec<UserData>),
+    RadialImpulseHits(Vec<(UserData, N)>),
+    Stats(PhysicsStats),
+    DebugShapes(Vec<DebugShape>),
+    ContactEvents(Vec<ContactEvent>),
+    JointAdded(JointID),
+    SensorEvents(Vec<SensorEvent>),
 }
 
 impl MessageFromPhysicsThread {
@@ -280,51 +566,58 @@ impl MessageFromPhysicsThread {
         }
     }
 
-    pub fn unwrap_position(self) -> Isometry<N> {
+    pub fn unwrap_position(self) -> Result<Isometry<N>, PhysicsError> {
         match self {
-            Position(x) => x,
+            Position(x) => Ok(x),
+            Error(e) => Err(e),
             _ => panic!("Expected Position"),
         }
     }
 
-    pub fn unwrap_half_extents(self) -> (N, N) {
+    pub fn unwrap_half_extents(self) -> Result<(N, N), PhysicsError> {
         match self {
-            HalfExtents(x, y) => (x, y),
+            HalfExtents(x, y) => Ok((x, y)),
+            Error(e) => Err(e),
             _ => panic!("Expected HalfExtents"),
         }
     }
 
-    pub fn unwrap_rotation(self) -> N {
+    pub fn unwrap_rotation(self) -> Result<N, PhysicsError> {
         match self {
-            Rotation(x) => x,
+            Rotation(x) => Ok(x),
+            Error(e) => Err(e),
             _ => panic!("Expected Rotation"),
         }
     }
 
-    pub fn unwrap_lin_vel(self) -> Vector<N> {
+    pub fn unwrap_lin_vel(self) -> Result<Vector<N>, PhysicsError> {
         match self {
-            LinVel(x) => x,
+            LinVel(x) => Ok(x),
+            Error(e) => Err(e),
             _ => panic!("Expected LinVel"),
         }
     }
 
-    pub fn unwrap_ang_vel(self) -> Orientation<N> {
+    pub fn unwrap_ang_vel(self) -> Result<Orientation<N>, PhysicsError> {
         match self {
-            AngVel(x) => x,
+            AngVel(x) => Ok(x),
+            Error(e) => Err(e),
             _ => panic!("Expected AngVel"),
         }
     }
 
-    pub fn unwrap_inv_mass(self) -> N {
+    pub fn unwrap_inv_mass(self) -> Result<N, PhysicsError> {
         match self {
-            InvMass(x) => x,
+            InvMass(x) => Ok(x),
+            Error(e) => Err(e),
             _ => panic!("Expected InvMass"),
         }
     }
 
-    pub fn unwrap_bodies_intersecting_sensor(self) -> Vec<UserData> {
+    pub fn unwrap_bodies_intersecting_sensor(self) -> Result<Vec<UserData>, PhysicsError> {
         match self {
-            BodiesIntersectingSensor(x) => x,
+            BodiesIntersectingSensor(x) => Ok(x),
+            Error(e) => Err(e),
             _ => panic!("Expected BodiesIntersectingSensor"),
         }
     }
@@ -336,33 +629,160 @@ impl MessageFromPhysicsThread {
         }
     }
 
-    pub fn unwrap_shape_handle(self) -> ShapeHandle<Point<N>, Isometry<N>> {
+    pub fn unwrap_shape_handle(self) -> Result<ShapeHandle<Point<N>, Isometry<N>>, PhysicsError> {
         match self {
-            ShapeHandle(x) => x,
+            ShapeHandle(x) => Ok(x),
+            Error(e) => Err(e),
             _ => panic!("Expected ShapeHandle"),
         }
     }
+
+    pub fn unwrap_snapshot(self) -> PhysicsSnapshot {
+        match self {
+            Snapshot(x) => x,
+            _ => panic!("Expected Snapshot"),
+        }
+    }
+
+    pub fn unwrap_raycast_hit(self) -> Option<(UserData, Point<N>, Vector<N>)> {
+        match self {
+            RaycastHit(x) => x,
+            _ => panic!("Expected RaycastHit"),
+        }
+    }
+
+    pub fn unwrap_bodies_in_aabb(self) -> Vec<UserData> {
+        match self {
+            BodiesInAABB(x) => x,
+            _ => panic!("Expected BodiesInAABB"),
+        }
+    }
+
+    pub fn unwrap_stats(self) -> PhysicsStats {
+        match self {
+            Stats(x) => x,
+            _ => panic!("Expected Stats"),
+        }
+    }
+
+    pub fn unwrap_debug_shapes(self) -> Vec<DebugShape> {
+        match self {
+            DebugShapes(x) => x,
+            _ => panic!("Expected DebugShapes"),
+        }
+    }
+
+    pub fn unwrap_radial_impulse_hits(self) -> Vec<(UserData, N)> {
+        match self {
+            RadialImpulseHits(x) => x,
+            _ => panic!("Expected RadialImpulseHits"),
+        }
+    }
+
+    pub fn unwrap_contact_events(self) -> Vec<ContactEvent> {
+        match self {
+            ContactEvents(x) => x,
+            _ => panic!("Expected ContactEvents"),
+        }
+    }
+
+    pub fn unwrap_sensor_events(self) -> Vec<SensorEvent> {
+        match self {
+            SensorEvents(x) => x,
+            _ => panic!("Expected SensorEvents"),
+        }
+    }
+
+    pub fn unwrap_joint_added(self) -> Result<JointID, PhysicsError> {
+        match self {
+            JointAdded(x) => Ok(x),
+            Error(e) => Err(e),
+            _ => panic!("Expected JointAdded"),
+        }
+    }
 }
 
 pub fn physics_thread_inner(gravity: Vector<N>, recv: chan::Receiver<MessageToPhysicsThread>, send: chan::Sender<MessageFromPhysicsThread>) {
     let mut physics_world = nphysics::world::World::new();
     physics_world.set_gravity(gravity);
+    let mut gravity = gravity;
 
     let mut rigid_body_id_map = HashMap::new();
     let mut sensor_map = HashMap::new();
-
+    let mut contact_tracker = ContactTracker::default();
+    let mut sensor_tracker = SensorTracker::default();
+    let mut joint_map = HashMap::new();
+    let mut kinematic_vel_map: HashMap<RigidBodyID, Vector<N>> = HashMap::new();
+    let mut next_joint_id = 1;
+    let mut stats = PhysicsStats::default();
+    let mut messages_since_step = 0;
+
+    // For queries: replies with an error if the body doesn't exist.
     macro_rules! body {
-        ($map:expr, $id:expr) => {$map.get(&$id).unwrap().borrow()}
+        ($map:expr, $id:expr) => {
+            match $map.get(&$id) {
+                Some(bh) => bh.borrow(),
+                None => {
+                    send.send(Error(PhysicsError::UnknownRigidBody($id)));
+                    continue;
+                }
+            }
+        }
     }
 
+    // For commands: nobody is waiting on a reply, so just log the error.
     macro_rules! body_mut {
-        ($map:expr, $id:expr) => {$map.get(&$id).unwrap().borrow_mut()}
+        ($map:expr, $id:expr) => {
+            match $map.get(&$id) {
+                Some(bh) => bh.borrow_mut(),
+                None => {
+                    eprintln!("Physics thread: {}", PhysicsError::UnknownRigidBody($id));
+                    continue;
+                }
+            }
+        }
     }
 
     for recv_message in recv.iter() {
+        messages_since_step += 1;
+
         match recv_message {
+            Shutdown => break,
+
             Step(dt) => {
+                stats.messages = messages_since_step;
+                messages_since_step = 0;
+
+                // Kinematic bodies have infinite mass, but nphysics still integrates gravity into their velocity,
+                // so cancel it out in advance.
+                for (id, &vel) in &kinematic_vel_map {
+                    let mut body = body_mut!(rigid_body_id_map, *id);
+                    body.set_lin_vel(vel - gravity * dt);
+                    body.set_ang_vel(Orientation::zero());
+                }
+
+                // nphysics has no spring joints, so we apply the spring forces ourselves
+                for joint in joint_map.values() {
+                    if let JointHandle::Spring(ref spring) = *joint {
+                        spring.apply(&rigid_body_id_map, dt);
+                    }
+                }
+
+                let step_start = Instant::now();
                 physics_world.step(dt);
+                stats.step_time = step_start.elapsed();
+
+                for (id, &vel) in &kinematic_vel_map {
+                    body_mut!(rigid_body_id_map, *id).set_lin_vel(vel);
+                }
+
+                contact_tracker.update(&physics_world);
+                sensor_tracker.update(&sensor_map);
+
+                stats.contacts = physics_world.collision_world().contacts().count();
+                stats.bodies = rigid_body_id_map.len();
+                stats.sensors = sensor_map.len();
+
                 send.send(FinishStep);
             }
 
@@ -374,19 +794,27 @@ pub fn physics_thread_inner(gravity: Vector<N>, recv: chan::Receiver<MessageToPh
                 restitution,
                 friction,
                 translation,
-                collision_groups_kind,
+                rotation,
+                collision_groups,
                 ccd,
+                kinematic,
             } => {
                 let mut body = RigidBody::new(shape, mass_properties, restitution, friction);
                 body.set_margin(BODY_MARGIN);
+                if kinematic {
+                    body.set_inv_mass(0.0);
+                    body.set_deactivation_threshold(None);
+                    kinematic_vel_map.insert(id, Vector::zero());
+                }
                 body.set_translation(Translation::from_vector(translation));
+                body.set_rotation(nphysics::math::Rotation::from_angle(rotation));
                 // body.set_deactivation_threshold(None); // XXX
                 body.set_user_data(Some(Box::new(UserData {
                     rigid_body_id: id,
                     entity,
                 })));
 
-                body.set_collision_groups(collision_groups_kind.to_collision_groups());
+                body.set_collision_groups(collision_groups.to_collision_groups());
 
                 let bh = physics_world.add_rigid_body(body);
                 if let Some(ccd_threshold) = ccd {
@@ -396,12 +824,25 @@ pub fn physics_thread_inner(gravity: Vector<N>, recv: chan::Receiver<MessageToPh
             }
 
             RemoveRigidBody(id) => {
+                kinematic_vel_map.remove(&id);
+
+                // joints don't outlive either of their bodies
+                let attached: Vec<JointID> = joint_map
+                    .iter()
+                    .filter(|&(_, joint)| joint.is_attached_to(id))
+                    .map(|(&joint_id, _)| joint_id)
+                    .collect();
+                for joint_id in attached {
+                    if let Some(joint) = joint_map.remove(&joint_id) {
+                        joint.remove(&mut physics_world);
+                    }
+                }
+
                 let bh = rigid_body_id_map.remove(&id);
                 if let Some(bh) = bh {
                     physics_world.remove_rigid_body(&bh);
                 } else {
-                    // XXX
-                    panic!("oh no");
+                    eprintln!("Physics thread: {}", PhysicsError::UnknownRigidBody(id));
                 }
             }
 
@@ -428,6 +869,11 @@ pub fn physics_thread_inner(gravity: Vector<N>, recv: chan::Receiver<MessageToPh
                 body.set_rotation(x);
             }
 
+            SetTranslation(id, x) => {
+                let mut body = body_mut!(rigid_body_id_map, id);
+                body.set_translation(Translation::from_vector(x));
+            }
+
             GetLinVel(id) => {
                 let body = body!(rigid_body_id_map, id);
                 send.send(LinVel(body.lin_vel()))
@@ -438,6 +884,26 @@ pub fn physics_thread_inner(gravity: Vector<N>, recv: chan::Receiver<MessageToPh
                 body.set_lin_vel(x);
             }
 
+            SetKinematicLinVel(id, x) => {
+                if let Some(vel) = kinematic_vel_map.get_mut(&id) {
+                    *vel = x;
+                }
+                body_mut!(rigid_body_id_map, id).set_lin_vel(x);
+            }
+
+            ClampVelocities(limits) => {
+                // bodies can be removed in the same step that their limit is sent
+                for (id, limit) in limits {
+                    if let Some(bh) = rigid_body_id_map.get(&id) {
+                        let mut body = bh.borrow_mut();
+                        let lin_vel = limit.clamp_lin(body.lin_vel());
+                        let ang_vel = limit.clamp_ang(body.ang_vel());
+                        body.set_lin_vel(lin_vel);
+                        body.set_ang_vel(ang_vel);
+                    }
+                }
+            }
+
             GetAngVel(id) => {
                 let body = body!(rigid_body_id_map, id);
                 send.send(AngVel(body.ang_vel()))
@@ -458,6 +924,16 @@ pub fn physics_thread_inner(gravity: Vector<N>, recv: chan::Receiver<MessageToPh
                 body.set_inv_mass(x);
             }
 
+            SetFriction(id, x) => {
+                let mut body = body_mut!(rigid_body_id_map, id);
+                body.set_friction(x);
+            }
+
+            SetRestitution(id, x) => {
+                let mut body = body_mut!(rigid_body_id_map, id);
+                body.set_restitution(x);
+            }
+
             AppendLinForce(id, x) => {
                 let mut body = body_mut!(rigid_body_id_map, id);
                 body.append_lin_force(x);
@@ -470,6 +946,7 @@ pub fn physics_thread_inner(gravity: Vector<N>, recv: chan::Receiver<MessageToPh
 
             SetGravity(g) => {
                 physics_world.set_gravity(g);
+                gravity = g;
             }
 
             ApplyCentralImpulse(id, x) => {
@@ -477,36 +954,149 @@ pub fn physics_thread_inner(gravity: Vector<N>, recv: chan::Receiver<MessageToPh
                 body.apply_central_impulse(x);
             }
 
-            SetCollisionGroupsKind(id, k) => {
+            SetCollisionGroups(id, spec) => {
                 {
                     let mut body = body_mut!(rigid_body_id_map, id);
-                    body.set_collision_groups(k.to_collision_groups());
+                    body.set_collision_groups(spec.to_collision_groups());
                 }
                 physics_world.update_rigid_body_collision_groups(rigid_body_id_map.get(&id).unwrap().clone());
             }
 
+            EnableCcd(id, threshold) => {
+                let bh = match rigid_body_id_map.get(&id) {
+                    Some(bh) => bh.clone(),
+                    None => {
+                        eprintln!("Physics thread: {}", PhysicsError::UnknownRigidBody(id));
+                        continue;
+                    }
+                };
+
+                // nphysics can't take ccd off a body again, so make the threshold unreachable instead
+                let threshold = threshold.unwrap_or(::std::f32::MAX);
+                physics_world.add_ccd_to(&bh, threshold, true);
+            }
+
             AddFixedJoint {
                 body1,
                 body2,
                 pos1,
                 pos2,
             } => {
-                let anchor1 = Anchor::new(Some(rigid_body_id_map.get(&body1).unwrap().clone()), pos1);
-                let anchor2 = Anchor::new(Some(rigid_body_id_map.get(&body2).unwrap().clone()), pos2);
+                let (bh1, bh2) = match (rigid_body_id_map.get(&body1), rigid_body_id_map.get(&body2)) {
+                    (Some(bh1), Some(bh2)) => (bh1.clone(), bh2.clone()),
+                    (None, _) => {
+                        send.send(Error(PhysicsError::UnknownRigidBody(body1)));
+                        continue;
+                    }
+                    (_, None) => {
+                        send.send(Error(PhysicsError::UnknownRigidBody(body2)));
+                        continue;
+                    }
+                };
+                let anchor1 = Anchor::new(Some(bh1), pos1);
+                let anchor2 = Anchor::new(Some(bh2), pos2);
+
+                let id = JointID(next_joint_id);
+                next_joint_id += 1;
+
+                let joint = physics_world.add_fixed(Fixed::new(anchor1, anchor2));
+                joint_map.insert(id, JointHandle::Fixed(joint, body1, body2));
+                send.send(JointAdded(id));
+            }
 
-                physics_world.add_fixed(Fixed::new(anchor1, anchor2));
+            AddRevoluteJoint {
+                body1,
+                body2,
+                pos1,
+                pos2,
+            } => {
+                let bh1 = match rigid_body_id_map.get(&body1) {
+                    Some(bh1) => bh1.clone(),
+                    None => {
+                        send.send(Error(PhysicsError::UnknownRigidBody(body1)));
+                        continue;
+                    }
+                };
+                let bh2 = match body2 {
+                    Some(body2) => match rigid_body_id_map.get(&body2) {
+                        Some(bh2) => Some(bh2.clone()),
+                        None => {
+                            send.send(Error(PhysicsError::UnknownRigidBody(body2)));
+                            continue;
+                        }
+                    },
+                    None => None,
+                };
+                let anchor1 = Anchor::new(Some(bh1), pos1);
+                let anchor2 = Anchor::new(bh2, pos2);
+
+                let id = JointID(next_joint_id);
+                next_joint_id += 1;
+
+                let joint = physics_world.add_ball_in_socket(BallInSocket::new(anchor1, anchor2));
+                joint_map.insert(id, JointHandle::Revolute(joint, body1, body2));
+                send.send(JointAdded(id));
             }
 
+            AddSpringJoint {
+                body1,
+                end2,
+                rest_length,
+                stiffness,
+                damping,
+            } => {
+                let missing = if !rigid_body_id_map.contains_key(&body1) {
+                    Some(body1)
+                } else {
+                    match end2 {
+                        SpringEnd::Body(body2) if !rigid_body_id_map.contains_key(&body2) => Some(body2),
+                        _ => None,
+                    }
+                };
+                if let Some(missing) = missing {
+                    send.send(Error(PhysicsError::UnknownRigidBody(missing)));
+                    continue;
+                }
+
+                let id = JointID(next_joint_id);
+                next_joint_id += 1;
+
+                joint_map.insert(
+                    id,
+                    JointHandle::Spring(Spring {
+                        body1,
+                        end2,
+                        rest_length,
+                        stiffness,
+                        damping,
+                    }),
+                );
+                send.send(JointAdded(id));
+            }
+
+            RemoveJoint(id) => if let Some(joint) = joint_map.remove(&id) {
+                joint.remove(&mut physics_world);
+            },
+
             AddSensor {
                 id,
                 shape,
                 parent,
                 rel_pos,
+                ignore_projectiles,
             } => {
-                let mut sensor = Sensor::new_with_shared_shape(
-                    shape,
-                    parent.map(|id| rigid_body_id_map.get(&id).unwrap().clone()),
-                );
+                let parent = match parent {
+                    Some(parent) => match rigid_body_id_map.get(&parent) {
+                        Some(bh) => Some(bh.clone()),
+                        None => {
+                            eprintln!("Physics thread: {}", PhysicsError::UnknownRigidBody(parent));
+                            continue;
+                        }
+                    },
+                    None => None,
+                };
+
+                let mut sensor = Sensor::new_with_shared_shape(shape, parent);
                 if let Some(rel_pos) = rel_pos {
                     sensor.set_relative_position(rel_pos);
                 }
@@ -514,6 +1104,9 @@ pub fn physics_thread_inner(gravity: Vector<N>, recv: chan::Receiver<MessageToPh
                 let mut cg = *sensor.collision_groups();
                 cg.enable_interaction_with_static();
                 cg.modify_membership(PARTICLE_GROUP_ID, false);
+                if ignore_projectiles {
+                    cg.modify_blacklist(PROJECTILE_GROUP_ID, true);
+                }
                 *sensor.collision_groups_mut() = cg;
 
                 sensor.enable_interfering_bodies_collection();
@@ -521,8 +1114,20 @@ pub fn physics_thread_inner(gravity: Vector<N>, recv: chan::Receiver<MessageToPh
                 sensor_map.insert(id, physics_world.add_sensor(sensor));
             }
 
+            RemoveSensor(id) => {
+                if let Some(sensor) = sensor_map.remove(&id) {
+                    physics_world.remove_sensor(&sensor);
+                }
+            }
+
             GetBodiesIntersectingSensor(id) => {
-                let sensor = sensor_map.get(&id).unwrap().borrow();
+                let sensor = match sensor_map.get(&id) {
+                    Some(sensor) => sensor.borrow(),
+                    None => {
+                        send.send(Error(PhysicsError::UnknownSensor(id)));
+                        continue;
+                    }
+                };
                 let interfering_bodies = sensor.interfering_bodies();
 
                 send.send(BodiesIntersectingSensor(
@@ -572,6 +1177,384 @@ pub fn physics_thread_inner(gravity: Vector<N>, recv: chan::Receiver<MessageToPh
             }
 
             GetShapeHandle(id) => send.send(ShapeHandle(body!(rigid_body_id_map, id).shape().clone())),
+
+            GetSnapshot => {
+                let mut snapshot = PhysicsSnapshot::default();
+
+                for (&id, bh) in &rigid_body_id_map {
+                    let body = bh.borrow();
+                    snapshot.bodies.insert(
+                        id,
+                        BodySnapshot {
+                            position: *body.position(),
+                            rotation: body.position().rotation.angle(),
+                            lin_vel: body.lin_vel(),
+                            ang_vel: body.ang_vel(),
+                            inv_mass: body.inv_mass(),
+                            is_static: !body.can_move(),
+                        },
+                    );
+                }
+
+                for (&id, sensor) in &sensor_map {
+                    let mut bodies: Vec<UserData> = match sensor.borrow().interfering_bodies() {
+                        Some(bodies) => bodies
+                            .into_iter()
+                            .map(|body| rigid_body_user_data(&*body.borrow()))
+                            .collect(),
+                        None => Vec::new(),
+                    };
+                    bodies.sort_by_key(|user_data| user_data.rigid_body_id);
+                    snapshot.sensors.insert(id, bodies);
+                }
+
+                send.send(Snapshot(snapshot));
+            }
+
+            Raycast {
+                origin,
+                dir,
+                max_dist,
+                groups,
+            } => {
+                // a zero direction would normalise to NaN
+                if dir.norm() == 0.0 {
+                    send.send(RaycastHit(None));
+                    continue;
+                }
+
+                let ray = Ray::new(origin, dir.normalize());
+                let mut closest: Option<(N, UserData, Vector<N>)> = None;
+
+                for (object, intersection) in physics_world
+                    .collision_world()
+                    .interferences_with_ray(&ray, &groups)
+                {
+                    if intersection.toi > max_dist {
+                        continue;
+                    }
+
+                    if let WorldObject::RigidBody(ref body) = object.data {
+                        if closest.map_or(true, |(toi, _, _)| intersection.toi < toi) {
+                            let user_data = *body.borrow()
+                                .user_data()
+                                .unwrap()
+                                .downcast_ref::<UserData>()
+                                .unwrap();
+                            closest = Some((intersection.toi, user_data, intersection.normal));
+                        }
+                    }
+                }
+
+                send.send(RaycastHit(
+                    closest.map(|(toi, user_data, normal)| (user_data, ray.origin + ray.dir * toi, normal)),
+                ));
+            }
+
+            QueryAABB(a, b) => {
+                // callers may pass the corners either way round, but ncollide insists on mins <= maxs
+                let query = AABB::new(
+                    Point::new(a.x.min(b.x), a.y.min(b.y)),
+                    Point::new(a.x.max(b.x), a.y.max(b.y)),
+                );
+
+                let mut bodies: Vec<UserData> = rigid_body_id_map
+                    .values()
+                    .filter_map(|bh| {
+                        let body = bh.borrow();
+                        let bounding_aabb: AABB<Point<N>> = body.bounding_volume(body.position());
+
+                        if bounding_aabb.intersects(&query) {
+                            Some(*body.user_data()
+                                .unwrap()
+                                .downcast_ref::<UserData>()
+                                .unwrap())
+                        } else {
+                            None
+                        }
+                    })
+                    .collect();
+                // in a fixed order, so replays see the same results as the recording
+                bodies.sort_by_key(|user_data| user_data.rigid_body_id);
+
+                send.send(BodiesInAABB(bodies));
+            }
+
+            ApplyRadialImpulse {
+                center,
+                radius,
+                strength,
+                falloff,
+            } => {
+                let mut hits = Vec::new();
+
+                for bh in rigid_body_id_map.values() {
+                    let mut body = bh.borrow_mut();
+                    let inv_mass = body.inv_mass();
+                    if !body.can_move() || inv_mass == 0.0 {
+                        continue;
+                    }
+
+                    let offset = body.position().translation.vector - center.coords;
+                    let distance = offset.norm();
+                    if distance > radius {
+                        continue;
+                    }
+
+                    let factor = falloff.factor(distance / radius);
+                    let dir = if distance > 0.0 {
+                        offset / distance
+                    } else {
+                        Vector::new(0.0, -1.0)
+                    };
+                    body.apply_central_impulse(dir * (strength * factor / inv_mass));
+
+                    let user_data = *body.user_data()
+                        .unwrap()
+                        .downcast_ref::<UserData>()
+                        .unwrap();
+                    hits.push((user_data, factor));
+                }
+                hits.sort_by_key(|&(user_data, _)| user_data.rigid_body_id);
+
+                send.send(RadialImpulseHits(hits));
+            }
+
+            GetContactEvents => send.send(ContactEvents(contact_tracker.drain())),
+
+            GetSensorEvents => send.send(SensorEvents(sensor_tracker.drain())),
+
+            GetStats => send.send(Stats(stats)),
+
+            GetDebugShapes => {
+                let mut shapes = Vec::new();
+
+                for bh in rigid_body_id_map.values() {
+                    let body = bh.borrow();
+                    let mut outlines = Vec::new();
+                    debug_outlines(body.shape(), body.position(), &mut outlines);
+
+                    shapes.extend(outlines.into_iter().map(|outline| DebugShape {
+                        outline,
+                        kind: if body.can_move() {
+                            DebugShapeKind::Dynamic
+                        } else {
+                            DebugShapeKind::Static
+                        },
+                    }));
+                }
+
+                for sensor in sensor_map.values() {
+                    let sensor = sensor.borrow();
+                    let touching = sensor
+                        .interfering_bodies()
+                        .map_or(false, |bodies| bodies.into_iter().next().is_some());
+                    let mut outlines = Vec::new();
+                    debug_outlines(sensor.shape(), &sensor.position(), &mut outlines);
+
+                    shapes.extend(outlines.into_iter().map(|outline| DebugShape {
+                        outline,
+                        kind: DebugShapeKind::Sensor { touching },
+                    }));
+                }
+
+                send.send(DebugShapes(shapes));
+            }
+        }
+    }
+}
+
+/// Counters from the physics thread, for finding out where the time goes.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PhysicsStats {
+    /// Time spent inside nphysics' step during the last step.
+    pub step_time: Duration,
+    /// Messages received between the previous step and the last one, including the step itself.
+    pub messages: usize,
+    /// Contacts after the last step.
+    pub contacts: usize,
+    pub bodies: usize,
+    pub sensors: usize,
+}
+
+// Number of points used to outline rounded shapes for the debug overlay.
+const DEBUG_OUTLINE_POINTS: usize = 24;
+
+/// A collider, as the debug overlay draws it.
+#[derive(Debug, Clone)]
+pub struct DebugShape {
+    /// Corners in world coordinates, going round the shape. Two points are a single line.
+    pub outline: Vec<Point<N>>,
+    pub kind: DebugShapeKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DebugShapeKind {
+    Static,
+    Dynamic,
+    /// `touching` is set while any body overlaps the sensor.
+    Sensor { touching: bool },
+}
+
+// Outlines a shape, one outline per convex piece.
+// Convex shapes are traced round by their support points; anything else we don't know how to look
+// inside is outlined by its bounding box.
+fn debug_outlines(shape: &ShapeHandle<Point<N>, Isometry<N>>, position: &Isometry<N>, outlines: &mut Vec<Vec<Point<N>>>) {
+    if let Some(compound) = shape.as_shape::<Compound<Point<N>, Isometry<N>>>() {
+        for &(ref part_position, ref part) in compound.shapes() {
+            debug_outlines(part, &(*position * *part_position), outlines);
+        }
+    } else if let Some(polyline) = shape.as_shape::<Polyline<Point<N>>>() {
+        let vertices = polyline.vertices();
+        for segment in polyline.indices().iter() {
+            outlines.push(vec![*position * vertices[segment.x], *position * vertices[segment.y]]);
+        }
+    } else if let Some(support_map) = shape.as_support_map() {
+        let outline = (0..DEBUG_OUTLINE_POINTS)
+            .map(|i| {
+                let angle = i as N / DEBUG_OUTLINE_POINTS as N * 2.0 * ::std::f32::consts::PI;
+                support_map.support_point(position, &Vector::new(angle.cos(), angle.sin()))
+            })
+            .collect();
+        outlines.push(outline);
+    } else {
+        let aabb: AABB<Point<N>> = shape.aabb(position);
+        let (min, max) = (aabb.mins(), aabb.maxs());
+        outlines.push(vec![
+            Point::new(min.x, min.y),
+            Point::new(max.x, min.y),
+            Point::new(max.x, max.y),
+            Point::new(min.x, max.y),
+        ]);
+    }
+}
+
+/// How the strength of a radial impulse drops off with distance from its centre.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Falloff {
+    Constant,
+    Linear,
+    Quadratic,
+}
+
+impl Falloff {
+    /// The multiplier at `fraction` of the way from the centre to the edge.
+    pub fn factor(self, fraction: N) -> N {
+        let remaining = (1.0 - fraction).max(0.0);
+        match self {
+            Falloff::Constant => 1.0,
+            Falloff::Linear => remaining,
+            Falloff::Quadratic => remaining * remaining,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum PhysicsError {
+    UnknownRigidBody(RigidBodyID),
+    UnknownSensor(SensorID),
+}
+
+impl Display for PhysicsError {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            PhysicsError::UnknownRigidBody(id) => write!(f, "unknown rigid body {:?}", id),
+            PhysicsError::UnknownSensor(id) => write!(f, "unknown sensor {:?}", id),
+        }
+    }
+}
+
+impl StdError for PhysicsError {
+    fn description(&self) -> &str {
+        "physics error"
+    }
+}
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub struct JointID(u32);
+
+/// A joint, along with the bodies it joins.
+enum JointHandle {
+    Fixed(Rc<RefCell<Fixed<N>>>, RigidBodyID, RigidBodyID),
+    Revolute(Rc<RefCell<BallInSocket<N>>>, RigidBodyID, Option<RigidBodyID>),
+    Spring(Spring),
+}
+
+impl JointHandle {
+    fn is_attached_to(&self, id: RigidBodyID) -> bool {
+        match *self {
+            JointHandle::Fixed(_, body1, body2) => body1 == id || body2 == id,
+            JointHandle::Revolute(_, body1, body2) => body1 == id || body2 == Some(id),
+            JointHandle::Spring(ref spring) => match spring.end2 {
+                SpringEnd::Body(body2) => spring.body1 == id || body2 == id,
+                SpringEnd::Point(_) => spring.body1 == id,
+            },
+        }
+    }
+
+    fn remove(self, physics_world: &mut nphysics::world::World<N>) {
+        match self {
+            JointHandle::Fixed(joint, _, _) => physics_world.remove_fixed(&joint),
+            JointHandle::Revolute(joint, _, _) => physics_world.remove_ball_in_socket(&joint),
+            // springs are applied by hand, so aren't in the physics world
+            JointHandle::Spring(_) => {}
+        }
+    }
+}
+
+/// What the second end of a spring joint is attached to.
+#[derive(Debug, Clone, Copy)]
+pub enum SpringEnd {
+    Body(RigidBodyID),
+    Point(Point<N>),
+}
+
+/// A damped spring between the centre of body1 and end2.
+struct Spring {
+    body1: RigidBodyID,
+    end2: SpringEnd,
+    rest_length: N,
+    stiffness: N,
+    damping: N,
+}
+
+impl Spring {
+    fn apply(&self, rigid_body_id_map: &HashMap<RigidBodyID, RigidBodyHandle>, dt: N) {
+        let (bh1, bh2) = match (rigid_body_id_map.get(&self.body1), self.end2) {
+            (Some(bh1), SpringEnd::Body(id2)) => match rigid_body_id_map.get(&id2) {
+                Some(bh2) => (bh1, Some(bh2)),
+                None => return,
+            },
+            (Some(bh1), SpringEnd::Point(_)) => (bh1, None),
+            (None, _) => return,
+        };
+
+        let (p1, v1) = {
+            let body1 = bh1.borrow();
+            (body1.position().translation.vector, body1.lin_vel())
+        };
+        let (p2, v2) = match (bh2, self.end2) {
+            (Some(bh2), _) => {
+                let body2 = bh2.borrow();
+                (body2.position().translation.vector, body2.lin_vel())
+            }
+            (None, SpringEnd::Point(point)) => (point.coords, Vector::zero()),
+            (None, SpringEnd::Body(_)) => return,
+        };
+
+        let delta = p2 - p1;
+        let length = delta.norm();
+        if length == 0.0 {
+            return;
+        }
+        let dir = delta / length;
+
+        let force = self.stiffness * (length - self.rest_length) + self.damping * (v2 - v1).dot(&dir);
+        let impulse = dir * force * dt;
+
+        bh1.borrow_mut().apply_central_impulse(impulse);
+        if let Some(bh2) = bh2 {
+            bh2.borrow_mut().apply_central_impulse(-impulse);
         }
     }
 }
@@ -582,6 +1565,184 @@ pub struct UserData {
     pub entity: Entity,
 }
 
+fn rigid_body_user_data(body: &RigidBody) -> UserData {
+    *body.user_data()
+        .unwrap()
+        .downcast_ref::<UserData>()
+        .unwrap()
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum ContactEvent {
+    ContactStarted(UserData, UserData),
+    ContactStopped(UserData, UserData),
+}
+
+/// Remembers which pairs of bodies were touching after the previous step,
+/// so we can tell when contacts begin and end.
+#[derive(Default)]
+struct ContactTracker {
+    active: HashMap<(RigidBodyID, RigidBodyID), (UserData, UserData)>,
+    pending: Vec<ContactEvent>,
+}
+
+impl ContactTracker {
+    fn update(&mut self, physics_world: &nphysics::world::World<N>) {
+        let mut current = HashMap::new();
+
+        for (obj1, obj2, _) in physics_world.collision_world().contacts() {
+            let data1 = rigid_body_user_data(&*obj1.data.borrow_rigid_body());
+            let data2 = rigid_body_user_data(&*obj2.data.borrow_rigid_body());
+
+            let key = if data1.rigid_body_id < data2.rigid_body_id {
+                (data1.rigid_body_id, data2.rigid_body_id)
+            } else {
+                (data2.rigid_body_id, data1.rigid_body_id)
+            };
+            current.insert(key, (data1, data2));
+        }
+
+        // HashMap order changes from run to run, so events are sorted to keep replays deterministic
+        {
+            let active = &self.active;
+            let mut started: Vec<_> = current.iter().filter(|&(key, _)| !active.contains_key(key)).collect();
+            started.sort_by_key(|&(&key, _)| key);
+            for (_, &(data1, data2)) in started {
+                self.pending.push(ContactEvent::ContactStarted(data1, data2));
+            }
+
+            let mut stopped: Vec<_> = active.iter().filter(|&(key, _)| !current.contains_key(key)).collect();
+            stopped.sort_by_key(|&(&key, _)| key);
+            for (_, &(data1, data2)) in stopped {
+                self.pending.push(ContactEvent::ContactStopped(data1, data2));
+            }
+        }
+
+        self.active = current;
+    }
+
+    fn drain(&mut self) -> Vec<ContactEvent> {
+        self.pending.drain(..).collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum SensorEvent {
+    SensorEntered(SensorID, UserData),
+    SensorExited(SensorID, UserData),
+}
+
+/// Remembers which bodies were inside each sensor after the previous step.
+#[derive(Default)]
+struct SensorTracker {
+    members: HashMap<SensorID, HashMap<RigidBodyID, UserData>>,
+    pending: Vec<SensorEvent>,
+}
+
+impl SensorTracker {
+    fn update(&mut self, sensor_map: &HashMap<SensorID, nphysics::object::SensorHandle<N>>) {
+        let mut current_members = HashMap::new();
+        let first_new = self.pending.len();
+
+        for (&id, sensor) in sensor_map {
+            let sensor = sensor.borrow();
+            let current: HashMap<RigidBodyID, UserData> = match sensor.interfering_bodies() {
+                Some(bodies) => bodies
+                    .into_iter()
+                    .map(|body| {
+                        let user_data = rigid_body_user_data(&*body.borrow());
+                        (user_data.rigid_body_id, user_data)
+                    })
+                    .collect(),
+                None => HashMap::new(),
+            };
+
+            {
+                let previous = self.members.entry(id).or_insert_with(HashMap::new);
+
+                for (body_id, &user_data) in &current {
+                    if !previous.contains_key(body_id) {
+                        self.pending.push(SensorEvent::SensorEntered(id, user_data));
+                    }
+                }
+
+                for (body_id, &user_data) in previous.iter() {
+                    if !current.contains_key(body_id) {
+                        self.pending.push(SensorEvent::SensorExited(id, user_data));
+                    }
+                }
+            }
+
+            current_members.insert(id, current);
+        }
+
+        // HashMap order changes from run to run, so events are sorted to keep replays deterministic
+        self.pending[first_new..].sort_by_key(|event| match *event {
+            SensorEvent::SensorEntered(id, user_data) | SensorEvent::SensorExited(id, user_data) => (id, user_data.rigid_body_id),
+        });
+
+        self.members = current_members;
+    }
+
+    fn drain(&mut self) -> Vec<SensorEvent> {
+        self.pending.drain(..).collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BodySnapshot {
+    pub position: Isometry<N>,
+    pub rotation: N,
+    pub lin_vel: Vector<N>,
+    pub ang_vel: Orientation<N>,
+    /// Zero for static and kinematic bodies.
+    pub inv_mass: N,
+    pub is_static: bool,
+}
+
+/// The state of every rigid body and sensor, taken once after each physics step.
+/// Systems should read from this rather than locking the PhysicsThreadLink.
+#[derive(Debug, Clone, Default)]
+pub struct PhysicsSnapshot {
+    bodies: HashMap<RigidBodyID, BodySnapshot>,
+    sensors: HashMap<SensorID, Vec<UserData>>,
+}
+
+impl PhysicsSnapshot {
+    pub fn bodies(&self) -> hash_map::Values<RigidBodyID, BodySnapshot> {
+        self.bodies.values()
+    }
+
+    pub fn get(&self, id: RigidBodyID) -> Option<&BodySnapshot> {
+        self.bodies.get(&id)
+    }
+
+    pub fn position(&self, id: RigidBodyID) -> Option<Isometry<N>> {
+        self.get(id).map(|body| body.position)
+    }
+
+    pub fn rotation(&self, id: RigidBodyID) -> Option<N> {
+        self.get(id).map(|body| body.rotation)
+    }
+
+    pub fn lin_vel(&self, id: RigidBodyID) -> Option<Vector<N>> {
+        self.get(id).map(|body| body.lin_vel)
+    }
+
+    pub fn ang_vel(&self, id: RigidBodyID) -> Option<Orientation<N>> {
+        self.get(id).map(|body| body.ang_vel)
+    }
+
+    pub fn inv_mass(&self, id: RigidBodyID) -> Option<N> {
+        self.get(id).map(|body| body.inv_mass)
+    }
+
+    /// The bodies inside the sensor, sorted by ID. Empty for an unknown sensor.
+    pub fn bodies_in_sensor(&self, id: SensorID) -> &[UserData] {
+        self.sensors.get(&id).map(|bodies| &bodies[..]).unwrap_or(&[])
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Contact {
     pub obj1: UserData,
@@ -0,0 +1,33 @@
+use super::*;
+
+/// How the player moves, set per level.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlayerConfig {
+    /// Top running speed, in m/s.
+    pub max_speed: N,
+    /// How quickly the player speeds up and slows down while on the ground, in m/s^2.
+    pub ground_acceleration: N,
+    /// As `ground_acceleration`, but in the air.
+    pub air_acceleration: N,
+    /// Upwards speed given by a jump, in m/s.
+    pub jump_speed: N,
+    /// Jumps allowed before touching the ground again. 2 gives a double jump.
+    pub max_jumps: u8,
+}
+
+const USAIN_BOLT_MAX_SPEED: N = 12.4;
+
+impl Default for PlayerConfig {
+    fn default() -> Self {
+        let max_speed = USAIN_BOLT_MAX_SPEED * 0.5;
+
+        PlayerConfig {
+            max_speed,
+            ground_acceleration: max_speed * 2.5,
+            air_acceleration: max_speed * 1.5,
+            jump_speed: 6.0,
+            max_jumps: 2,
+        }
+    }
+}
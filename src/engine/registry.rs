@@ -0,0 +1,36 @@
+use std::collections::HashMap;
+
+use specs::Entity;
+
+use super::RigidBodyID;
+
+/// Maps rigid bodies to the entities that own them, and back.
+/// Kept up to date by `World` when bodies are added and by `RemoveSystem` when they are removed.
+#[derive(Default)]
+pub struct BodyRegistry {
+    entities: HashMap<RigidBodyID, Entity>,
+    bodies: HashMap<Entity, RigidBodyID>,
+}
+
+impl BodyRegistry {
+    pub fn insert(&mut self, entity: Entity, id: RigidBodyID) {
+        self.entities.insert(id, entity);
+        self.bodies.insert(entity, id);
+    }
+
+    pub fn remove_entity(&mut self, entity: Entity) -> Option<RigidBodyID> {
+        let id = self.bodies.remove(&entity);
+        if let Some(id) = id {
+            self.entities.remove(&id);
+        }
+        id
+    }
+
+    pub fn entity_for_body(&self, id: RigidBodyID) -> Option<Entity> {
+        self.entities.get(&id).cloned()
+    }
+
+    pub fn body_for_entity(&self, entity: Entity) -> Option<RigidBodyID> {
+        self.bodies.get(&entity).cloned()
+    }
+}
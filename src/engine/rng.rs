@@ -0,0 +1,27 @@
+use rand::{Rng, SeedableRng, XorShiftRng};
+
+/// The source of all gameplay randomness, so that a run can be reproduced from its seed.
+pub struct GameRng {
+    seed: u32,
+    rng: XorShiftRng,
+}
+
+impl GameRng {
+    pub fn new(seed: u32) -> Self {
+        // XorShiftRng panics if the seed is all zeroes, so pad it with some constants
+        GameRng {
+            seed,
+            rng: XorShiftRng::from_seed([seed, 0x193a_6754, 0xa8a7_d469, 0x9783_0e05]),
+        }
+    }
+
+    pub fn seed(&self) -> u32 {
+        self.seed
+    }
+}
+
+impl Rng for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+}
@@ -0,0 +1,13 @@
+/// Points gathered so far in the current level.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Score {
+    pub points: u32,
+    pub coins_collected: u32,
+}
+
+impl Score {
+    pub fn collect_coin(&mut self, value: u32) {
+        self.points += value;
+        self.coins_collected += 1;
+    }
+}
@@ -0,0 +1,82 @@
+use super::*;
+
+use ncollide::shape::{Ball, Compound, ConvexHull, Cuboid, ShapeHandle};
+use ncollide::transformation;
+use nphysics::math::{Isometry, Point, Vector};
+
+/// A collision shape in a body's local coordinates, for bodies that aren't a single rectangle.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ShapeDesc {
+    Cuboid { hw: N, hh: N },
+    Ball { radius: N },
+    /// The convex hull of the points, so they may be given in any order.
+    ConvexPolygon { points: Vec<(N, N)> },
+    /// Several shapes making up one body, e.g. an L-shaped platform.
+    Compound { parts: Vec<ShapePart> },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShapePart {
+    /// Position of the part's centre, relative to the centre of the whole shape.
+    pub offset: (N, N),
+    /// Clockwise, in radians, around the part's centre.
+    #[serde(default)]
+    pub rotation: N,
+    pub shape: ShapeDesc,
+}
+
+impl ShapeDesc {
+    pub fn to_shape_handle(&self) -> ShapeHandle<Point<N>, Isometry<N>> {
+        match *self {
+            ShapeDesc::Cuboid { hw, hh } => ShapeHandle::new(Cuboid::new(Vector::new(hw - BODY_MARGIN, hh - BODY_MARGIN))),
+            ShapeDesc::Ball { radius } => ShapeHandle::new(Ball::new(radius - BODY_MARGIN)),
+            // unlike the others, the outline isn't shrunk by the margin, so it collides very slightly outside it
+            ShapeDesc::ConvexPolygon { ref points } => ShapeHandle::new(ConvexHull::new(hull_points(points))),
+            ShapeDesc::Compound { ref parts } => {
+                let shapes = parts
+                    .iter()
+                    .map(|part| {
+                        let (x, y) = part.offset;
+                        (Isometry::new(Vector::new(x, y), part.rotation), part.shape.to_shape_handle())
+                    })
+                    .collect();
+                ShapeHandle::new(Compound::new(shapes))
+            }
+        }
+    }
+
+    /// Items drawing the shape, relative to the body it belongs to.
+    pub fn render_items(&self, color: [f32; 4]) -> Vec<RenderItem> {
+        self.render_items_at(0.0, 0.0, 0.0, color)
+    }
+
+    fn render_items_at(&self, x: N, y: N, rotation: N, color: [f32; 4]) -> Vec<RenderItem> {
+        match *self {
+            ShapeDesc::Cuboid { hw, hh } => vec![RenderItem::rectangle(x, y, hw * 2.0, hh * 2.0, rotation, color)],
+            ShapeDesc::Ball { radius } => vec![RenderItem::ellipse(x, y, radius * 2.0, radius * 2.0, rotation, color)],
+            ShapeDesc::ConvexPolygon { ref points } => {
+                let points = hull_points(points).iter().map(|p| [p.x, p.y]).collect();
+                vec![RenderItem::polygon(x, y, points, rotation, color)]
+            }
+            ShapeDesc::Compound { ref parts } => parts
+                .iter()
+                .flat_map(|part| {
+                    // parts of nested compounds are carried round with their parent
+                    let (ox, oy) = part.offset;
+                    let (sin, cos) = rotation.sin_cos();
+                    let px = x + ox * cos - oy * sin;
+                    let py = y + ox * sin + oy * cos;
+                    part.shape.render_items_at(px, py, rotation + part.rotation, color)
+                })
+                .collect(),
+        }
+    }
+}
+
+fn hull_points(points: &[(N, N)]) -> Vec<Point<N>> {
+    let points: Vec<Point<N>> = points.iter().map(|&(x, y)| Point::new(x, y)).collect();
+    transformation::convex_hull2_idx(&points)
+        .into_iter()
+        .map(|i| points[i])
+        .collect()
+}
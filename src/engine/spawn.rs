@@ -0,0 +1,62 @@
+use std::mem;
+
+use nphysics::math::Vector;
+use specs::Entity;
+
+use super::*;
+
+/// An entity to be created by `World` once the current step's systems have run.
+#[derive(Debug, Clone)]
+pub enum SpawnCommand {
+    Knife { x: N, y: N, velocity: Vector<N> },
+    Bullet {
+        pos: Vector<N>,
+        radius: N,
+        velocity: Vector<N>,
+        /// Bullets that damage whatever they hit first.
+        damage: Option<u16>,
+        team: Team,
+    },
+    Enemy {
+        rect: Rect,
+        kind: EnemyKind,
+        /// The `Spawner` entity that made it, if any.
+        spawner: Option<Entity>,
+    },
+    Crate { rect: Rect, material: CrateMaterial },
+    Particle {
+        rect: Rect,
+        velocity: Vector<N>,
+        ttl: N,
+        color: [f32; 4],
+    },
+    Coin { pos: Vector<N>, value: u32 },
+    /// A crate flying at `velocity`, which hurts what it hits like one thrown by the player.
+    ThrownCrate {
+        rect: Rect,
+        material: CrateMaterial,
+        velocity: Vector<N>,
+        thrower: Entity,
+    },
+    Pickup { pos: Vector<N>, kind: PickupKind },
+}
+
+/// Systems can't create entities with physics bodies themselves, so they push commands here instead.
+#[derive(Default)]
+pub struct SpawnBuffer {
+    commands: Vec<SpawnCommand>,
+}
+
+impl SpawnBuffer {
+    pub fn push(&mut self, command: SpawnCommand) {
+        self.commands.push(command);
+    }
+
+    pub fn extend<I: IntoIterator<Item = SpawnCommand>>(&mut self, it: I) {
+        self.commands.extend(it);
+    }
+
+    pub fn drain(&mut self) -> Vec<SpawnCommand> {
+        mem::replace(&mut self.commands, Vec::new())
+    }
+}
@@ -2,6 +2,7 @@ use super::*;
 
 use std::sync::{Arc, Mutex};
 use std::collections::HashMap;
+use std::mem;
 use specs::{self, Join};
 use nphysics::math::{Isometry, Orientation, Point, Rotation, Vector};
 use ncollide::query;
@@ -16,44 +17,67 @@ pub struct SystemContext {
     pub time: N,
     pub physics_thread_link: Arc<Mutex<PhysicsThreadLink>>,
     pub time_is_stopped: bool,
+    pub gravity: Vector<N>,
     pub contact_map: HashMap<RigidBodyID, Vec<Contact>>,
-    pub events: Arc<Mutex<Vec<Event>>>,
+    pub contact_events: Vec<ContactEvent>,
+    pub sensor_events: Vec<SensorEvent>,
     pub player: specs::Entity,
 }
 
-impl SystemContext {
-    pub fn push_event(&self, event: Event) {
-        self.events.lock().unwrap().push(event);
-    }
-
-    pub fn push_events<I: IntoIterator<Item = Event>>(&self, it: I) {
-        let mut events = self.events.lock().unwrap();
-        for event in it.into_iter() {
-            events.push(event);
-        }
-    }
-}
-
 pub fn register_systems<'a, 'b>(d: specs::DispatcherBuilder<'a, 'b>) -> specs::DispatcherBuilder<'a, 'b> {
     let d = d.add(
         UpdateRenderableFromRigidBodyIDSystem,
         "UpdateRenderableFromRigidBodyIDSystem",
         &[],
     );
+    let d = d.add(GroundSensorSystem, "GroundSensorSystem", &[]);
     let d = d.add(PlayerSystem, "PlayerSystem", &[]);
+    let d = d.add(StatusEffectSystem, "StatusEffectSystem", &[]);
+    let d = d.add(AnimationSystem, "AnimationSystem", &["GroundSensorSystem"]);
     let d = d.add(TimeStopSystem, "TimeStopSystem", &[]);
     let d = d.add(KnifeSystem, "KnifeSystem", &[]);
+    let d = d.add(CarrySystem, "CarrySystem", &[]);
+    let d = d.add(ThrownSystem, "ThrownSystem", &[]);
+    let d = d.add(MovingPlatformSystem, "MovingPlatformSystem", &[]);
+    let d = d.add(GravityZoneSystem, "GravityZoneSystem", &[]);
+    let d = d.add(GravityScaleSystem, "GravityScaleSystem", &[]);
+    let d = d.add(ForceFieldSystem, "ForceFieldSystem", &[]);
+    let d = d.add(GoalSystem, "GoalSystem", &[]);
+    let d = d.add(CoinSystem, "CoinSystem", &[]);
+    let d = d.add(PickupSystem, "PickupSystem", &[]);
+    let d = d.add(SwitchSystem, "SwitchSystem", &[]);
+    let d = d.add(KeySystem, "KeySystem", &[]);
+    let d = d.add(TriggerSystem, "TriggerSystem", &[]);
+    let d = d.add(SpawnerSystem, "SpawnerSystem", &[]);
+    let d = d.add(DoorSystem, "DoorSystem", &["SwitchSystem", "KeySystem"]);
+    let d = d.add(ContactDamageSystem, "ContactDamageSystem", &[]);
+    let d = d.add(HazardSystem, "HazardSystem", &["ContactDamageSystem"]);
+    let d = d.add(EnemyMovementSystem, "EnemyMovementSystem", &[]);
+    let d = d.add(FlyerSystem, "FlyerSystem", &["EnemyMovementSystem"]);
+    let d = d.add(BossSystem, "BossSystem", &[]);
+    let d = d.add(RangedAttackerSystem, "RangedAttackerSystem", &["EnemyMovementSystem"]);
 
     let d = d.add_barrier();
-    let d = d.add(BasicEnemySystem, "BasicEnemySystem", &[]);
+    let d = d.add(DamageSystem, "DamageSystem", &[]);
+    let d = d.add(DeathSystem, "DeathSystem", &["DamageSystem"]);
+    let d = d.add(CorpseSystem, "CorpseSystem", &["DeathSystem"]);
+    let d = d.add(CrateSystem, "CrateSystem", &["DamageSystem"]);
 
     let d = d.add_barrier();
     let d = d.add(RemoveOOBEntitiesSystem, "RemoveOOBEntitiesSystem", &[]);
     let d = d.add(TimedRemoveSystem, "TimedRemoveSystem", &[]);
+    let d = d.add(TextFadeSystem, "TextFadeSystem", &[]);
+    let d = d.add(GrenadeSystem, "GrenadeSystem", &["TimedRemoveSystem"]);
+    let d = d.add(VelocityLimitSystem, "VelocityLimitSystem", &["GrenadeSystem"]);
     let d = d.add(
         RemoveSystem,
         "RemoveSystem",
-        &["TimedRemoveSystem", "RemoveOOBEntitiesSystem"],
+        &[
+            "TimedRemoveSystem",
+            "TextFadeSystem",
+            "RemoveOOBEntitiesSystem",
+            "GrenadeSystem",
+        ],
     );
 
     d
@@ -61,10 +85,10 @@ pub fn register_systems<'a, 'b>(d: specs::DispatcherBuilder<'a, 'b>) -> specs::D
 
 #[derive(SystemData)]
 struct UpdateRenderableFromRigidBodyIDData<'a> {
-    rigidbodyidc: WS<'a, RigidBodyID>, // write because we lock the physics thread link
+    rigidbodyidc: RS<'a, RigidBodyID>,
     renderablec: WS<'a, Renderable>,
 
-    c: specs::Fetch<'a, SystemContext>,
+    snapshot: specs::Fetch<'a, PhysicsSnapshot>,
 }
 
 struct UpdateRenderableFromRigidBodyIDSystem;
@@ -73,14 +97,107 @@ impl<'a> specs::System<'a> for UpdateRenderableFromRigidBodyIDSystem {
     type SystemData = UpdateRenderableFromRigidBodyIDData<'a>;
 
     fn run(&mut self, mut data: Self::SystemData) {
-        let physics_thread_link = data.c.physics_thread_link.lock().unwrap();
-
         for (&rigidbodyid, renderable) in (&data.rigidbodyidc, &mut data.renderablec).join() {
-            let pos = physics_thread_link.get_position(rigidbodyid);
+            // bodies created since the last step aren't in the snapshot yet
+            if let Some(body) = data.snapshot.get(rigidbodyid) {
+                renderable.set_transform(
+                    body.position.translation.vector.x,
+                    body.position.translation.vector.y,
+                    body.rotation,
+                );
+            }
+        }
+    }
+}
+
+#[derive(SystemData)]
+struct GroundSensorData<'a> {
+    ground_sensorc: WS<'a, GroundSensor>,
+    platformc: RS<'a, MovingPlatform>,
+
+    snapshot: specs::Fetch<'a, PhysicsSnapshot>,
+}
+
+struct GroundSensorSystem;
+
+impl<'a> specs::System<'a> for GroundSensorSystem {
+    type SystemData = GroundSensorData<'a>;
+
+    fn run(&mut self, mut data: Self::SystemData) {
+        for ground_sensor in (&mut data.ground_sensorc).join() {
+            let bodies = data.snapshot.bodies_in_sensor(ground_sensor.sensor_id());
+
+            ground_sensor.touching_ground = !bodies.is_empty();
+            ground_sensor.ground_velocity = bodies
+                .iter()
+                .filter(|body| data.platformc.get(body.entity).is_some())
+                .filter_map(|body| data.snapshot.lin_vel(body.rigid_body_id))
+                .next()
+                .unwrap_or(Vector::zero());
+        }
+    }
+}
+
+#[derive(SystemData)]
+struct AnimationData<'a> {
+    animationc: WS<'a, Animation>,
+    renderablec: WS<'a, Renderable>,
+    rigidbodyidc: RS<'a, RigidBodyID>,
+    playerc: RS<'a, Player>,
+    ground_sensorc: RS<'a, GroundSensor>,
+    hitpointsc: RS<'a, Hitpoints>,
+
+    entities: specs::Entities<'a>,
+    c: specs::Fetch<'a, SystemContext>,
+    snapshot: specs::Fetch<'a, PhysicsSnapshot>,
+}
+
+// Slower than this counts as standing still.
+const ANIMATION_RUN_SPEED: N = 0.3;
 
-            renderable.x = pos.translation.vector.x;
-            renderable.y = pos.translation.vector.y;
-            renderable.rotation = physics_thread_link.get_rotation(rigidbodyid);
+/// Picks animation clips from what players and enemies are doing, and shows the current frame.
+struct AnimationSystem;
+
+impl<'a> specs::System<'a> for AnimationSystem {
+    type SystemData = AnimationData<'a>;
+
+    fn run(&mut self, mut data: Self::SystemData) {
+        for (entity, animation, renderable) in (&*data.entities, &mut data.animationc, &mut data.renderablec).join() {
+            let is_dead = match data.playerc.get(entity) {
+                Some(player) => player.is_dead,
+                None => data.hitpointsc
+                    .get(entity)
+                    .map_or(false, |hitpoints| hitpoints.current() == 0),
+            };
+            let in_air = data.ground_sensorc
+                .get(entity)
+                .map_or(false, |ground_sensor| !ground_sensor.touching_ground);
+            let speed = data.rigidbodyidc
+                .get(entity)
+                .and_then(|&body_id| data.snapshot.lin_vel(body_id))
+                .map_or(0.0, |vel| vel.x.abs());
+
+            animation.set_state(if is_dead {
+                AnimationState::Dead
+            } else if in_air {
+                AnimationState::Jump
+            } else if speed > ANIMATION_RUN_SPEED {
+                AnimationState::Run
+            } else {
+                AnimationState::Idle
+            });
+
+            // the player keeps moving while time is stopped, and so keeps animating
+            if !data.c.time_is_stopped || entity == data.c.player {
+                animation.advance(data.c.time);
+            }
+
+            let frame = animation.source_rect();
+            for item in &mut renderable.items {
+                if let RenderItemKind::Sprite { ref mut source, .. } = item.kind {
+                    *source = Some(frame);
+                }
+            }
         }
     }
 }
@@ -89,15 +206,16 @@ impl<'a> specs::System<'a> for UpdateRenderableFromRigidBodyIDSystem {
 struct PlayerData<'a> {
     rigidbodyidc: WS<'a, RigidBodyID>,
     playerc: WS<'a, Player>,
+    facingc: WS<'a, Facing>,
+    ground_sensorc: RS<'a, GroundSensor>,
+    status_effectsc: RS<'a, StatusEffects>,
 
+    entities: specs::Entities<'a>,
     c: specs::Fetch<'a, SystemContext>,
+    snapshot: specs::Fetch<'a, PhysicsSnapshot>,
+    config: specs::Fetch<'a, PlayerConfig>,
 }
 
-
-const USAIN_BOLT_MAX_SPEED: N = 12.4;
-const PLAYER_MAX_SPEED: N = USAIN_BOLT_MAX_SPEED * 0.5;
-const PLAYER_ACCELERATION: N = PLAYER_MAX_SPEED * 2.5;
-
 struct PlayerSystem;
 
 impl<'a> specs::System<'a> for PlayerSystem {
@@ -106,35 +224,65 @@ impl<'a> specs::System<'a> for PlayerSystem {
     fn run(&mut self, mut data: Self::SystemData) {
         let physics = data.c.physics_thread_link.lock().unwrap();
 
-        for (&body_id, player) in (&data.rigidbodyidc, &mut data.playerc).join() {
-            player.touching_ground = !physics
-                .get_bodies_intersecting_sensor(player.sensor_id())
-                .is_empty();
-
+        for (entity, &body_id, player, ground_sensor) in (&*data.entities, &data.rigidbodyidc, &mut data.playerc, &data.ground_sensorc).join() {
             physics.clear_lin_force(body_id);
 
-            let mut lvel = physics.get_lin_vel(body_id);
+            // a dead player is left to tip over and fall
+            if player.is_dead {
+                continue;
+            }
+
+            let speed_multiplier = data.status_effectsc
+                .get(entity)
+                .map_or(1.0, |effects| effects.speed_multiplier());
+            let max_speed = data.config.max_speed * speed_multiplier;
+            let acceleration = if ground_sensor.touching_ground {
+                player.jumps_used = 0;
+                data.config.ground_acceleration
+            } else {
+                data.config.air_acceleration
+            };
+            // a stunned player can't do anything but slow down
+            let (moving_left, moving_right) = if speed_multiplier > 0.0 {
+                (player.moving_left, player.moving_right)
+            } else {
+                (false, false)
+            };
+
+            if moving_left != moving_right {
+                let facing = if moving_left { Facing::Left } else { Facing::Right };
+                data.facingc.insert(entity, facing);
+            }
 
+            // Movement is relative to whatever the player is standing on, so moving platforms carry them along.
+            let ground_vel = ground_sensor.ground_velocity;
+            let mut lvel = match data.snapshot.lin_vel(body_id) {
+                Some(lvel) => lvel - ground_vel,
+                None => continue,
+            };
 
-            let mass = 1.0 / physics.get_inv_mass(body_id);
-            let lin_force = mass * PLAYER_ACCELERATION;
+            let mass = match physics.get_inv_mass(body_id) {
+                Ok(inv_mass) => 1.0 / inv_mass,
+                Err(_) => continue,
+            };
+            let lin_force = mass * acceleration * speed_multiplier;
 
             // if self.touching_ground // why??????
             {
-                if player.moving_right == player.moving_left {
+                if moving_right == moving_left {
                     let neg = lvel.x < 0.0;
-                    lvel.x = (lvel.x.abs() - PLAYER_ACCELERATION * data.c.time).max(0.0);
+                    lvel.x = (lvel.x.abs() - acceleration * data.c.time).max(0.0);
                     if neg {
                         lvel.x = -lvel.x;
                     }
                 } else {
-                    if player.moving_left {
-                        if lvel.norm() < PLAYER_MAX_SPEED {
+                    if moving_left {
+                        if lvel.norm() < max_speed {
                             physics.append_lin_force(body_id, Vector::new(-lin_force, 0.0));
                         }
                     // lvel.x = (lvel.x - PLAYER_ACCELERATION).max(-PLAYER_MAX_SPEED);
-                    } else if player.moving_right {
-                        if lvel.norm() < PLAYER_MAX_SPEED {
+                    } else if moving_right {
+                        if lvel.norm() < max_speed {
                             physics.append_lin_force(body_id, Vector::new(lin_force, 0.0));
                         }
                         // lvel.x = (lvel.x + PLAYER_ACCELERATION).min(PLAYER_MAX_SPEED);
@@ -142,7 +290,7 @@ impl<'a> specs::System<'a> for PlayerSystem {
                 }
             }
 
-            physics.set_lin_vel(body_id, lvel);
+            physics.set_lin_vel(body_id, lvel + ground_vel);
 
             physics.set_rotation(body_id, Rotation::new(0.0));
         }
@@ -150,12 +298,49 @@ impl<'a> specs::System<'a> for PlayerSystem {
 }
 
 
+#[derive(SystemData)]
+struct StatusEffectData<'a> {
+    body_idc: RS<'a, RigidBodyID>,
+    status_effectsc: WS<'a, StatusEffects>,
+
+    entities: specs::Entities<'a>,
+    c: specs::Fetch<'a, SystemContext>,
+    snapshot: specs::Fetch<'a, PhysicsSnapshot>,
+    damage: specs::FetchMut<'a, DamageEvents>,
+}
+
+/// Runs down status effects and deals bleeding damage.
+struct StatusEffectSystem;
+
+impl<'a> specs::System<'a> for StatusEffectSystem {
+    type SystemData = StatusEffectData<'a>;
+
+    fn run(&mut self, mut data: Self::SystemData) {
+        if data.c.time_is_stopped {
+            return;
+        }
+
+        for (entity, &body_id, effects) in (&*data.entities, &data.body_idc, &mut data.status_effectsc).join() {
+            let bleed = effects.tick(data.c.time);
+            if bleed == 0 {
+                continue;
+            }
+
+            if let Some(pos) = data.snapshot.position(body_id) {
+                let pos = Point::from_coordinates(pos.translation.vector);
+                data.damage.push(DamageEvent::new(entity, bleed, pos));
+            }
+        }
+    }
+}
+
 #[derive(SystemData)]
 struct TimeStopData<'a> {
     rigidbodyidc: WS<'a, RigidBodyID>,
     time_stop_storec: WS<'a, TimeStopStore>,
 
     c: specs::Fetch<'a, SystemContext>,
+    snapshot: specs::Fetch<'a, PhysicsSnapshot>,
 }
 
 struct TimeStopSystem;
@@ -164,18 +349,23 @@ impl<'a> specs::System<'a> for TimeStopSystem {
     type SystemData = TimeStopData<'a>;
 
     fn run(&mut self, mut data: Self::SystemData) {
-        let physics = data.c.physics_thread_link.lock().unwrap();
-
         if data.c.time_is_stopped {
+            let physics = data.c.physics_thread_link.lock().unwrap();
+
             for (&body_id, store) in (&data.rigidbodyidc, &mut data.time_stop_storec).join() {
                 assert!(store.saved_ang_vel.is_none() == store.saved_lin_vel.is_none());
 
+                let body = match data.snapshot.get(body_id) {
+                    Some(body) => *body,
+                    None => continue,
+                };
+
                 // use zero values if this body was created during time stop
                 let saved_lin_vel = store.saved_lin_vel.unwrap_or(Vector::zero());
                 let saved_ang_vel = store.saved_ang_vel.unwrap_or(Orientation::zero());
 
-                let init_lin_vel = physics.get_lin_vel(body_id);
-                let init_ang_vel = physics.get_ang_vel(body_id);
+                let init_lin_vel = body.lin_vel;
+                let init_ang_vel = body.ang_vel;
 
                 let ratio = (0.001f64.powf(data.c.time as f64)) as N;
                 let new_lin_vel = init_lin_vel * ratio;
@@ -195,14 +385,24 @@ impl<'a> specs::System<'a> for TimeStopSystem {
 struct KnifeData<'a> {
     rigid_body_idc: WS<'a, RigidBodyID>,
     knifec: WS<'a, Knife>,
-    hitpointsc: WS<'a, Hitpoints>,
+    hitpointsc: RS<'a, Hitpoints>,
     removec: WS<'a, Remove>,
-    playerc: WS<'a, Player>,
+    playerc: RS<'a, Player>,
+    inventoryc: WS<'a, Inventory>,
 
     entities: specs::Entities<'a>,
     c: specs::Fetch<'a, SystemContext>,
+    snapshot: specs::Fetch<'a, PhysicsSnapshot>,
+    rng: specs::FetchMut<'a, GameRng>,
+    registry: specs::Fetch<'a, BodyRegistry>,
+    effects: specs::FetchMut<'a, EffectParticles>,
+    damage: specs::FetchMut<'a, DamageEvents>,
+    teamc: RS<'a, Team>,
 }
 
+// Impulse (kg m/s) a knife gives whatever it hits.
+const KNIFE_KNOCKBACK: N = 1200.0;
+
 struct KnifeSystem;
 
 impl<'a> specs::System<'a> for KnifeSystem {
@@ -212,21 +412,82 @@ impl<'a> specs::System<'a> for KnifeSystem {
         let physics = data.c.physics_thread_link.lock().unwrap();
 
         for (entity, &body_id, knife) in (&*data.entities, &data.rigid_body_idc, &mut data.knifec).join() {
-            if knife.stuck_into_entity.is_some() {
-                // pass
+            if let Some(stuck_into_entity) = knife.stuck_into_entity {
+                let target_is_dead = !data.entities.is_alive(stuck_into_entity) ||
+                    data.registry.body_for_entity(stuck_into_entity).is_none() ||
+                    data.hitpointsc
+                        .get(stuck_into_entity)
+                        .map_or(true, |hitpoints| hitpoints.current() == 0);
+
+                if target_is_dead {
+                    detach_knife(&physics, body_id, knife);
+                }
+            } else if knife.bounces_left > 0 {
+                for event in &data.c.contact_events {
+                    let other = match *event {
+                        ContactEvent::ContactStarted(a, b) if a.entity == entity => b,
+                        ContactEvent::ContactStarted(a, b) if b.entity == entity => a,
+                        _ => continue,
+                    };
+
+                    let is_static = data.snapshot
+                        .get(other.rigid_body_id)
+                        .map_or(false, |body| body.is_static);
+
+                    if !is_static {
+                        // ricocheting knives cut through enemies instead of sticking
+                        let is_alive = data.hitpointsc
+                            .get(other.entity)
+                            .map_or(false, |hitpoints| hitpoints.current() > 0);
+                        if !is_alive {
+                            continue;
+                        }
+
+                        if let Some(body) = data.snapshot.get(body_id) {
+                            let pos = Point::from_coordinates(body.position.translation.vector);
+                            data.effects.extend(spawn_blood(pos, &mut data.rng));
+                            data.damage.push(
+                                DamageEvent::new(other.entity, 1, pos)
+                                    .with_impulse(knockback(body.lin_vel, KNIFE_KNOCKBACK))
+                                    .with_effect(knife.effect)
+                                    .with_source_team(data.teamc.get(entity).cloned()),
+                            );
+                        }
+                    }
+
+                    // the physics world does the bouncing itself; we just count the hits
+                    knife.bounces_left -= 1;
+                    if knife.bounces_left == 0 {
+                        physics.set_restitution(body_id, KNIFE_RESTITUTION);
+                        break;
+                    }
+                }
             } else {
                 if let Some(contacts) = data.c.contact_map.get(&body_id) {
                     for contact in contacts {
-                        if let Some(hitpoints) = data.hitpointsc.get_mut(contact.obj2.entity) {
+                        if let Some(hitpoints) = data.hitpointsc.get(contact.obj2.entity) {
+                            // don't get stuck into corpses
+                            if hitpoints.current() == 0 {
+                                continue;
+                            }
+
                             knife.stuck_into_entity = Some(contact.obj2.entity);
-                            data.c.push_events(spawn_blood(contact.position1));
-                            hitpoints.damage(1);
+                            data.effects.extend(spawn_blood(contact.position1, &mut data.rng));
+
+                            let knife_vel = data.snapshot.lin_vel(body_id).unwrap_or(Vector::zero());
+                            data.damage.push(
+                                DamageEvent::new(contact.obj2.entity, 1, contact.position1)
+                                    .with_impulse(knockback(knife_vel, KNIFE_KNOCKBACK))
+                                    .with_effect(knife.effect)
+                                    .with_source_team(data.teamc.get(entity).cloned()),
+                            );
 
                             physics.set_lin_vel(body_id, Vector::new(0.0, 0.0));
                             physics.set_ang_vel(body_id, Orientation::new(0.0));
 
-                            add_fixed_joint_from_contact(&physics, &contact);
+                            knife.joint = add_fixed_joint_from_contact(&physics, &contact).ok();
                             physics.set_collision_groups_kind(body_id, CollisionGroupsKind::EmbeddedKnife);
+                            physics.enable_ccd(body_id, None);
                             break;
                         }
                     }
@@ -235,28 +496,233 @@ impl<'a> specs::System<'a> for KnifeSystem {
 
             if data.playerc.get(data.c.player).unwrap().picking_up {
                 let player_body_id = *data.rigid_body_idc.get(data.c.player).unwrap();
-                let player_pos = physics.get_position(player_body_id);
-                let player_shape = physics.get_shape_handle(player_body_id);
-                let knife_pos = physics.get_position(body_id);
-                let knife_shape = physics.get_shape_handle(body_id);
+                let (player_pos, knife_pos) = match (data.snapshot.position(player_body_id), data.snapshot.position(body_id)) {
+                    (Some(player_pos), Some(knife_pos)) => (player_pos, knife_pos),
+                    _ => continue,
+                };
+                let (player_shape, knife_shape) = match (physics.get_shape_handle(player_body_id), physics.get_shape_handle(body_id)) {
+                    (Ok(player_shape), Ok(knife_shape)) => (player_shape, knife_shape),
+                    _ => continue,
+                };
 
                 if query::contact(&player_pos, &*player_shape, &knife_pos, &*knife_shape, 0.05).is_some() {
                     // Pick up the knife
+                    detach_knife(&physics, body_id, knife);
                     data.removec.insert(entity, Remove);
-                    data.playerc.get_mut(data.c.player).unwrap().inc_knives();
+                    if let Some(inventory) = data.inventoryc.get_mut(data.c.player) {
+                        inventory.refill(Weapon::Knife, 1);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(SystemData)]
+struct MovingPlatformData<'a> {
+    body_idc: RS<'a, RigidBodyID>,
+    platformc: WS<'a, MovingPlatform>,
+
+    c: specs::Fetch<'a, SystemContext>,
+    snapshot: specs::Fetch<'a, PhysicsSnapshot>,
+}
+
+struct MovingPlatformSystem;
+
+impl<'a> specs::System<'a> for MovingPlatformSystem {
+    type SystemData = MovingPlatformData<'a>;
+
+    fn run(&mut self, mut data: Self::SystemData) {
+        let physics = data.c.physics_thread_link.lock().unwrap();
+
+        for (&body_id, platform) in (&data.body_idc, &mut data.platformc).join() {
+            if data.c.time_is_stopped {
+                physics.set_kinematic_lin_vel(body_id, Vector::zero());
+                continue;
+            }
+
+            let pos = match data.snapshot.position(body_id) {
+                Some(pos) => pos.translation.vector,
+                None => continue,
+            };
+
+            let mut target = platform.target();
+            if let Some(t) = target {
+                if (t - pos).norm() <= platform.speed * data.c.time {
+                    platform.advance();
+                    target = platform.target();
+                }
+            }
+
+            let vel = match target {
+                Some(t) if (t - pos).norm() > 0.0 => (t - pos).normalize() * platform.speed,
+                _ => Vector::zero(),
+            };
+            physics.set_kinematic_lin_vel(body_id, vel);
+        }
+    }
+}
+
+#[derive(SystemData)]
+struct GravityZoneData<'a> {
+    gravity_zonec: RS<'a, GravityZone>,
+
+    c: specs::Fetch<'a, SystemContext>,
+    snapshot: specs::Fetch<'a, PhysicsSnapshot>,
+}
+
+struct GravityZoneSystem;
+
+impl<'a> specs::System<'a> for GravityZoneSystem {
+    type SystemData = GravityZoneData<'a>;
+
+    fn run(&mut self, data: Self::SystemData) {
+        if data.c.time_is_stopped {
+            return;
+        }
+
+        let mut impulses = Vec::new();
+
+        for zone in (&data.gravity_zonec).join() {
+            // the physics world keeps applying normal gravity, so only apply the difference
+            let gravity_diff = zone.gravity - data.c.gravity;
+
+            for body in data.snapshot.bodies_in_sensor(zone.sensor_id) {
+                let inv_mass = data.snapshot.inv_mass(body.rigid_body_id).unwrap_or(0.0);
+                if inv_mass == 0.0 {
+                    continue;
                 }
+
+                impulses.push((body.rigid_body_id, gravity_diff * (1.0 / inv_mass) * data.c.time));
+            }
+        }
+
+        if impulses.is_empty() {
+            return;
+        }
+
+        let physics = data.c.physics_thread_link.lock().unwrap();
+        for (id, impulse) in impulses {
+            physics.apply_central_impulse(id, impulse);
+        }
+    }
+}
+
+#[derive(SystemData)]
+struct GravityScaleData<'a> {
+    body_idc: RS<'a, RigidBodyID>,
+    gravity_scalec: RS<'a, GravityScale>,
+    hitpointsc: RS<'a, Hitpoints>,
+
+    entities: specs::Entities<'a>,
+    c: specs::Fetch<'a, SystemContext>,
+    snapshot: specs::Fetch<'a, PhysicsSnapshot>,
+}
+
+struct GravityScaleSystem;
+
+impl<'a> specs::System<'a> for GravityScaleSystem {
+    type SystemData = GravityScaleData<'a>;
+
+    fn run(&mut self, data: Self::SystemData) {
+        if data.c.time_is_stopped {
+            return;
+        }
+
+        let physics = data.c.physics_thread_link.lock().unwrap();
+
+        for (entity, &body_id, &GravityScale(scale)) in (&*data.entities, &data.body_idc, &data.gravity_scalec).join() {
+            // the dead fall like anything else
+            if data.hitpointsc.get(entity).map_or(false, |hitpoints| hitpoints.current() == 0) {
+                continue;
+            }
+
+            let inv_mass = data.snapshot.inv_mass(body_id).unwrap_or(0.0);
+            if inv_mass == 0.0 {
+                continue;
+            }
+
+            // as with gravity zones, the physics world has already applied normal gravity
+            physics.apply_central_impulse(body_id, data.c.gravity * (scale - 1.0) * (1.0 / inv_mass) * data.c.time);
+        }
+    }
+}
+
+#[derive(SystemData)]
+struct ForceFieldData<'a> {
+    force_fieldc: RS<'a, ForceField>,
+
+    c: specs::Fetch<'a, SystemContext>,
+    snapshot: specs::Fetch<'a, PhysicsSnapshot>,
+}
+
+struct ForceFieldSystem;
+
+impl<'a> specs::System<'a> for ForceFieldSystem {
+    type SystemData = ForceFieldData<'a>;
+
+    fn run(&mut self, data: Self::SystemData) {
+        if data.c.time_is_stopped {
+            return;
+        }
+
+        let mut impulses = Vec::new();
+
+        for field in (&data.force_fieldc).join() {
+            for body in data.snapshot.bodies_in_sensor(field.sensor_id) {
+                if data.snapshot.inv_mass(body.rigid_body_id).unwrap_or(0.0) == 0.0 {
+                    continue;
+                }
+
+                impulses.push((body.rigid_body_id, field.force * data.c.time));
             }
         }
+
+        if impulses.is_empty() {
+            return;
+        }
+
+        let physics = data.c.physics_thread_link.lock().unwrap();
+        for (id, impulse) in impulses {
+            physics.apply_central_impulse(id, impulse);
+        }
+    }
+}
+
+#[derive(SystemData)]
+struct VelocityLimitData<'a> {
+    rigid_body_idc: RS<'a, RigidBodyID>,
+    velocity_limitc: RS<'a, VelocityLimit>,
+
+    c: specs::Fetch<'a, SystemContext>,
+}
+
+/// Runs after everything else that applies impulses this step.
+struct VelocityLimitSystem;
+
+impl<'a> specs::System<'a> for VelocityLimitSystem {
+    type SystemData = VelocityLimitData<'a>;
+
+    fn run(&mut self, data: Self::SystemData) {
+        // the snapshot is from before this step's impulses, so the clamping is done on the physics thread
+        let limits = (&data.rigid_body_idc, &data.velocity_limitc)
+            .join()
+            .map(|(&body_id, &limit)| (body_id, limit))
+            .collect();
+
+        data.c.physics_thread_link.lock().unwrap().clamp_velocities(limits);
     }
 }
 
 #[derive(SystemData)]
 struct RemoveData<'a> {
     rigid_body_idc: WS<'a, RigidBodyID>,
+    sensor_idc: RS<'a, SensorID>,
     removec: WS<'a, Remove>,
 
     entities: specs::Entities<'a>,
     c: specs::Fetch<'a, SystemContext>,
+    registry: specs::FetchMut<'a, BodyRegistry>,
 }
 
 struct RemoveSystem;
@@ -264,7 +730,16 @@ struct RemoveSystem;
 impl<'a> specs::System<'a> for RemoveSystem {
     type SystemData = RemoveData<'a>;
 
-    fn run(&mut self, data: Self::SystemData) {
+    fn run(&mut self, mut data: Self::SystemData) {
+        // sensors first, since they may be attached to the bodies
+        for (&sensor_id, _) in (&data.sensor_idc, &data.removec).join() {
+            data.c
+                .physics_thread_link
+                .lock()
+                .unwrap()
+                .remove_sensor(sensor_id);
+        }
+
         for (&body_id, _) in (&data.rigid_body_idc, &data.removec).join() {
             data.c
                 .physics_thread_link
@@ -274,6 +749,7 @@ impl<'a> specs::System<'a> for RemoveSystem {
         }
 
         for (entity, _) in (&*data.entities, &data.removec).join() {
+            data.registry.remove_entity(entity);
             data.entities.delete(entity);
         }
     }
@@ -309,82 +785,1672 @@ impl<'a> specs::System<'a> for TimedRemoveSystem {
 
 
 #[derive(SystemData)]
-struct BasicEnemyData<'a> {
-    basic_enemyc: WS<'a, BasicEnemy>,
+struct GrenadeData<'a> {
+    grenadec: RS<'a, Grenade>,
+    body_idc: RS<'a, RigidBodyID>,
+    removec: RS<'a, Remove>,
     hitpointsc: RS<'a, Hitpoints>,
-    body_idc: WS<'a, RigidBodyID>,
 
+    entities: specs::Entities<'a>,
     c: specs::Fetch<'a, SystemContext>,
+    snapshot: specs::Fetch<'a, PhysicsSnapshot>,
+    rng: specs::FetchMut<'a, GameRng>,
+    effects: specs::FetchMut<'a, EffectParticles>,
+    damage: specs::FetchMut<'a, DamageEvents>,
+    events: specs::FetchMut<'a, GameEvents>,
+    teamc: RS<'a, Team>,
 }
 
-struct BasicEnemySystem;
+// Speed given to bodies at the centre of an explosion, regardless of their mass.
+const EXPLOSION_SPEED: N = 12.0;
+
+/// Sets off grenades whose fuse has just run out.
+struct GrenadeSystem;
 
-impl<'a> specs::System<'a> for BasicEnemySystem {
-    type SystemData = BasicEnemyData<'a>;
+impl<'a> specs::System<'a> for GrenadeSystem {
+    type SystemData = GrenadeData<'a>;
 
     fn run(&mut self, mut data: Self::SystemData) {
-        for (enemy, hitpoints, &body_id) in (&mut data.basic_enemyc, &data.hitpointsc, &data.body_idc).join() {
-            if !enemy.is_dead && hitpoints.current() == 0 {
-                enemy.is_dead = true;
-                let physics = data.c.physics_thread_link.lock().unwrap();
-                physics.set_collision_groups_kind(body_id, CollisionGroupsKind::DeadEnemy);
+        let physics = data.c.physics_thread_link.lock().unwrap();
+
+        for (entity, grenade, &body_id, _) in (&*data.entities, &data.grenadec, &data.body_idc, &data.removec).join() {
+            let centre = match data.snapshot.position(body_id) {
+                Some(pos) => pos.translation.vector,
+                None => continue,
+            };
+
+            let origin = Point::from_coordinates(centre);
+
+            // everything in range is pushed, friend or foe; only the damage cares about teams
+            let hits = physics.apply_radial_impulse(origin, grenade.radius, EXPLOSION_SPEED, Falloff::Linear);
+
+            for (user_data, falloff) in hits {
+                if user_data.entity == entity {
+                    continue;
+                }
+
+                let damage = (grenade.damage as N * falloff).ceil() as u16;
+                let is_alive = data.hitpointsc
+                    .get(user_data.entity)
+                    .map_or(false, |hitpoints| hitpoints.current() > 0);
+
+                if is_alive && damage > 0 {
+                    let position = data.snapshot
+                        .position(user_data.rigid_body_id)
+                        .map_or(origin, |pos| Point::from_coordinates(pos.translation.vector));
+                    data.damage.push(
+                        DamageEvent::new(user_data.entity, damage, position)
+                            .with_effect(grenade.effect)
+                            .with_source_team(data.teamc.get(entity).cloned()),
+                    );
+                }
             }
+
+            data.effects.extend(spawn_explosion(origin, &mut data.rng));
+            data.events.push(GameEvent::Impact {
+                position: origin,
+                strength: 1.0,
+            });
         }
     }
 }
 
 
 #[derive(SystemData)]
-struct RemoveOOBEntitiesData<'a> {
-    body_idc: WS<'a, RigidBodyID>,
-    removec: WS<'a, Remove>,
+struct GoalData<'a> {
+    goalc: WS<'a, Goal>,
+    playerc: RS<'a, Player>,
 
-    entities: specs::Entities<'a>,
     c: specs::Fetch<'a, SystemContext>,
+    events: specs::FetchMut<'a, GameEvents>,
 }
 
-struct RemoveOOBEntitiesSystem;
+struct GoalSystem;
 
-impl<'a> specs::System<'a> for RemoveOOBEntitiesSystem {
-    type SystemData = RemoveOOBEntitiesData<'a>;
+impl<'a> specs::System<'a> for GoalSystem {
+    type SystemData = GoalData<'a>;
 
     fn run(&mut self, mut data: Self::SystemData) {
-        for (entity, &body_id) in (&*data.entities, &data.body_idc).join() {
-            let pos = data.c
-                .physics_thread_link
-                .lock()
-                .unwrap()
-                .get_position(body_id);
-            let x = pos.translation.vector.x;
-            let y = pos.translation.vector.y;
+        if data.playerc.get(data.c.player).map_or(true, |player| player.is_dead) {
+            return;
+        }
 
-            if x.abs() > 500.0 || y.abs() > 500.0 {
-                data.removec.insert(entity, Remove);
-                println!("removed entity for being OOB");
+        for goal in (&mut data.goalc).join() {
+            if goal.reached {
+                continue;
+            }
+
+            for event in &data.c.sensor_events {
+                if let SensorEvent::SensorEntered(sensor_id, user_data) = *event {
+                    if sensor_id == goal.sensor_id && user_data.entity == data.c.player {
+                        goal.reached = true;
+                        data.events.push(GameEvent::LevelComplete);
+                    }
+                }
             }
         }
     }
 }
 
-// Helper functions
+#[derive(SystemData)]
+struct CoinData<'a> {
+    coinc: RS<'a, Coin>,
+    playerc: RS<'a, Player>,
+    removec: WS<'a, Remove>,
 
-fn spawn_blood(origin: Point<N>) -> Vec<Event> {
-    let mut res = Vec::new();
+    entities: specs::Entities<'a>,
+    c: specs::Fetch<'a, SystemContext>,
+    score: specs::FetchMut<'a, Score>,
+    events: specs::FetchMut<'a, GameEvents>,
+}
 
-    use rand;
-    use rand::distributions::{ChiSquared, IndependentSample, Normal, Range};
+struct CoinSystem;
 
-    let mean_size = 0.065;
+impl<'a> specs::System<'a> for CoinSystem {
+    type SystemData = CoinData<'a>;
 
-    let size_dist = Normal::new(mean_size, 0.02);
-    let velocity_dist = Normal::new(0.0, 1.0);
-    let ttl_dist = ChiSquared::new(4.0);
+    fn run(&mut self, mut data: Self::SystemData) {
+        if data.playerc.get(data.c.player).map_or(true, |player| player.is_dead) {
+            return;
+        }
 
-    let rng = &mut rand::thread_rng();
+        for (entity, coin) in (&*data.entities, &data.coinc).join() {
+            if data.removec.get(entity).is_some() {
+                continue;
+            }
 
-    let max_num_dist = Range::new(2, 5);
+            for event in &data.c.sensor_events {
+                if let SensorEvent::SensorEntered(sensor_id, user_data) = *event {
+                    if sensor_id == coin.sensor_id && user_data.entity == data.c.player {
+                        data.score.collect_coin(coin.value);
+                        data.events
+                            .push(GameEvent::CoinCollected { value: coin.value });
+                        data.removec.insert(entity, Remove);
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
 
-    for i in 0..max_num_dist.ind_sample(rng) {
+#[derive(SystemData)]
+struct CarryData<'a> {
+    body_idc: RS<'a, RigidBodyID>,
+    playerc: WS<'a, Player>,
+    carryablec: RS<'a, Carryable>,
+
+    entities: specs::Entities<'a>,
+    c: specs::Fetch<'a, SystemContext>,
+    snapshot: specs::Fetch<'a, PhysicsSnapshot>,
+    registry: specs::Fetch<'a, BodyRegistry>,
+}
+
+// How far from the player's centre a body can be picked up from.
+const CARRY_REACH: N = 1.5;
+// Carried bodies have this mass, whatever they weighed before.
+const CARRIED_INV_MASS: N = 1.0 / 20.0;
+
+struct CarrySystem;
+
+impl<'a> specs::System<'a> for CarrySystem {
+    type SystemData = CarryData<'a>;
+
+    fn run(&mut self, mut data: Self::SystemData) {
+        let physics = data.c.physics_thread_link.lock().unwrap();
+        let player_body_id = match data.body_idc.get(data.c.player) {
+            Some(&body_id) => body_id,
+            None => return,
+        };
+        let player = data.playerc.get_mut(data.c.player).unwrap();
+
+        if let Some(carried) = player.carrying {
+            let still_there = data.entities.is_alive(carried.entity) && data.registry.body_for_entity(carried.entity).is_some();
+            if !still_there {
+                physics.remove_joint(carried.joint);
+                player.carrying = None;
+            } else if player.is_dead {
+                drop_carried(&physics, &carried);
+                player.carrying = None;
+            }
+            return;
+        }
+
+        if !player.picking_up || player.is_dead {
+            return;
+        }
+
+        let player_pos = match data.snapshot.position(player_body_id) {
+            Some(pos) => pos.translation.vector,
+            None => return,
+        };
+
+        let nearest = (&*data.entities, &data.body_idc, &data.carryablec)
+            .join()
+            .filter_map(|(entity, &body_id, _)| {
+                data.snapshot
+                    .position(body_id)
+                    .map(|pos| (entity, body_id, (pos.translation.vector - player_pos).norm()))
+            })
+            .filter(|&(_, _, distance)| distance <= CARRY_REACH)
+            .min_by(|a, b| a.2.partial_cmp(&b.2).unwrap());
+
+        let (entity, body_id) = match nearest {
+            Some((entity, body_id, _)) => (entity, body_id),
+            None => return,
+        };
+
+        let (inv_mass, (_, hh)) = match (physics.get_inv_mass(body_id), physics.get_half_extents(body_id)) {
+            (Ok(inv_mass), Ok(half_extents)) => (inv_mass, half_extents),
+            _ => return,
+        };
+
+        // hold it just above the player's head
+        let hold_pos = Isometry::new(Vector::new(0.0, -(PLAYER_HALF_HEIGHT + hh + 0.05)), 0.0);
+        let joint = match physics.add_fixed_joint(player_body_id, body_id, hold_pos, Isometry::identity()) {
+            Ok(joint) => joint,
+            Err(_) => return,
+        };
+
+        physics.set_inv_mass(body_id, CARRIED_INV_MASS);
+        physics.set_collision_groups_kind(body_id, CollisionGroupsKind::Carried);
+
+        player.carrying = Some(Carried {
+            entity,
+            body_id,
+            joint,
+            inv_mass,
+        });
+    }
+}
+
+/// Lets go of a carried body, making it a normal dynamic body again.
+pub fn drop_carried(physics: &PhysicsThreadLink, carried: &Carried) {
+    physics.remove_joint(carried.joint);
+    physics.set_inv_mass(carried.body_id, carried.inv_mass);
+    physics.set_collision_groups_kind(carried.body_id, CollisionGroupsKind::GenericDynamic);
+}
+
+#[derive(SystemData)]
+struct ThrownData<'a> {
+    body_idc: RS<'a, RigidBodyID>,
+    thrownc: WS<'a, Thrown>,
+    hitpointsc: RS<'a, Hitpoints>,
+
+    entities: specs::Entities<'a>,
+    c: specs::Fetch<'a, SystemContext>,
+    snapshot: specs::Fetch<'a, PhysicsSnapshot>,
+    rng: specs::FetchMut<'a, GameRng>,
+    effects: specs::FetchMut<'a, EffectParticles>,
+    damage: specs::FetchMut<'a, DamageEvents>,
+    teamc: RS<'a, Team>,
+}
+
+// Momentum (kg m/s) needed to deal one point of damage with a thrown body.
+const MOMENTUM_PER_DAMAGE: N = 25000.0;
+
+struct ThrownSystem;
+
+impl<'a> specs::System<'a> for ThrownSystem {
+    type SystemData = ThrownData<'a>;
+
+    fn run(&mut self, mut data: Self::SystemData) {
+        let physics = data.c.physics_thread_link.lock().unwrap();
+        let mut landed = Vec::new();
+
+        for (entity, &body_id, thrown) in (&*data.entities, &data.body_idc, &mut data.thrownc).join() {
+            let contacts = data.c
+                .contact_map
+                .get(&body_id)
+                .map(|contacts| contacts.as_slice())
+                .unwrap_or(&[]);
+            let contact = contacts
+                .iter()
+                .find(|contact| contact.obj2.entity != thrown.thrower);
+
+            let contact = match contact {
+                Some(contact) => contact,
+                None => {
+                    if let Some(vel) = data.snapshot.lin_vel(body_id) {
+                        thrown.last_velocity = vel;
+                    }
+                    continue;
+                }
+            };
+
+            // it only hurts on the first thing it hits
+            landed.push(entity);
+            physics.enable_ccd(body_id, None);
+
+            let target = contact.obj2.entity;
+            let is_alive = data.hitpointsc
+                .get(target)
+                .map_or(false, |hitpoints| hitpoints.current() > 0);
+            if !is_alive {
+                continue;
+            }
+
+            let mass = match physics.get_inv_mass(body_id) {
+                Ok(inv_mass) if inv_mass > 0.0 => 1.0 / inv_mass,
+                _ => continue,
+            };
+            let target_vel = data.snapshot
+                .lin_vel(contact.obj2.rigid_body_id)
+                .unwrap_or(Vector::zero());
+            let momentum = mass * (thrown.last_velocity - target_vel).norm();
+            let damage = (momentum / MOMENTUM_PER_DAMAGE) as u16;
+
+            if damage > 0 {
+                data.effects
+                    .extend(spawn_blood(contact.position1, &mut data.rng));
+                // the target takes some of the thrown body's momentum
+                data.damage.push(
+                    DamageEvent::new(target, damage, contact.position1)
+                        .with_impulse((thrown.last_velocity - target_vel) * (mass * 0.5))
+                        .with_source_team(data.teamc.get(thrown.thrower).cloned()),
+                );
+            }
+        }
+
+        for entity in landed {
+            data.thrownc.remove(entity);
+        }
+    }
+}
+
+#[derive(SystemData)]
+struct PickupData<'a> {
+    pickupc: WS<'a, Pickup>,
+    renderablec: WS<'a, Renderable>,
+    playerc: WS<'a, Player>,
+    hitpointsc: WS<'a, Hitpoints>,
+    inventoryc: WS<'a, Inventory>,
+    removec: WS<'a, Remove>,
+
+    entities: specs::Entities<'a>,
+    c: specs::Fetch<'a, SystemContext>,
+    snapshot: specs::Fetch<'a, PhysicsSnapshot>,
+    events: specs::FetchMut<'a, GameEvents>,
+    time_stop_meter: specs::FetchMut<'a, TimeStopMeter>,
+}
+
+const PICKUP_BOB_HEIGHT: N = 0.08;
+const PICKUP_BOB_SPEED: N = 3.0;
+
+struct PickupSystem;
+
+impl<'a> specs::System<'a> for PickupSystem {
+    type SystemData = PickupData<'a>;
+
+    fn run(&mut self, mut data: Self::SystemData) {
+        let player_is_dead = data.playerc.get(data.c.player).map_or(true, |player| player.is_dead);
+
+        for (entity, pickup, renderable) in (&*data.entities, &mut data.pickupc, &mut data.renderablec).join() {
+            if data.removec.get(entity).is_some() {
+                continue;
+            }
+
+            let mut respawned = false;
+            if !data.c.time_is_stopped {
+                pickup.bob_phase += data.c.time * PICKUP_BOB_SPEED;
+                if let Some(remaining) = pickup.respawn_remaining {
+                    if remaining <= data.c.time {
+                        pickup.respawn_remaining = None;
+                        respawned = true;
+                        mem::swap(&mut renderable.items, &mut pickup.hidden_items);
+                    } else {
+                        pickup.respawn_remaining = Some(remaining - data.c.time);
+                    }
+                }
+            }
+
+            let bob = pickup.bob_phase.sin() * PICKUP_BOB_HEIGHT;
+            renderable.set_transform(pickup.origin.x, pickup.origin.y + bob, 0.0);
+
+            if player_is_dead || !pickup.is_available() {
+                continue;
+            }
+
+            let entered = data.c.sensor_events.iter().any(|event| match *event {
+                SensorEvent::SensorEntered(sensor_id, user_data) => {
+                    sensor_id == pickup.sensor_id && user_data.entity == data.c.player
+                }
+                _ => false,
+            });
+            // a player already standing on the spot when it respawns won't enter it again
+            let touched = entered || (respawned && data.snapshot
+                .bodies_in_sensor(pickup.sensor_id)
+                .iter()
+                .any(|body| body.entity == data.c.player));
+            if !touched {
+                continue;
+            }
+
+            let player = data.c.player;
+            match pickup.kind {
+                PickupKind::Health(amount) => if let Some(hitpoints) = data.hitpointsc.get_mut(player) {
+                    hitpoints.heal(amount);
+                },
+                PickupKind::Knives(count) => if let Some(inventory) = data.inventoryc.get_mut(player) {
+                    inventory.refill(Weapon::Knife, count);
+                },
+                PickupKind::Grenades(count) => if let Some(inventory) = data.inventoryc.get_mut(player) {
+                    inventory.refill(Weapon::Grenade, count);
+                },
+                PickupKind::TimeStopEnergy(amount) => data.time_stop_meter.add(amount),
+                PickupKind::Ricochet(bounces) => if let Some(player) = data.playerc.get_mut(player) {
+                    player.knife_bounces = player.knife_bounces.max(bounces);
+                },
+                PickupKind::Serrated => if let Some(player) = data.playerc.get_mut(player) {
+                    player.serrated_knives = true;
+                },
+            }
+
+            data.events.push(GameEvent::PickupCollected { kind: pickup.kind });
+
+            match pickup.respawn_time {
+                Some(time) => {
+                    pickup.respawn_remaining = Some(time);
+                    mem::swap(&mut renderable.items, &mut pickup.hidden_items);
+                }
+                None => {
+                    data.removec.insert(entity, Remove);
+                }
+            }
+        }
+    }
+}
+
+#[derive(SystemData)]
+struct SwitchData<'a> {
+    switchc: WS<'a, Switch>,
+    knifec: RS<'a, Knife>,
+    renderablec: WS<'a, Renderable>,
+
+    entities: specs::Entities<'a>,
+    c: specs::Fetch<'a, SystemContext>,
+    signals: specs::FetchMut<'a, LinkSignals>,
+}
+
+struct SwitchSystem;
+
+impl<'a> specs::System<'a> for SwitchSystem {
+    type SystemData = SwitchData<'a>;
+
+    fn run(&mut self, mut data: Self::SystemData) {
+        for (entity, switch) in (&*data.entities, &mut data.switchc).join() {
+            if switch.pressed {
+                continue;
+            }
+
+            for event in &data.c.sensor_events {
+                if let SensorEvent::SensorEntered(sensor_id, user_data) = *event {
+                    let by_player_or_knife = user_data.entity == data.c.player || data.knifec.get(user_data.entity).is_some();
+                    if sensor_id == switch.sensor_id && by_player_or_knife {
+                        switch.pressed = true;
+                        break;
+                    }
+                }
+            }
+
+            if switch.pressed {
+                data.signals.send(&switch.target, Signal::Open);
+
+                if let Some(renderable) = data.renderablec.get_mut(entity) {
+                    for item in &mut renderable.items {
+                        item.color = [0.1, 0.8, 0.1, 1.0];
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(SystemData)]
+struct KeyData<'a> {
+    keyc: RS<'a, Key>,
+    removec: WS<'a, Remove>,
+
+    entities: specs::Entities<'a>,
+    c: specs::Fetch<'a, SystemContext>,
+    signals: specs::FetchMut<'a, LinkSignals>,
+}
+
+struct KeySystem;
+
+impl<'a> specs::System<'a> for KeySystem {
+    type SystemData = KeyData<'a>;
+
+    fn run(&mut self, mut data: Self::SystemData) {
+        for (entity, key) in (&*data.entities, &data.keyc).join() {
+            if data.removec.get(entity).is_some() {
+                continue;
+            }
+
+            for event in &data.c.sensor_events {
+                if let SensorEvent::SensorEntered(sensor_id, user_data) = *event {
+                    if sensor_id == key.sensor_id && user_data.entity == data.c.player {
+                        data.signals.send(&key.target, Signal::Open);
+                        data.removec.insert(entity, Remove);
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(SystemData)]
+struct TriggerData<'a> {
+    triggerc: WS<'a, Trigger>,
+    playerc: RS<'a, Player>,
+
+    c: specs::Fetch<'a, SystemContext>,
+    actions: specs::FetchMut<'a, ActionQueue>,
+}
+
+struct TriggerSystem;
+
+impl<'a> specs::System<'a> for TriggerSystem {
+    type SystemData = TriggerData<'a>;
+
+    fn run(&mut self, mut data: Self::SystemData) {
+        if data.playerc.get(data.c.player).map_or(true, |player| player.is_dead) {
+            return;
+        }
+
+        for trigger in (&mut data.triggerc).join() {
+            if trigger.fired && !trigger.repeat {
+                continue;
+            }
+
+            for event in &data.c.sensor_events {
+                if let SensorEvent::SensorEntered(sensor_id, user_data) = *event {
+                    if sensor_id == trigger.sensor_id && user_data.entity == data.c.player {
+                        trigger.fired = true;
+                        data.actions.extend(trigger.actions.iter().cloned());
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(SystemData)]
+struct SpawnerData<'a> {
+    spawnerc: WS<'a, Spawner>,
+    spawned_byc: RS<'a, SpawnedBy>,
+    enemyc: RS<'a, BasicEnemy>,
+
+    entities: specs::Entities<'a>,
+    c: specs::Fetch<'a, SystemContext>,
+    spawns: specs::FetchMut<'a, SpawnBuffer>,
+}
+
+struct SpawnerSystem;
+
+impl<'a> specs::System<'a> for SpawnerSystem {
+    type SystemData = SpawnerData<'a>;
+
+    fn run(&mut self, mut data: Self::SystemData) {
+        if data.c.time_is_stopped {
+            return;
+        }
+
+        for (entity, spawner) in (&*data.entities, &mut data.spawnerc).join() {
+            if !spawner.active {
+                spawner.active = data.c.sensor_events.iter().any(|event| match *event {
+                    SensorEvent::SensorEntered(sensor_id, user_data) => {
+                        Some(sensor_id) == spawner.trigger_sensor && user_data.entity == data.c.player
+                    }
+                    _ => false,
+                });
+                continue;
+            }
+
+            if !spawner.tick(data.c.time) {
+                continue;
+            }
+
+            let num_alive = (&data.spawned_byc, &data.enemyc)
+                .join()
+                .filter(|&(&SpawnedBy(spawned_by), enemy)| spawned_by == entity && !enemy.is_dead)
+                .count();
+            if num_alive >= spawner.max_alive {
+                continue;
+            }
+
+            data.spawns.push(SpawnCommand::Enemy {
+                rect: spawner.rect,
+                kind: spawner.kind,
+                spawner: Some(entity),
+            });
+            spawner.spawned();
+        }
+    }
+}
+
+#[derive(SystemData)]
+struct DoorData<'a> {
+    body_idc: RS<'a, RigidBodyID>,
+    doorc: WS<'a, Door>,
+    renderablec: WS<'a, Renderable>,
+
+    c: specs::Fetch<'a, SystemContext>,
+    signals: specs::FetchMut<'a, LinkSignals>,
+}
+
+struct DoorSystem;
+
+impl<'a> specs::System<'a> for DoorSystem {
+    type SystemData = DoorData<'a>;
+
+    fn run(&mut self, mut data: Self::SystemData) {
+        let signals = data.signals.drain();
+        if signals.is_empty() {
+            return;
+        }
+
+        let physics = data.c.physics_thread_link.lock().unwrap();
+
+        for (&body_id, door, renderable) in (&data.body_idc, &mut data.doorc, &mut data.renderablec).join() {
+            let was_open = door.open;
+
+            for &(ref target, signal) in &signals {
+                if *target != door.id {
+                    continue;
+                }
+
+                door.open = match signal {
+                    Signal::Open => true,
+                    Signal::Close => false,
+                    Signal::Toggle => !door.open,
+                };
+            }
+
+            if door.open == was_open {
+                continue;
+            }
+
+            let (kind, alpha) = if door.open {
+                (CollisionGroupsKind::OpenDoor, 0.2)
+            } else {
+                (CollisionGroupsKind::GenericStatic, 1.0)
+            };
+            physics.set_collision_groups_kind(body_id, kind);
+            for item in &mut renderable.items {
+                item.color[3] = alpha;
+            }
+        }
+    }
+}
+
+#[derive(SystemData)]
+struct ContactDamageData<'a> {
+    body_idc: RS<'a, RigidBodyID>,
+    contact_damagec: WS<'a, ContactDamage>,
+    hitpointsc: RS<'a, Hitpoints>,
+
+    entities: specs::Entities<'a>,
+    c: specs::Fetch<'a, SystemContext>,
+    rng: specs::FetchMut<'a, GameRng>,
+    effects: specs::FetchMut<'a, EffectParticles>,
+    damage: specs::FetchMut<'a, DamageEvents>,
+    teamc: RS<'a, Team>,
+}
+
+// Impulse (kg m/s) given by touching something that does contact damage, e.g. an enemy or a bullet.
+const CONTACT_KNOCKBACK: N = 1500.0;
+
+struct ContactDamageSystem;
+
+impl<'a> specs::System<'a> for ContactDamageSystem {
+    type SystemData = ContactDamageData<'a>;
+
+    fn run(&mut self, mut data: Self::SystemData) {
+        // frozen things can't hurt anyone
+        if data.c.time_is_stopped {
+            return;
+        }
+
+        for contact_damage in (&mut data.contact_damagec).join() {
+            contact_damage.tick(data.c.time);
+        }
+
+        let mut hits = Vec::new();
+
+        for (entity, &body_id, contact_damage) in (&*data.entities, &data.body_idc, &data.contact_damagec).join() {
+            let is_dead = data.hitpointsc
+                .get(entity)
+                .map_or(false, |hitpoints| hitpoints.current() == 0);
+            if !contact_damage.ready() || is_dead {
+                continue;
+            }
+
+            if let Some(contacts) = data.c.contact_map.get(&body_id) {
+                for contact in contacts {
+                    let target = contact.obj2.entity;
+                    if data.contact_damagec.get(target).is_some() {
+                        continue;
+                    }
+
+                    let friendly = match (data.teamc.get(entity), data.teamc.get(target)) {
+                        (Some(&team), Some(&target_team)) => team.is_friendly_to(target_team),
+                        _ => false,
+                    };
+                    if friendly {
+                        continue;
+                    }
+
+                    let target_is_alive = data.hitpointsc
+                        .get(target)
+                        .map_or(false, |hitpoints| hitpoints.current() > 0);
+                    if target_is_alive {
+                        hits.push((entity, target, contact.position1, contact.normal));
+                        break;
+                    }
+                }
+            }
+        }
+
+        for (entity, target, position, normal) in hits {
+            let (damage, single_use) = {
+                let contact_damage = data.contact_damagec.get_mut(entity).unwrap();
+                contact_damage.reset();
+                (contact_damage.damage, contact_damage.single_use)
+            };
+
+            if single_use {
+                data.contact_damagec.remove(entity);
+            }
+
+            data.effects.extend(spawn_blood(position, &mut data.rng));
+            data.damage.push(
+                DamageEvent::new(target, damage, position)
+                    .with_impulse(knockback(normal, CONTACT_KNOCKBACK))
+                    .with_source_team(data.teamc.get(entity).cloned()),
+            );
+        }
+    }
+}
+
+#[derive(SystemData)]
+struct HazardData<'a> {
+    body_idc: RS<'a, RigidBodyID>,
+    hazardc: WS<'a, Hazard>,
+    hitpointsc: RS<'a, Hitpoints>,
+
+    c: specs::Fetch<'a, SystemContext>,
+    rng: specs::FetchMut<'a, GameRng>,
+    effects: specs::FetchMut<'a, EffectParticles>,
+    damage: specs::FetchMut<'a, DamageEvents>,
+}
+
+struct HazardSystem;
+
+impl<'a> specs::System<'a> for HazardSystem {
+    type SystemData = HazardData<'a>;
+
+    fn run(&mut self, mut data: Self::SystemData) {
+        if data.c.time_is_stopped {
+            return;
+        }
+
+        for (&body_id, hazard) in (&data.body_idc, &mut data.hazardc).join() {
+            hazard.tick(data.c.time);
+
+            let contacts = match data.c.contact_map.get(&body_id) {
+                Some(contacts) => contacts,
+                None => continue,
+            };
+
+            for contact in contacts {
+                let target = contact.obj2.entity;
+                let hitpoints = match data.hitpointsc.get(target) {
+                    Some(hitpoints) if hitpoints.current() > 0 => hitpoints,
+                    _ => continue,
+                };
+
+                if !hazard.try_hit(target) {
+                    continue;
+                }
+
+                let damage = hazard.damage.unwrap_or(hitpoints.current());
+
+                if hazard.kind == HazardKind::Spikes {
+                    data.effects
+                        .extend(spawn_blood(contact.position1, &mut data.rng));
+                }
+                data.damage.push(DamageEvent::new(target, damage, contact.position1));
+            }
+        }
+    }
+}
+
+#[derive(SystemData)]
+struct EnemyMovementData<'a> {
+    body_idc: RS<'a, RigidBodyID>,
+    enemyc: RS<'a, BasicEnemy>,
+    flyerc: RS<'a, Flyer>,
+    playerc: RS<'a, Player>,
+    ranged_attackerc: RS<'a, RangedAttacker>,
+    patrolc: WS<'a, Patrol>,
+    aggroc: WS<'a, Aggro>,
+    facingc: WS<'a, Facing>,
+    status_effectsc: RS<'a, StatusEffects>,
+
+    entities: specs::Entities<'a>,
+    c: specs::Fetch<'a, SystemContext>,
+    snapshot: specs::Fetch<'a, PhysicsSnapshot>,
+}
+
+const ENEMY_CHASE_SPEED: N = 3.0;
+// Enemies notice the player behind them only when this close, as a fraction of their aggro radius.
+const AGGRO_BEHIND_FRACTION: N = 0.3;
+// Engaged enemies give up once the player is this far away, as a multiple of their aggro radius.
+const AGGRO_LOSE_MULTIPLE: N = 1.5;
+
+struct EnemyMovementSystem;
+
+impl<'a> specs::System<'a> for EnemyMovementSystem {
+    type SystemData = EnemyMovementData<'a>;
+
+    fn run(&mut self, mut data: Self::SystemData) {
+        if data.c.time_is_stopped {
+            return;
+        }
+
+        let physics = data.c.physics_thread_link.lock().unwrap();
+
+        let player_alive = data.playerc.get(data.c.player).map_or(false, |player| !player.is_dead);
+        let player_pos = data.body_idc
+            .get(data.c.player)
+            .and_then(|&body_id| data.snapshot.position(body_id))
+            .map(|pos| pos.translation.vector);
+
+        for (entity, &body_id, enemy, facing) in (&*data.entities, &data.body_idc, &data.enemyc, &mut data.facingc).join() {
+            if enemy.is_dead {
+                continue;
+            }
+
+            let (pos, mut vel) = match (data.snapshot.position(body_id), data.snapshot.lin_vel(body_id)) {
+                (Some(pos), Some(vel)) => (pos.translation.vector, vel),
+                _ => continue,
+            };
+
+            let mut engaged = false;
+            if let Some(aggro) = data.aggroc.get_mut(entity) {
+                aggro.engaged = match player_pos {
+                    Some(player_pos) if player_alive => {
+                        let offset = player_pos - pos;
+                        let in_front = offset.x * facing.sign() >= 0.0;
+
+                        if aggro.engaged {
+                            offset.norm() <= aggro.radius * AGGRO_LOSE_MULTIPLE
+                        } else if in_front {
+                            offset.norm() <= aggro.radius
+                        } else {
+                            offset.norm() <= aggro.radius * AGGRO_BEHIND_FRACTION
+                        }
+                    }
+                    _ => false,
+                };
+                engaged = aggro.engaged;
+            }
+
+            // FlyerSystem does its own steering
+            if data.flyerc.get(entity).is_some() {
+                continue;
+            }
+
+            let target_x_vel = if engaged {
+                let dx = player_pos.unwrap().x - pos.x;
+                *facing = Facing::from_direction(dx);
+
+                // shooters keep their distance
+                if data.ranged_attackerc.get(entity).is_some() || dx.abs() < 0.5 {
+                    0.0
+                } else {
+                    dx.signum() * ENEMY_CHASE_SPEED
+                }
+            } else if let Some(patrol) = data.patrolc.get_mut(entity) {
+                if (patrol.target().x - pos.x).abs() < 0.1 {
+                    patrol.advance();
+                }
+
+                let dx = patrol.target().x - pos.x;
+                *facing = Facing::from_direction(dx);
+                dx.signum() * patrol.speed
+            } else {
+                continue;
+            };
+
+            let speed_multiplier = data.status_effectsc
+                .get(entity)
+                .map_or(1.0, |effects| effects.speed_multiplier());
+
+            vel.x = target_x_vel * speed_multiplier;
+            physics.set_lin_vel(body_id, vel);
+        }
+    }
+}
+
+#[derive(SystemData)]
+struct FlyerData<'a> {
+    body_idc: RS<'a, RigidBodyID>,
+    enemyc: RS<'a, BasicEnemy>,
+    playerc: RS<'a, Player>,
+    aggroc: RS<'a, Aggro>,
+    flyerc: WS<'a, Flyer>,
+    facingc: WS<'a, Facing>,
+    status_effectsc: RS<'a, StatusEffects>,
+
+    entities: specs::Entities<'a>,
+    c: specs::Fetch<'a, SystemContext>,
+    snapshot: specs::Fetch<'a, PhysicsSnapshot>,
+}
+
+// How quickly flyers can change velocity, in m/s^2.
+const FLYER_ACCELERATION: N = 12.0;
+const FLYER_MAX_DIVE_TIME: N = 1.5;
+const FLYER_RECOVER_TIME: N = 1.0;
+
+struct FlyerSystem;
+
+impl<'a> specs::System<'a> for FlyerSystem {
+    type SystemData = FlyerData<'a>;
+
+    fn run(&mut self, mut data: Self::SystemData) {
+        if data.c.time_is_stopped {
+            return;
+        }
+
+        let physics = data.c.physics_thread_link.lock().unwrap();
+        let time = data.c.time;
+
+        let player_alive = data.playerc.get(data.c.player).map_or(false, |player| !player.is_dead);
+        let player_pos = data.body_idc
+            .get(data.c.player)
+            .and_then(|&body_id| data.snapshot.position(body_id))
+            .map(|pos| pos.translation.vector);
+
+        for (entity, &body_id, enemy, flyer) in (&*data.entities, &data.body_idc, &data.enemyc, &mut data.flyerc).join() {
+            let stunned = data.status_effectsc
+                .get(entity)
+                .map_or(false, |effects| effects.is_stunned());
+            if enemy.is_dead || stunned {
+                continue;
+            }
+
+            let (pos, vel) = match (data.snapshot.position(body_id), data.snapshot.lin_vel(body_id)) {
+                (Some(pos), Some(vel)) => (pos.translation.vector, vel),
+                _ => continue,
+            };
+
+            let engaged = player_alive && data.aggroc.get(entity).map_or(true, |aggro| aggro.engaged);
+
+            let desired_vel = match player_pos {
+                Some(player_pos) if engaged => {
+                    let hover_pos = player_pos - Vector::new(0.0, flyer.hover_height);
+
+                    let (state, desired_vel) = match flyer.state {
+                        FlyerState::Hovering { remaining } => {
+                            let offset = hover_pos - pos;
+                            if remaining <= time {
+                                (
+                                    FlyerState::Diving {
+                                        target: player_pos,
+                                        remaining: FLYER_MAX_DIVE_TIME,
+                                    },
+                                    vel,
+                                )
+                            } else {
+                                (
+                                    FlyerState::Hovering { remaining: remaining - time },
+                                    steer_towards(offset, flyer.speed),
+                                )
+                            }
+                        }
+                        FlyerState::Diving { target, remaining } => {
+                            let offset = target - pos;
+                            if remaining <= time || offset.norm() < 0.3 {
+                                (FlyerState::Recovering { remaining: FLYER_RECOVER_TIME }, vel)
+                            } else {
+                                (
+                                    FlyerState::Diving {
+                                        target,
+                                        remaining: remaining - time,
+                                    },
+                                    steer_towards(offset, flyer.dive_speed),
+                                )
+                            }
+                        }
+                        FlyerState::Recovering { remaining } => {
+                            if remaining <= time {
+                                (FlyerState::Hovering { remaining: flyer.dive_interval }, vel)
+                            } else {
+                                (
+                                    FlyerState::Recovering { remaining: remaining - time },
+                                    steer_towards(hover_pos - pos, flyer.speed),
+                                )
+                            }
+                        }
+                    };
+
+                    flyer.state = state;
+                    if let Some(facing) = data.facingc.get_mut(entity) {
+                        *facing = Facing::from_direction(player_pos.x - pos.x);
+                    }
+                    desired_vel
+                }
+                // idle flyers hang in the air
+                _ => Vector::zero(),
+            };
+
+            // turn gradually rather than snapping to the new velocity
+            let change = desired_vel - vel;
+            let max_change = FLYER_ACCELERATION * time;
+            let new_vel = if change.norm() > max_change {
+                vel + change.normalize() * max_change
+            } else {
+                desired_vel
+            };
+
+            physics.set_lin_vel(body_id, new_vel);
+            physics.set_ang_vel(body_id, Orientation::new(0.0));
+            physics.set_rotation(body_id, Rotation::new(0.0));
+        }
+    }
+}
+
+// Velocity heading along offset at up to max_speed, slowing down when close so as not to overshoot.
+fn steer_towards(offset: Vector<N>, max_speed: N) -> Vector<N> {
+    let distance = offset.norm();
+    if distance == 0.0 {
+        return Vector::zero();
+    }
+
+    offset / distance * max_speed.min(distance * 2.0)
+}
+
+#[derive(SystemData)]
+struct BossData<'a> {
+    body_idc: RS<'a, RigidBodyID>,
+    enemyc: RS<'a, BasicEnemy>,
+    hitpointsc: RS<'a, Hitpoints>,
+    bossc: WS<'a, Boss>,
+    status_effectsc: RS<'a, StatusEffects>,
+
+    entities: specs::Entities<'a>,
+    c: specs::Fetch<'a, SystemContext>,
+    snapshot: specs::Fetch<'a, PhysicsSnapshot>,
+    rng: specs::FetchMut<'a, GameRng>,
+    spawns: specs::FetchMut<'a, SpawnBuffer>,
+    events: specs::FetchMut<'a, GameEvents>,
+}
+
+const BOSS_REST_TIME: N = 2.0;
+const BOSS_CHARGE_SPEED: N = 8.0;
+const BOSS_CHARGE_TIME: N = 1.2;
+const BOSS_SPREAD_BULLETS: usize = 7;
+// Angle between neighbouring bullets in a spread, in radians.
+const BOSS_SPREAD_ANGLE: N = 0.15;
+const BOSS_BULLET_SPEED: N = 12.0;
+const BOSS_THROW_SPEED: N = 12.0;
+
+struct BossSystem;
+
+impl<'a> specs::System<'a> for BossSystem {
+    type SystemData = BossData<'a>;
+
+    fn run(&mut self, mut data: Self::SystemData) {
+        use rand::Rng;
+
+        if data.c.time_is_stopped {
+            return;
+        }
+
+        let physics = data.c.physics_thread_link.lock().unwrap();
+        let time = data.c.time;
+
+        let player_body_id = *data.body_idc.get(data.c.player).unwrap();
+        let (player_pos, player_vel) = match (
+            data.snapshot.position(player_body_id),
+            data.snapshot.lin_vel(player_body_id),
+        ) {
+            (Some(pos), Some(vel)) => (pos.translation.vector, vel),
+            _ => return,
+        };
+
+        for (entity, &body_id, enemy, hitpoints, boss) in (&*data.entities, &data.body_idc, &data.enemyc, &data.hitpointsc, &mut data.bossc).join() {
+            let stunned = data.status_effectsc
+                .get(entity)
+                .map_or(false, |effects| effects.is_stunned());
+            if enemy.is_dead || stunned {
+                continue;
+            }
+
+            let phase = Boss::phase_for(hitpoints);
+            if phase != boss.phase {
+                boss.phase = phase;
+                data.events.push(GameEvent::BossPhaseChanged { entity, phase });
+            }
+
+            let (pos, vel) = match (data.snapshot.position(body_id), data.snapshot.lin_vel(body_id)) {
+                (Some(pos), Some(vel)) => (pos.translation.vector, vel),
+                _ => continue,
+            };
+
+            // later phases attack more often and charge faster
+            let speedup = 1.0 + 0.25 * (phase - 1) as N;
+
+            boss.state = match boss.state {
+                BossState::Idle { remaining } if remaining > time => BossState::Idle { remaining: remaining - time },
+                BossState::Idle { .. } => {
+                    let to_player = player_pos - pos;
+                    let rest = BossState::Idle { remaining: BOSS_REST_TIME / speedup };
+
+                    match data.rng.gen_range(0, phase) {
+                        0 => {
+                            // a stomp to set off
+                            data.events.push(GameEvent::Impact {
+                                position: Point::from_coordinates(pos),
+                                strength: 0.5,
+                            });
+                            BossState::Charging {
+                                direction: to_player.x.signum(),
+                                remaining: BOSS_CHARGE_TIME,
+                            }
+                        }
+                        1 => {
+                            let aim = to_player.y.atan2(to_player.x);
+                            let muzzle_distance = boss.half_height + 0.3;
+
+                            for i in 0..BOSS_SPREAD_BULLETS {
+                                let offset = i as N - (BOSS_SPREAD_BULLETS - 1) as N / 2.0;
+                                let angle = aim + offset * BOSS_SPREAD_ANGLE;
+                                let dir = Vector::new(angle.cos(), angle.sin());
+
+                                data.spawns.push(SpawnCommand::Bullet {
+                                    pos: pos + dir * muzzle_distance,
+                                    radius: 0.08,
+                                    velocity: dir * BOSS_BULLET_SPEED,
+                                    damage: Some(1),
+                                    team: Team::Enemy,
+                                });
+                            }
+                            rest
+                        }
+                        _ => {
+                            let crate_pos = pos - Vector::new(0.0, boss.half_height + 0.6);
+                            let dir = lead_target(crate_pos, player_pos, player_vel, BOSS_THROW_SPEED, data.c.gravity);
+
+                            data.spawns.push(SpawnCommand::ThrownCrate {
+                                rect: Rect::new(crate_pos.x, crate_pos.y, 0.4, 0.4),
+                                material: CrateMaterial::Wood,
+                                velocity: dir * BOSS_THROW_SPEED,
+                                thrower: entity,
+                            });
+                            rest
+                        }
+                    }
+                }
+                BossState::Charging { direction, remaining } => {
+                    physics.set_lin_vel(body_id, Vector::new(direction * BOSS_CHARGE_SPEED * speedup, vel.y));
+
+                    if remaining > time {
+                        BossState::Charging {
+                            direction,
+                            remaining: remaining - time,
+                        }
+                    } else {
+                        physics.set_lin_vel(body_id, Vector::new(0.0, vel.y));
+                        BossState::Idle { remaining: BOSS_REST_TIME / speedup }
+                    }
+                }
+            };
+
+            physics.set_ang_vel(body_id, Orientation::new(0.0));
+            physics.set_rotation(body_id, Rotation::new(0.0));
+        }
+    }
+}
+
+#[derive(SystemData)]
+struct RangedAttackerData<'a> {
+    body_idc: RS<'a, RigidBodyID>,
+    ranged_attackerc: WS<'a, RangedAttacker>,
+    hitpointsc: RS<'a, Hitpoints>,
+    aggroc: RS<'a, Aggro>,
+    status_effectsc: RS<'a, StatusEffects>,
+
+    entities: specs::Entities<'a>,
+    c: specs::Fetch<'a, SystemContext>,
+    snapshot: specs::Fetch<'a, PhysicsSnapshot>,
+    spawns: specs::FetchMut<'a, SpawnBuffer>,
+}
+
+struct RangedAttackerSystem;
+
+impl<'a> specs::System<'a> for RangedAttackerSystem {
+    type SystemData = RangedAttackerData<'a>;
+
+    fn run(&mut self, mut data: Self::SystemData) {
+        if data.c.time_is_stopped {
+            return;
+        }
+
+        let player_body_id = *data.body_idc.get(data.c.player).unwrap();
+        let (player_pos, player_vel) = match (
+            data.snapshot.position(player_body_id),
+            data.snapshot.lin_vel(player_body_id),
+        ) {
+            (Some(pos), Some(vel)) => (pos.translation.vector, vel),
+            _ => return,
+        };
+
+        for (entity, &body_id, attacker) in (&*data.entities, &data.body_idc, &mut data.ranged_attackerc).join() {
+            let is_dead = data.hitpointsc
+                .get(entity)
+                .map_or(false, |hitpoints| hitpoints.current() == 0);
+            if is_dead || !attacker.tick(data.c.time) {
+                continue;
+            }
+
+            if data.aggroc.get(entity).map_or(false, |aggro| !aggro.engaged) {
+                continue;
+            }
+
+            if data.status_effectsc.get(entity).map_or(false, |effects| effects.is_stunned()) {
+                continue;
+            }
+
+            let pos = match data.snapshot.position(body_id) {
+                Some(pos) => pos.translation.vector,
+                None => continue,
+            };
+
+            if (player_pos - pos).norm() > attacker.range {
+                continue;
+            }
+
+            let dir = lead_target(pos, player_pos, player_vel, attacker.bullet_speed, data.c.gravity);
+
+            data.spawns.push(SpawnCommand::Bullet {
+                pos: pos + dir * attacker.muzzle_distance,
+                radius: attacker.bullet_radius,
+                velocity: dir * attacker.bullet_speed,
+                damage: Some(attacker.bullet_damage),
+                team: Team::Enemy,
+            });
+        }
+    }
+}
+
+// How long corpses stay around, and how much of that is spent fading out.
+const CORPSE_TIME: N = 8.0;
+const CORPSE_FADE_TIME: N = 2.0;
+
+#[derive(SystemData)]
+struct CrateData<'a> {
+    body_idc: RS<'a, RigidBodyID>,
+    cratec: RS<'a, Crate>,
+    hitpointsc: RS<'a, Hitpoints>,
+    removec: WS<'a, Remove>,
+
+    entities: specs::Entities<'a>,
+    snapshot: specs::Fetch<'a, PhysicsSnapshot>,
+    rng: specs::FetchMut<'a, GameRng>,
+    spawns: specs::FetchMut<'a, SpawnBuffer>,
+}
+
+struct CrateSystem;
+
+impl<'a> specs::System<'a> for CrateSystem {
+    type SystemData = CrateData<'a>;
+
+    fn run(&mut self, mut data: Self::SystemData) {
+        use rand::Rng;
+
+        for (entity, &body_id, crate_, hitpoints) in (&*data.entities, &data.body_idc, &data.cratec, &data.hitpointsc).join() {
+            if hitpoints.current() > 0 || data.removec.get(entity).is_some() {
+                continue;
+            }
+
+            let pos = match data.snapshot.position(body_id) {
+                Some(pos) => pos.translation.vector,
+                None => continue,
+            };
+
+            data.removec.insert(entity, Remove);
+
+            let (outer_color, inner_color) = crate_.material.color();
+            for i in 0..12 {
+                let size = data.rng.gen_range(0.05, 0.12);
+                data.spawns.push(SpawnCommand::Particle {
+                    rect: Rect::new(pos.x, pos.y, size, size),
+                    velocity: Vector::new(data.rng.gen_range(-3.0, 3.0), data.rng.gen_range(-4.0, 0.0)),
+                    ttl: data.rng.gen_range(1.0, 3.0),
+                    color: if i % 2 == 0 { outer_color } else { inner_color },
+                });
+            }
+
+            for &loot in &crate_.contents {
+                match loot {
+                    Loot::Knife => data.spawns.push(SpawnCommand::Knife {
+                        x: pos.x,
+                        y: pos.y,
+                        velocity: Vector::new(0.0, -2.0),
+                    }),
+                    Loot::Health(amount) => data.spawns.push(SpawnCommand::Pickup {
+                        pos,
+                        kind: PickupKind::Health(amount),
+                    }),
+                    Loot::Coins(count) => for i in 0..count {
+                        // spread the coins out in a row, so they can be collected one by one
+                        let offset = (i as N - (count - 1) as N / 2.0) * 0.5;
+                        data.spawns.push(SpawnCommand::Coin {
+                            pos: pos + Vector::new(offset, 0.0),
+                            value: 1,
+                        });
+                    },
+                }
+            }
+        }
+    }
+}
+
+#[derive(SystemData)]
+struct DamageData<'a> {
+    body_idc: RS<'a, RigidBodyID>,
+    hitpointsc: WS<'a, Hitpoints>,
+    hit_flashc: WS<'a, HitFlash>,
+    invulnerablec: WS<'a, Invulnerable>,
+    status_effectsc: WS<'a, StatusEffects>,
+    teamc: RS<'a, Team>,
+
+    entities: specs::Entities<'a>,
+    c: specs::Fetch<'a, SystemContext>,
+    damage: specs::FetchMut<'a, DamageEvents>,
+    events: specs::FetchMut<'a, GameEvents>,
+}
+
+const HIT_FLASH_TIME: N = 0.12;
+// How long the player is safe from further damage after being hurt.
+const PLAYER_INVULNERABLE_TIME: N = 1.0;
+
+/// Deals the damage queued in `DamageEvents` during the step.
+struct DamageSystem;
+
+impl<'a> specs::System<'a> for DamageSystem {
+    type SystemData = DamageData<'a>;
+
+    fn run(&mut self, mut data: Self::SystemData) {
+        let mut finished = Vec::new();
+        for (entity, flash) in (&*data.entities, &mut data.hit_flashc).join() {
+            flash.0 -= data.c.time;
+            if flash.0 <= 0.0 {
+                finished.push(entity);
+            }
+        }
+        for entity in finished {
+            data.hit_flashc.remove(entity);
+        }
+
+        if !data.c.time_is_stopped {
+            let mut finished = Vec::new();
+            for (entity, invulnerable) in (&*data.entities, &mut data.invulnerablec).join() {
+                invulnerable.0 -= data.c.time;
+                if invulnerable.0 <= 0.0 {
+                    finished.push(entity);
+                }
+            }
+            for entity in finished {
+                data.invulnerablec.remove(entity);
+            }
+        }
+
+        let physics = data.c.physics_thread_link.lock().unwrap();
+
+        for event in data.damage.drain() {
+            if data.invulnerablec.get(event.target).is_some() {
+                continue;
+            }
+
+            let friendly_fire = match (event.source_team, data.teamc.get(event.target)) {
+                (Some(source), Some(&target)) => source.is_friendly_to(target),
+                _ => false,
+            };
+            if friendly_fire {
+                continue;
+            }
+
+            match data.hitpointsc.get_mut(event.target) {
+                Some(hitpoints) if hitpoints.current() > 0 => hitpoints.damage(event.amount),
+                _ => continue,
+            }
+
+            if let Some(&body_id) = data.body_idc.get(event.target) {
+                if event.impulse != Vector::zero() {
+                    physics.apply_central_impulse(body_id, event.impulse);
+                }
+            }
+
+            if let Some(effect) = event.effect {
+                if data.status_effectsc.get(event.target).is_none() {
+                    data.status_effectsc.insert(event.target, StatusEffects::default());
+                }
+                data.status_effectsc.get_mut(event.target).unwrap().add(effect);
+            }
+
+            data.hit_flashc.insert(event.target, HitFlash(HIT_FLASH_TIME));
+            if event.target == data.c.player {
+                data.invulnerablec.insert(event.target, Invulnerable(PLAYER_INVULNERABLE_TIME));
+            }
+            data.events.push(GameEvent::Damaged {
+                entity: event.target,
+                damage: event.amount,
+                position: event.position,
+            });
+        }
+    }
+}
+
+#[derive(SystemData)]
+struct DeathData<'a> {
+    basic_enemyc: WS<'a, BasicEnemy>,
+    playerc: WS<'a, Player>,
+    hitpointsc: RS<'a, Hitpoints>,
+    body_idc: RS<'a, RigidBodyID>,
+    corpsec: WS<'a, Corpse>,
+    contact_damagec: WS<'a, ContactDamage>,
+
+    entities: specs::Entities<'a>,
+    c: specs::Fetch<'a, SystemContext>,
+    rng: specs::FetchMut<'a, GameRng>,
+    events: specs::FetchMut<'a, GameEvents>,
+    time_stop_meter: specs::FetchMut<'a, TimeStopMeter>,
+}
+
+struct DeathSystem;
+
+impl<'a> specs::System<'a> for DeathSystem {
+    type SystemData = DeathData<'a>;
+
+    fn run(&mut self, mut data: Self::SystemData) {
+        use rand::Rng;
+
+        let physics = data.c.physics_thread_link.lock().unwrap();
+
+        for (entity, enemy, hitpoints, &body_id) in (&*data.entities, &mut data.basic_enemyc, &data.hitpointsc, &data.body_idc).join() {
+            if enemy.is_dead || hitpoints.current() > 0 {
+                continue;
+            }
+
+            enemy.is_dead = true;
+            physics.set_collision_groups_kind(body_id, CollisionGroupsKind::DeadEnemy);
+
+            // tip over to one side
+            let direction = if data.rng.gen() { 1.0 } else { -1.0 };
+            physics.set_ang_vel(body_id, Orientation::new(direction * 2.5));
+
+            data.contact_damagec.remove(entity);
+            data.corpsec
+                .insert(entity, Corpse::new(CORPSE_TIME, CORPSE_FADE_TIME));
+            data.events.push(GameEvent::EntityDied { entity });
+            data.time_stop_meter.reward_kill();
+        }
+
+        let player = data.c.player;
+        let body_id = match data.body_idc.get(player) {
+            Some(&body_id) => body_id,
+            None => return,
+        };
+        let player_is_dead = data.hitpointsc.get(player).map_or(false, |hitpoints| hitpoints.current() == 0);
+        let player = match data.playerc.get_mut(player) {
+            Some(player) => player,
+            None => return,
+        };
+
+        if player_is_dead && !player.is_dead {
+            player.is_dead = true;
+            physics.set_ang_vel(body_id, Orientation::new(-2.5));
+            data.events.push(GameEvent::PlayerDied);
+        }
+    }
+}
+
+#[derive(SystemData)]
+struct CorpseData<'a> {
+    corpsec: WS<'a, Corpse>,
+    renderablec: WS<'a, Renderable>,
+    removec: WS<'a, Remove>,
+
+    entities: specs::Entities<'a>,
+    c: specs::Fetch<'a, SystemContext>,
+}
+
+struct CorpseSystem;
+
+impl<'a> specs::System<'a> for CorpseSystem {
+    type SystemData = CorpseData<'a>;
+
+    fn run(&mut self, mut data: Self::SystemData) {
+        if data.c.time_is_stopped {
+            return;
+        }
+
+        for (entity, corpse, renderable) in (&*data.entities, &mut data.corpsec, &mut data.renderablec).join() {
+            corpse.remaining -= data.c.time;
+
+            let alpha = corpse.alpha();
+            for item in &mut renderable.items {
+                item.color[3] = alpha;
+            }
+
+            if corpse.remaining <= 0.0 {
+                data.removec.insert(entity, Remove);
+            }
+        }
+    }
+}
+
+#[derive(SystemData)]
+struct TextFadeData<'a> {
+    text_fadec: WS<'a, TextFade>,
+    renderablec: WS<'a, Renderable>,
+    removec: WS<'a, Remove>,
+
+    entities: specs::Entities<'a>,
+    c: specs::Fetch<'a, SystemContext>,
+}
+
+struct TextFadeSystem;
+
+impl<'a> specs::System<'a> for TextFadeSystem {
+    type SystemData = TextFadeData<'a>;
+
+    fn run(&mut self, mut data: Self::SystemData) {
+        if data.c.time_is_stopped {
+            return;
+        }
+
+        for (entity, fade, renderable) in (&*data.entities, &mut data.text_fadec, &mut data.renderablec).join() {
+            fade.elapsed += data.c.time;
+
+            let alpha = fade.alpha();
+            for item in &mut renderable.items {
+                if let RenderItemKind::Text { .. } = item.kind {
+                    item.color[3] = alpha;
+                }
+            }
+
+            if fade.remove_when_done && fade.is_done() {
+                data.removec.insert(entity, Remove);
+            }
+        }
+    }
+}
+
+
+#[derive(SystemData)]
+struct RemoveOOBEntitiesData<'a> {
+    body_idc: RS<'a, RigidBodyID>,
+    removec: WS<'a, Remove>,
+    hitpointsc: WS<'a, Hitpoints>,
+
+    entities: specs::Entities<'a>,
+    c: specs::Fetch<'a, SystemContext>,
+    snapshot: specs::Fetch<'a, PhysicsSnapshot>,
+}
+
+struct RemoveOOBEntitiesSystem;
+
+impl<'a> specs::System<'a> for RemoveOOBEntitiesSystem {
+    type SystemData = RemoveOOBEntitiesData<'a>;
+
+    fn run(&mut self, mut data: Self::SystemData) {
+        for (entity, &body_id) in (&*data.entities, &data.body_idc).join() {
+            let pos = match data.snapshot.position(body_id) {
+                Some(pos) => pos,
+                None => continue,
+            };
+            let x = pos.translation.vector.x;
+            let y = pos.translation.vector.y;
+
+            if x.abs() > 500.0 || y.abs() > 500.0 {
+                // the player is never removed, leaving the level just kills them
+                if entity == data.c.player {
+                    if let Some(hitpoints) = data.hitpointsc.get_mut(entity) {
+                        hitpoints.set_current(0);
+                    }
+                    continue;
+                }
+
+                data.removec.insert(entity, Remove);
+                println!("removed entity for being OOB");
+            }
+        }
+    }
+}
+
+// Helper functions
+
+// Returns the direction to fire a projectile from `origin` so that it meets a target moving at a constant velocity.
+// The aim is raised to make up for the drop due to gravity over the flight time.
+fn lead_target(origin: Vector<N>, target: Vector<N>, target_vel: Vector<N>, speed: N, gravity: Vector<N>) -> Vector<N> {
+    let d = target - origin;
+
+    // solve |d + target_vel * t| = speed * t for the smallest positive t
+    let a = target_vel.norm_squared() - speed * speed;
+    let b = 2.0 * d.dot(&target_vel);
+    let c = d.norm_squared();
+
+    let t = if a.abs() < 1e-6 {
+        if b.abs() < 1e-6 { 0.0 } else { (-c / b).max(0.0) }
+    } else {
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant < 0.0 {
+            // the target is too fast to ever catch, so just aim where it is now
+            0.0
+        } else {
+            let sqrt = discriminant.sqrt();
+            let t1 = (-b - sqrt) / (2.0 * a);
+            let t2 = (-b + sqrt) / (2.0 * a);
+            match (t1 > 0.0, t2 > 0.0) {
+                (true, true) => t1.min(t2),
+                (true, false) => t1,
+                (false, true) => t2,
+                (false, false) => 0.0,
+            }
+        }
+    };
+
+    let aim = d + target_vel * t - gravity * (0.5 * t * t);
+    if aim.norm_squared() < 1e-6 {
+        return Vector::new(1.0, 0.0);
+    }
+    aim.normalize()
+}
+
+// Effect particles don't collide with anything, so blood settles this far below the wound instead of on the ground.
+const BLOOD_FLOOR_DROP: N = 0.5;
+
+fn spawn_blood(origin: Point<N>, rng: &mut GameRng) -> Vec<EffectParticle> {
+    let mut res = Vec::new();
+
+    use rand::distributions::{ChiSquared, IndependentSample, Normal, Range};
+
+    let mean_size = 0.065;
+
+    let size_dist = Normal::new(mean_size, 0.02);
+    let velocity_dist = Normal::new(0.0, 1.0);
+    let ttl_dist = ChiSquared::new(4.0);
+
+    let max_num_dist = Range::new(2, 5);
+
+    for i in 0..max_num_dist.ind_sample(rng) {
         let size = size_dist
             .ind_sample(rng)
             .max(0.055)
@@ -394,28 +2460,70 @@ fn spawn_blood(origin: Point<N>) -> Vec<Event> {
         // Bigger particles tend to live for less time
         let ttl = ttl_dist.ind_sample(rng).min(30.0) * (mean_size / size);
 
-        res.push(Event::SpawnParticle {
-            rect: Rect::new(origin.x, origin.y, size as N, size as N),
-            velocity: Vector::new(
-                velocity_dist.ind_sample(rng) as N,
-                velocity_dist.ind_sample(rng) as N,
-            ),
-            ttl: ttl as N,
-        });
+        let velocity = Vector::new(
+            velocity_dist.ind_sample(rng) as N,
+            velocity_dist.ind_sample(rng) as N,
+        );
+        res.push(
+            EffectParticle::new(origin, velocity, size as N * 2.0, ttl as N, [1.0, 0.0, 0.0, 1.0])
+                .with_floor(origin.y + BLOOD_FLOOR_DROP),
+        );
     }
 
     res
 }
 
-fn add_fixed_joint_from_contact(physics: &PhysicsThreadLink, contact: &Contact) {
+fn spawn_explosion(origin: Point<N>, rng: &mut GameRng) -> Vec<EffectParticle> {
+    use rand::distributions::{IndependentSample, Range};
+
+    let speed_dist = Range::new(2.0, 8.0);
+    let angle_dist = Range::new(0.0, 2.0 * ::std::f64::consts::PI);
+    let ttl_dist = Range::new(0.3, 0.8);
+    let colors = [[1.0, 0.6, 0.0, 1.0], [1.0, 0.85, 0.2, 1.0], [0.3, 0.3, 0.3, 1.0]];
+
+    (0..16)
+        .map(|i| {
+            let angle = angle_dist.ind_sample(rng);
+            let speed = speed_dist.ind_sample(rng);
+
+            EffectParticle::new(
+                origin,
+                Vector::new((angle.cos() * speed) as N, (angle.sin() * speed) as N),
+                0.16,
+                ttl_dist.ind_sample(rng) as N,
+                colors[i % colors.len()],
+            )
+        })
+        .collect()
+}
+
+/// An impulse of `strength` in the direction of `dir`, or nothing if `dir` is zero.
+fn knockback(dir: Vector<N>, strength: N) -> Vector<N> {
+    if dir.norm() > 0.0 {
+        dir.normalize() * strength
+    } else {
+        Vector::zero()
+    }
+}
+
+fn detach_knife(physics: &PhysicsThreadLink, body_id: RigidBodyID, knife: &mut Knife) {
+    if let Some(joint) = knife.joint.take() {
+        physics.remove_joint(joint);
+        physics.set_collision_groups_kind(body_id, CollisionGroupsKind::Knife);
+        physics.enable_ccd(body_id, Some(PROJECTILE_CCD_THRESHOLD));
+    }
+    knife.stuck_into_entity = None;
+}
+
+fn add_fixed_joint_from_contact(physics: &PhysicsThreadLink, contact: &Contact) -> Result<JointID, PhysicsError> {
     let body1 = contact.obj1.rigid_body_id;
     let body2 = contact.obj2.rigid_body_id;
 
-    let p1 = contact.position1 - Point::from_coordinates(physics.get_position(body1).translation.vector);
-    let p2 = contact.position2 - Point::from_coordinates(physics.get_position(body2).translation.vector);
+    let p1 = contact.position1 - Point::from_coordinates(physics.get_position(body1)?.translation.vector);
+    let p2 = contact.position2 - Point::from_coordinates(physics.get_position(body2)?.translation.vector);
 
-    let r1 = physics.get_rotation(body1);
-    let r2 = physics.get_rotation(body2);
+    let r1 = physics.get_rotation(body1)?;
+    let r2 = physics.get_rotation(body2)?;
 
     let mut local_pos1 = Isometry::new(p1, 0.0);
     let mut local_pos2 = Isometry::new(p2, 0.0);
@@ -426,5 +2534,5 @@ fn add_fixed_joint_from_contact(physics: &PhysicsThreadLink, contact: &Contact)
     local_pos2.append_rotation_mut(&Rotation::new(-r2));
     local_pos2.rotation = UnitComplex::new(-r2);
 
-    physics.add_fixed_joint(body1, body2, local_pos1, local_pos2);
+    physics.add_fixed_joint(body1, body2, local_pos1, local_pos2)
 }
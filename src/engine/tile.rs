@@ -0,0 +1,116 @@
+use super::*;
+
+use serde::{Deserialize, Deserializer};
+use serde::de::Error as DeError;
+
+/// A grid of tiles drawn from a tileset image, for building levels out of many small pieces.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TileLayer {
+    /// Side length of a tile, in metres.
+    pub tile_size: N,
+    /// World position of the top-left corner of the first tile.
+    pub origin: (N, N),
+    /// Image in the media directory, laid out as a grid of `tile_pixels` sized tiles.
+    pub tileset: String,
+    #[serde(deserialize_with = "deserialize_nonzero")]
+    pub tileset_columns: u32,
+    #[serde(deserialize_with = "deserialize_nonzero")]
+    pub tile_pixels: u32,
+    /// Rows of tile ids, from the top. 0 is empty, and n is the (n - 1)th tile of the tileset.
+    pub tiles: Vec<Vec<u32>>,
+    /// Decorative layers are only drawn, without any collision.
+    #[serde(default)]
+    pub decorative: bool,
+}
+
+// Tiles are looked up by dividing by these, so a zero is rejected when the level is loaded.
+fn deserialize_nonzero<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+    let value = u32::deserialize(deserializer)?;
+    if value == 0 {
+        return Err(D::Error::custom("tile layer sizes must be greater than zero"));
+    }
+    Ok(value)
+}
+
+impl TileLayer {
+    pub fn tile_at(&self, row: usize, col: usize) -> u32 {
+        self.tiles
+            .get(row)
+            .and_then(|tiles| tiles.get(col))
+            .cloned()
+            .unwrap_or(0)
+    }
+
+    /// The world position of the top-left corner of a tile.
+    pub fn tile_pos(&self, row: usize, col: usize) -> (N, N) {
+        (
+            self.origin.0 + col as N * self.tile_size,
+            self.origin.1 + row as N * self.tile_size,
+        )
+    }
+
+    /// Where the tile with the given id is in the tileset image, as [x, y, w, h] in pixels.
+    pub fn source_rect(&self, tile: u32) -> [f64; 4] {
+        let index = tile - 1;
+        let size = self.tile_pixels as f64;
+        [
+            (index % self.tileset_columns) as f64 * size,
+            (index / self.tileset_columns) as f64 * size,
+            size,
+            size,
+        ]
+    }
+
+    /// Covers the non-empty tiles with as few rectangles as we easily can, so that the physics world
+    /// doesn't have to deal with a body per tile.
+    /// Runs of tiles are grown to the right first, then downwards while the rows below match.
+    pub fn collision_rects(&self) -> Vec<Rect> {
+        let num_rows = self.tiles.len();
+        let mut covered: Vec<Vec<bool>> = self.tiles
+            .iter()
+            .map(|tiles| vec![false; tiles.len()])
+            .collect();
+        let is_free = |covered: &Vec<Vec<bool>>, row: usize, col: usize| {
+            self.tile_at(row, col) != 0 && !covered[row][col]
+        };
+
+        let mut rects = Vec::new();
+
+        for row in 0..num_rows {
+            for col in 0..self.tiles[row].len() {
+                if !is_free(&covered, row, col) {
+                    continue;
+                }
+
+                let mut width = 1;
+                while col + width < self.tiles[row].len() && is_free(&covered, row, col + width) {
+                    width += 1;
+                }
+
+                let mut height = 1;
+                while row + height < num_rows &&
+                    (col..col + width).all(|c| c < self.tiles[row + height].len() && is_free(&covered, row + height, c))
+                {
+                    height += 1;
+                }
+
+                for r in row..row + height {
+                    for c in col..col + width {
+                        covered[r][c] = true;
+                    }
+                }
+
+                let (x, y) = self.tile_pos(row, col);
+                let hw = width as N * self.tile_size / 2.0;
+                let hh = height as N * self.tile_size / 2.0;
+                rects.push(Rect::new(x + hw, y + hh, hw, hh));
+            }
+        }
+
+        rects
+    }
+}
+
+/// Every tile layer of the level, for rendering.
+#[derive(Default)]
+pub struct TileLayers(pub Vec<TileLayer>);
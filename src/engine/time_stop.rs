@@ -0,0 +1,78 @@
+use super::*;
+
+/// How the time stop meter behaves, set per level.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct TimeStopConfig {
+    /// The longest time can be stopped for, in seconds.
+    pub capacity: N,
+    /// Seconds of stopped time regained per second of normal time.
+    pub recharge_rate: N,
+    /// Seconds regained for each enemy killed.
+    pub kill_bonus: N,
+}
+
+impl Default for TimeStopConfig {
+    fn default() -> Self {
+        TimeStopConfig {
+            capacity: 5.0,
+            recharge_rate: 0.25,
+            kill_bonus: 1.0,
+        }
+    }
+}
+
+// Stopping time with less than this left isn't worth it.
+const MIN_CHARGE_TO_START: N = 0.5;
+
+/// The player's store of stopped time, drained while time is stopped and refilled otherwise.
+#[derive(Debug, Clone, Copy)]
+pub struct TimeStopMeter {
+    pub config: TimeStopConfig,
+
+    charge: N,
+}
+
+impl TimeStopMeter {
+    pub fn new(config: TimeStopConfig) -> Self {
+        TimeStopMeter {
+            config,
+            charge: config.capacity,
+        }
+    }
+
+    pub fn charge(&self) -> N {
+        self.charge
+    }
+
+    /// From 0 (empty) to 1 (full).
+    pub fn fraction(&self) -> N {
+        self.charge / self.config.capacity
+    }
+
+    pub fn can_start(&self) -> bool {
+        self.charge >= MIN_CHARGE_TO_START
+    }
+
+    pub fn drain(&mut self, time: N) {
+        self.charge = (self.charge - time).max(0.0);
+    }
+
+    pub fn recharge(&mut self, time: N) {
+        self.add(time * self.config.recharge_rate);
+    }
+
+    pub fn reward_kill(&mut self) {
+        let bonus = self.config.kill_bonus;
+        self.add(bonus);
+    }
+
+    pub fn add(&mut self, amount: N) {
+        self.charge = (self.charge + amount).min(self.config.capacity);
+    }
+}
+
+impl Default for TimeStopMeter {
+    fn default() -> Self {
+        TimeStopMeter::new(TimeStopConfig::default())
+    }
+}
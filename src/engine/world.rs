@@ -2,10 +2,11 @@ use super::*;
 
 use std::sync::{Arc, Mutex};
 use std::thread;
-use std::mem::uninitialized;
 use std::collections::HashMap;
+use std::time::{Duration, Instant};
 
-use ncollide::shape::{Ball, Cuboid, ShapeHandle};
+use ncollide::shape::{Ball, Capsule, Cuboid, Polyline, ShapeHandle};
+use na::Point2;
 use nphysics;
 use nphysics::math::{AngularInertia, Isometry, Orientation, Point, Rotation, Translation, Vector};
 use nphysics::volumetric::Volumetric;
@@ -23,10 +24,52 @@ pub const BODY_MARGIN: N = 0.04;
 pub const PLAYER_HALF_WIDTH: N = 0.35;
 pub const PLAYER_HALF_HEIGHT: N = 0.85;
 
-// TODO event system: entities aren't really added until events processed
+pub const KNIFE_RESTITUTION: N = 0.2;
+// Ricocheting knives keep most of their speed off each wall.
+const RICOCHET_KNIFE_RESTITUTION: N = 0.9;
+
+// Crates no bigger than this can be picked up.
+const MAX_CARRYABLE_HALF_EXTENT: N = 0.5;
+const THROW_SPEED: N = 10.0;
+
+/// Movement in a single step beyond which fast, small bodies get motion clamping.
+pub const PROJECTILE_CCD_THRESHOLD: N = 0.04;
+
+const MELEE_REACH: N = 1.0;
+const MELEE_DAMAGE: u16 = 2;
+// Speed the target is knocked away at.
+const MELEE_KNOCKBACK: N = 4.0;
+
+/// Default `VelocityLimit` for anything big enough to be knocked around.
+const MAX_LIN_VEL: N = 30.0;
+const MAX_ANG_VEL: N = 30.0;
+
+const GRENADE_FUSE: N = 2.0;
+const GRENADE_STUN_TIME: N = 1.5;
+const SERRATED_BLEED_TIME: N = 2.5;
+const GRENADE_THROW_SPEED: N = 9.0;
+// Grenades are lobbed, so they get some extra upwards velocity.
+const GRENADE_LOFT: N = 3.0;
+
+const ENEMY_PATROL_SPEED: N = 1.5;
+
+/// Optional settings for `World::new_configured_enemy`.
+#[derive(Debug, Clone, Default)]
+pub struct EnemyConfig {
+    pub kind: EnemyKind,
+    pub facing: Facing,
+    /// Defaults to the usual 5.
+    pub hitpoints: Option<u16>,
+    /// Points to walk between, after the starting position.
+    pub patrol: Vec<Vector<N>>,
+    pub aggro_radius: Option<N>,
+}
+
+/// The length of a single physics step, regardless of frame rate.
+pub const FIXED_TIMESTEP: N = 1.0 / 120.0;
 
-#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
-pub struct SensorID(u32);
+// If we fall this far behind (e.g. after a long hitch), drop the remaining time rather than trying to catch up.
+const MAX_STEPS_PER_TICK: usize = 8;
 
 struct Counter {
     next: u32,
@@ -44,8 +87,39 @@ impl Counter {
     }
 }
 
+/// How long each part of the last `World::step` took.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct StepTimings {
+    /// Stepping the physics world and fetching its results, including the channel round trips.
+    pub physics: Duration,
+    pub dispatch: Duration,
+    /// Running the spawn commands pushed by systems.
+    pub spawns: Duration,
+}
+
+/// How many of each thing are alive, for the diagnostics overlay.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct WorldCounts {
+    pub entities: usize,
+    pub bodies: usize,
+    pub particles: usize,
+    pub effect_particles: usize,
+}
+
+/// Everything the physics debug overlay draws.
+#[derive(Debug, Clone)]
+pub struct PhysicsDebugView {
+    pub shapes: Vec<DebugShape>,
+    /// Contacts from the last step, each listed once.
+    pub contacts: Vec<Contact>,
+    /// Position and linear velocity of every body that can move.
+    pub velocities: Vec<(Point<N>, Vector<N>)>,
+    pub player_on_ground: bool,
+}
+
 pub struct World {
     specs_world: specs::World,
+    dispatcher: specs::Dispatcher<'static, 'static>,
     physics_thread: thread::JoinHandle<()>,
     physics_thread_link: Arc<Mutex<PhysicsThreadLink>>,
     next_rigid_body_id: Counter,
@@ -53,14 +127,35 @@ pub struct World {
     player: Entity,
 
     time_stop_remaining: Option<N>,
+    /// Whether the current time stop is the player's, paid for from the `TimeStopMeter`.
+    player_stopped_time: bool,
     normal_gravity: Vector<N>,
+    accumulator: N,
+    last_step_timings: StepTimings,
+    /// Text shown by `Action::ShowText`, with the time it has left.
+    message: Option<(String, N)>,
+    /// How lit the level is away from any lights, from 0 (pitch black) to 1. None skips lighting altogether.
+    ambient_light: Option<f32>,
 }
 
 impl World {
-    pub fn new(x: N, y: N) -> Self {
+    pub fn new(x: N, y: N, seed: u32) -> Self {
         let mut specs_world = specs::World::new();
 
         register_components(&mut specs_world);
+        specs_world.add_resource(PhysicsSnapshot::default());
+        specs_world.add_resource(GameRng::new(seed));
+        specs_world.add_resource(BodyRegistry::default());
+        specs_world.add_resource(SpawnBuffer::default());
+        specs_world.add_resource(GameEvents::default());
+        specs_world.add_resource(DamageEvents::default());
+        specs_world.add_resource(TileLayers::default());
+        specs_world.add_resource(Score::default());
+        specs_world.add_resource(LinkSignals::default());
+        specs_world.add_resource(ActionQueue::default());
+        specs_world.add_resource(TimeStopMeter::default());
+        specs_world.add_resource(PlayerConfig::default());
+        specs_world.add_resource(EffectParticles::default());
 
         let (physics_thread_sender, recv) = chan::sync(0);
         let (send, physics_thread_receiver) = chan::sync(0);
@@ -68,8 +163,13 @@ impl World {
         let gravity = nphysics::math::Vector::new(0.0, 9.81);
         let physics_thread = thread::spawn(move || physics_thread_inner(gravity, recv, send));
 
+        // The player entity is reserved up front so that `World::player` is always valid;
+        // its components are added by `init_player` once the physics thread link exists.
+        let player = specs_world.create_entity().build();
+
         let mut world = World {
             specs_world,
+            dispatcher: register_systems(specs::DispatcherBuilder::new()).build(),
             next_rigid_body_id: Counter::new(),
             next_sensor_id: Counter::new(),
             physics_thread,
@@ -77,16 +177,51 @@ impl World {
                 send: physics_thread_sender,
                 recv: physics_thread_receiver,
             })),
-            player: unsafe { uninitialized() },
+            player,
             time_stop_remaining: None,
+            player_stopped_time: false,
             normal_gravity: gravity,
+            accumulator: 0.0,
+            last_step_timings: StepTimings::default(),
+            message: None,
+            ambient_light: None,
         };
 
-        world.player = world.new_player(x, y);
+        world.init_player(x, y);
+
+        // replaced every step
+        let context = SystemContext {
+            time: 0.0,
+            physics_thread_link: world.physics_thread_link.clone(),
+            time_is_stopped: false,
+            gravity,
+            contact_map: HashMap::new(),
+            contact_events: Vec::new(),
+            sensor_events: Vec::new(),
+            player: world.player,
+        };
+        world.specs_world.add_resource(context);
 
         world
     }
 
+    /// Stops the physics thread and waits for it to exit.
+    /// Messages are sent synchronously, so everything sent before this has already been processed.
+    pub fn shutdown(self) {
+        let World {
+            physics_thread,
+            physics_thread_link,
+            ..
+        } = self;
+
+        physics_thread_link
+            .lock()
+            .unwrap()
+            .send
+            .send(MessageToPhysicsThread::Shutdown);
+        physics_thread.join().unwrap();
+    }
+
     pub fn physics_thread_link(&self) -> Arc<Mutex<PhysicsThreadLink>> {
         self.physics_thread_link.clone()
     }
@@ -95,15 +230,62 @@ impl World {
         self.player
     }
 
+    pub fn entity_for_body(&self, id: RigidBodyID) -> Option<Entity> {
+        self.specs_world
+            .read_resource::<BodyRegistry>()
+            .entity_for_body(id)
+    }
+
+    pub fn body_for_entity(&self, entity: Entity) -> Option<RigidBodyID> {
+        self.specs_world
+            .read_resource::<BodyRegistry>()
+            .body_for_entity(entity)
+    }
+
     pub fn player_rigid_body_id(&self) -> RigidBodyID {
         let idc = self.read_component::<RigidBodyID>();
         *idc.get(self.player).unwrap()
     }
 
+    /// Replaces the collision groups an entity's body was created with.
+    pub fn set_collision_groups(&mut self, entity: Entity, spec: CollisionGroupsSpec) {
+        let id = match self.read_component::<RigidBodyID>().get(entity) {
+            Some(&id) => id,
+            None => return,
+        };
+        self.physics_thread_link.lock().unwrap().set_collision_groups(id, spec);
+    }
+
     pub fn read_component<T: Component>(&self) -> specs::ReadStorage<T> {
         self.specs_world.read::<T>()
     }
 
+    pub fn level_complete(&self) -> bool {
+        self.read_component::<Goal>().join().any(|goal| goal.reached)
+    }
+
+    pub fn score(&self) -> Score {
+        *self.specs_world.read_resource::<Score>()
+    }
+
+    pub fn player_is_dead(&self) -> bool {
+        self.read_component::<Player>()
+            .get(self.player)
+            .map_or(false, |player| player.is_dead)
+    }
+
+    /// The player's (current, max) hitpoints.
+    pub fn player_hitpoints(&self) -> (u16, u16) {
+        self.read_component::<Hitpoints>()
+            .get(self.player)
+            .map_or((0, 0), |hitpoints| (hitpoints.current(), hitpoints.max()))
+    }
+
+    /// Whether the player was just hurt, and is flashing.
+    pub fn player_hit_flash(&self) -> bool {
+        self.read_component::<HitFlash>().get(self.player).is_some()
+    }
+
     pub fn clone_player_component(&self) -> Player {
         self.specs_world
             .read::<Player>()
@@ -112,22 +294,115 @@ impl World {
             .clone()
     }
 
+    /// Where the player is drawn, `alpha` of the way through the current update.
+    pub fn player_render_pos(&self, alpha: N) -> Option<(N, N)> {
+        self.read_component::<Renderable>().get(self.player).map(|renderable| {
+            let (x, y, _) = renderable.interpolated(alpha);
+            (x, y)
+        })
+    }
+
+    pub fn player_inventory(&self) -> Inventory {
+        self.read_component::<Inventory>()
+            .get(self.player)
+            .unwrap()
+            .clone()
+    }
+
+    pub fn player_weapon(&self) -> Weapon {
+        self.player_inventory().selected()
+    }
+
+    pub fn player_facing(&self) -> Facing {
+        self.read_component::<Facing>()
+            .get(self.player)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Turns the player towards `x`, e.g. when they attack something.
+    pub fn player_face_towards(&mut self, x: N) {
+        let player_x = match self.physics_thread_link
+            .lock()
+            .unwrap()
+            .get_position(self.player_rigid_body_id())
+        {
+            Ok(pos) => pos.translation.vector.x,
+            Err(_) => return,
+        };
+
+        self.specs_world
+            .write::<Facing>()
+            .insert(self.player, Facing::from_direction(x - player_x));
+    }
+
+    pub fn player_select_weapon(&mut self, index: usize) {
+        if let Some(inventory) = self.specs_world.write::<Inventory>().get_mut(self.player) {
+            inventory.select(index);
+        }
+    }
+
     pub fn entities(&self) -> specs::Fetch<specs::EntitiesRes> {
         self.specs_world.entities()
     }
 
-    pub fn tick(&mut self, time: N) {
+    /// Advances the world by dt, in as many fixed steps as fit. Leftover time is carried over to the next tick.
+    pub fn tick(&mut self, dt: N) {
+        assert!(dt > 0.0);
+
+        self.accumulator += dt;
+
+        // frames drawn before the next tick interpolate from where things are now to where this tick leaves them
+        if self.accumulator >= FIXED_TIMESTEP {
+            for renderable in (&mut self.specs_world.write::<Renderable>()).join() {
+                renderable.store_previous_transform();
+            }
+            self.specs_world
+                .write_resource::<EffectParticles>()
+                .store_previous_positions();
+        }
+
+        let mut steps = 0;
+        while self.accumulator >= FIXED_TIMESTEP {
+            if steps == MAX_STEPS_PER_TICK {
+                self.accumulator = 0.0;
+                break;
+            }
+
+            self.step(FIXED_TIMESTEP);
+            self.accumulator -= FIXED_TIMESTEP;
+            steps += 1;
+        }
+    }
+
+    /// Returns the events emitted since the last call.
+    pub fn drain_events(&mut self) -> Vec<GameEvent> {
+        self.specs_world.write_resource::<GameEvents>().drain()
+    }
+
+    pub fn last_step_timings(&self) -> StepTimings {
+        self.last_step_timings
+    }
+
+    /// Advances the world by exactly one step of the given length.
+    pub fn step(&mut self, time: N) {
         assert!(time > 0.0);
 
         if self.time_stop_remaining.is_some() {
             let body_id = self.player_rigid_body_id();
             let physics = self.physics_thread_link.lock().unwrap();
-            let inv_mass = physics.get_inv_mass(body_id);
-            physics.apply_central_impulse(body_id, self.normal_gravity * (1.0 / inv_mass) * time);
+            if let Ok(inv_mass) = physics.get_inv_mass(body_id) {
+                physics.apply_central_impulse(body_id, self.normal_gravity * (1.0 / inv_mass) * time);
+            }
         }
 
+        let physics_start = Instant::now();
         self.physics_thread_link.lock().unwrap().step(time);
+        let snapshot = self.physics_thread_link.lock().unwrap().get_snapshot();
         let contacts = self.physics_thread_link.lock().unwrap().get_contacts();
+        let contact_events = self.physics_thread_link.lock().unwrap().get_contact_events();
+        let sensor_events = self.physics_thread_link.lock().unwrap().get_sensor_events();
+        self.last_step_timings.physics = physics_start.elapsed();
 
         let mut contact_map = HashMap::new();
 
@@ -144,25 +419,61 @@ impl World {
 
         self.specs_world.maintain();
 
-        let events = Arc::new(Mutex::new(Vec::new()));
-
         let context = SystemContext {
             time,
             physics_thread_link: self.physics_thread_link.clone(),
             time_is_stopped: self.time_stop_remaining.is_some(),
+            gravity: if self.time_stop_remaining.is_some() {
+                Vector::zero()
+            } else {
+                self.normal_gravity
+            },
             contact_map,
-            events: events.clone(),
+            contact_events,
+            sensor_events,
             player: self.player,
         };
-        self.specs_world.add_resource(context.clone());
+        *self.specs_world.write_resource::<SystemContext>() = context;
+        *self.specs_world.write_resource::<PhysicsSnapshot>() = snapshot;
 
-        let mut dispatcher = register_systems(specs::DispatcherBuilder::new()).build();
-        dispatcher.dispatch(&mut self.specs_world.res);
+        let dispatch_start = Instant::now();
+        self.dispatcher.dispatch(&mut self.specs_world.res);
+        self.last_step_timings.dispatch = dispatch_start.elapsed();
 
         // self.specs_world.maintain();
 
-        for event in &*events.lock().unwrap() {
-            self.run_event(event);
+        let spawns_start = Instant::now();
+        let commands = self.specs_world.write_resource::<SpawnBuffer>().drain();
+        for command in commands {
+            self.spawn(command);
+        }
+        self.last_step_timings.spawns = spawns_start.elapsed();
+
+        if self.time_stop_remaining.is_none() {
+            self.specs_world
+                .write_resource::<EffectParticles>()
+                .update(time, self.normal_gravity);
+        }
+
+        let actions = self.specs_world.write_resource::<ActionQueue>().drain();
+        for action in actions {
+            self.run_action(action);
+        }
+
+        if let Some((_, ref mut remaining)) = self.message {
+            *remaining -= time;
+        }
+        if self.message.as_ref().map_or(false, |&(_, remaining)| remaining <= 0.0) {
+            self.message = None;
+        }
+
+        {
+            let mut meter = self.specs_world.write_resource::<TimeStopMeter>();
+            if self.time_stop_remaining.is_none() {
+                meter.recharge(time);
+            } else if self.player_stopped_time {
+                meter.drain(time);
+            }
         }
 
         if let Some(t) = self.time_stop_remaining {
@@ -174,18 +485,243 @@ impl World {
         }
     }
 
-    pub fn run_event(&mut self, event: &Event) {
-        match *event {
-            Event::SpawnParticle {
+    pub fn spawn(&mut self, command: SpawnCommand) -> Entity {
+        match command {
+            SpawnCommand::Knife { x, y, velocity } => self.new_knife(x, y, velocity, 0),
+            SpawnCommand::Bullet {
+                pos,
+                radius,
+                velocity,
+                damage,
+                team,
+            } => {
+                let entity = self.new_bullet(pos, radius, velocity);
+                if let Some(damage) = damage {
+                    self.specs_world
+                        .write::<ContactDamage>()
+                        .insert(entity, ContactDamage::single_use(damage));
+                }
+                self.specs_world.write::<Team>().insert(entity, team);
+                entity
+            }
+            SpawnCommand::Enemy {
+                rect,
+                kind,
+                spawner,
+            } => {
+                let entity = self.new_configured_enemy(
+                    rect,
+                    EnemyConfig {
+                        kind,
+                        ..EnemyConfig::default()
+                    },
+                );
+                if let Some(spawner) = spawner {
+                    self.specs_world
+                        .write::<SpawnedBy>()
+                        .insert(entity, SpawnedBy(spawner));
+                }
+                entity
+            }
+            SpawnCommand::Crate { rect, material } => self.new_crate(rect, material),
+            SpawnCommand::Particle {
                 rect,
                 velocity,
                 ttl,
+                color,
+            } => self.new_particle(rect, velocity, ttl, color),
+            SpawnCommand::Coin { pos, value } => self.new_coin(pos, value),
+            SpawnCommand::ThrownCrate {
+                rect,
+                material,
+                velocity,
+                thrower,
             } => {
-                self.new_particle(rect, velocity, ttl);
+                let entity = self.new_crate(rect, material);
+                let id = *self.read_component::<RigidBodyID>().get(entity).unwrap();
+                {
+                    let physics = self.physics_thread_link.lock().unwrap();
+                    physics.set_lin_vel(id, velocity);
+                    physics.enable_ccd(id, Some(PROJECTILE_CCD_THRESHOLD));
+                }
+                self.specs_world.write::<Thrown>().insert(
+                    entity,
+                    Thrown {
+                        thrower,
+                        last_velocity: velocity,
+                    },
+                );
+                entity
+            }
+            SpawnCommand::Pickup { pos, kind } => self.new_pickup(pos, kind, None),
+        }
+    }
+
+    pub fn run_action(&mut self, action: Action) {
+        match action {
+            Action::SpawnEnemy { rect, kind } => {
+                self.new_configured_enemy(
+                    rect,
+                    EnemyConfig {
+                        kind,
+                        ..EnemyConfig::default()
+                    },
+                );
+            }
+            Action::Signal { target, signal } => {
+                self.specs_world
+                    .write_resource::<LinkSignals>()
+                    .send(&target, signal);
+            }
+            Action::ShowText { text, duration } => self.message = Some((text, duration)),
+            Action::PlaySound { path } => {
+                self.specs_world
+                    .write_resource::<GameEvents>()
+                    .push(GameEvent::PlaySound { path });
+            }
+            Action::StopTime { duration } => {
+                self.stop_time(duration);
+            }
+        }
+    }
+
+    /// The text currently shown by a trigger, if any.
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_ref().map(|&(ref text, _)| text.as_str())
+    }
+
+    /// Stops time for as long as the meter lasts, or starts it again if the player already stopped it.
+    /// Returns true if time was stopped.
+    pub fn player_toggle_time_stop(&mut self) -> bool {
+        if self.time_stop_remaining.is_some() {
+            if self.player_stopped_time {
+                self.start_time();
             }
+            return false;
+        }
+
+        let meter = *self.specs_world.read_resource::<TimeStopMeter>();
+        if !meter.can_start() {
+            return false;
+        }
+
+        self.stop_time(meter.charge());
+        self.player_stopped_time = true;
+        true
+    }
+
+    /// A frozen knife or bullet at (x, y) which can be re-aimed, if time is stopped.
+    pub fn poseable_at(&self, x: N, y: N) -> Option<Entity> {
+        if self.time_stop_remaining.is_none() {
+            return None;
+        }
+
+        let reach = Vector::new(0.3, 0.3);
+        let pos = Point::new(x, y);
+        let bodies = self.physics_thread_link
+            .lock()
+            .unwrap()
+            .query_aabb(pos - reach, pos + reach);
+
+        let knifec = self.read_component::<Knife>();
+        let bulletc = self.read_component::<Bullet>();
+        let time_stop_storec = self.read_component::<TimeStopStore>();
+
+        bodies
+            .into_iter()
+            .map(|body| body.entity)
+            .find(|&entity| {
+                let is_free_knife = knifec
+                    .get(entity)
+                    .map_or(false, |knife| knife.stuck_into_entity.is_none());
+                let is_projectile = is_free_knife || bulletc.get(entity).is_some();
+
+                is_projectile && time_stop_storec.get(entity).is_some()
+            })
+    }
+
+    /// Points the frozen object grabbed at `grab` towards `aim`, keeping its speed.
+    /// It flies off in the new direction once time starts again.
+    pub fn pose_frozen(&mut self, grab: (N, N), aim: (N, N)) {
+        let entity = match self.poseable_at(grab.0, grab.1) {
+            Some(entity) => entity,
+            None => return,
+        };
+        let body_id = *self.read_component::<RigidBodyID>().get(entity).unwrap();
+
+        let physics = self.physics_thread_link.lock().unwrap();
+        let (pos, cur_vel) = match (physics.get_position(body_id), physics.get_lin_vel(body_id)) {
+            (Ok(pos), Ok(vel)) => (pos.translation.vector, vel),
+            _ => return,
+        };
+
+        let dir = Vector::new(aim.0, aim.1) - pos;
+        if dir.norm() == 0.0 {
+            return;
+        }
+
+        let mut time_stop_storec = self.specs_world.write::<TimeStopStore>();
+        let store = time_stop_storec.get_mut(entity).unwrap();
+        let saved_vel = store.saved_lin_vel.unwrap_or(Vector::zero());
+
+        let new_vel = dir.normalize() * (saved_vel + cur_vel).norm();
+        store.saved_lin_vel = Some(new_vel - cur_vel);
+        // the store may not have been filled by a stopped step yet, and both velocities are saved together
+        if store.saved_ang_vel.is_none() {
+            store.saved_ang_vel = Some(Orientation::zero());
+        }
+
+        if self.read_component::<Knife>().get(entity).is_some() {
+            physics.set_rotation(body_id, Rotation::from_angle(dir.y.atan2(dir.x)));
         }
     }
 
+    /// Where each poseable frozen object is, and the velocity it will have when time starts again.
+    pub fn frozen_velocities(&self) -> Vec<(Vector<N>, Vector<N>)> {
+        if self.time_stop_remaining.is_none() {
+            return Vec::new();
+        }
+
+        let snapshot = self.specs_world.read_resource::<PhysicsSnapshot>();
+        let knifec = self.read_component::<Knife>();
+        let bulletc = self.read_component::<Bullet>();
+
+        (&*self.entities(), &self.read_component::<RigidBodyID>(), &self.read_component::<TimeStopStore>())
+            .join()
+            .filter(|&(entity, _, _)| {
+                knifec
+                    .get(entity)
+                    .map_or(false, |knife| knife.stuck_into_entity.is_none()) || bulletc.get(entity).is_some()
+            })
+            .filter_map(|(_, &body_id, store)| {
+                snapshot.get(body_id).map(|body| {
+                    let saved_vel = store.saved_lin_vel.unwrap_or(Vector::zero());
+                    (body.position.translation.vector, body.lin_vel + saved_vel)
+                })
+            })
+            .collect()
+    }
+
+    pub fn time_stop_meter(&self) -> TimeStopMeter {
+        *self.specs_world.read_resource::<TimeStopMeter>()
+    }
+
+    pub fn set_time_stop_config(&mut self, config: TimeStopConfig) {
+        *self.specs_world.write_resource::<TimeStopMeter>() = TimeStopMeter::new(config);
+    }
+
+    pub fn set_player_config(&mut self, config: PlayerConfig) {
+        *self.specs_world.write_resource::<PlayerConfig>() = config;
+    }
+
+    pub fn set_ambient_light(&mut self, ambient: Option<f32>) {
+        self.ambient_light = ambient.map(|ambient| ambient.max(0.0).min(1.0));
+    }
+
+    pub fn ambient_light(&self) -> Option<f32> {
+        self.ambient_light
+    }
+
     /// Returns true if sucessfully stops time, false otherwise.
     pub fn stop_time(&mut self, dur: N) -> bool {
         if self.time_stop_remaining.is_some() {
@@ -195,6 +731,9 @@ impl World {
         println!("[stop time]");
 
         self.time_stop_remaining = Some(dur);
+        self.specs_world
+            .write_resource::<GameEvents>()
+            .push(GameEvent::TimeStopStarted);
 
         let physics = self.physics_thread_link.lock().unwrap();
         physics.set_gravity(Vector::zero());
@@ -221,6 +760,10 @@ impl World {
         println!("[start time]");
 
         self.time_stop_remaining = None;
+        self.player_stopped_time = false;
+        self.specs_world
+            .write_resource::<GameEvents>()
+            .push(GameEvent::TimeStopEnded);
 
         let physics = self.physics_thread_link.lock().unwrap();
         physics.set_gravity(self.normal_gravity);
@@ -229,30 +772,91 @@ impl World {
 
         let mut time_stop_storec = self.specs_world.write::<TimeStopStore>();
         let rigid_body_idc = self.read_component::<RigidBodyID>();
+        let velocity_limitc = self.read_component::<VelocityLimit>();
 
-        for (&body_id, store) in (&rigid_body_idc, &mut time_stop_storec).join() {
+        for (entity, &body_id, store) in (&*self.entities(), &rigid_body_idc, &mut time_stop_storec).join() {
             assert!(store.saved_ang_vel.is_none() == store.saved_lin_vel.is_none());
 
             // use zero values if this body was created during time stop
             let saved_lin_vel = store.saved_lin_vel.unwrap_or(Vector::zero());
             let saved_ang_vel = store.saved_ang_vel.unwrap_or(Orientation::zero());
 
-            let cur_lin_vel = physics.get_lin_vel(body_id);
-            let cur_ang_vel = physics.get_ang_vel(body_id);
+            let (cur_lin_vel, cur_ang_vel) = match (physics.get_lin_vel(body_id), physics.get_ang_vel(body_id)) {
+                (Ok(lin_vel), Ok(ang_vel)) => (lin_vel, ang_vel),
+                _ => continue,
+            };
 
             // XXX
             // if store.saved_lin_vel.is_some() && !handle.is_active() {
             // handle.activate(na::Bounded::max_value());
             // }
 
-            physics.set_lin_vel(body_id, cur_lin_vel + saved_lin_vel);
-            physics.set_ang_vel(body_id, cur_ang_vel + saved_ang_vel);
+            let (mut lin_vel, mut ang_vel) = (cur_lin_vel + saved_lin_vel, cur_ang_vel + saved_ang_vel);
+            if let Some(limit) = velocity_limitc.get(entity) {
+                lin_vel = limit.clamp_lin(lin_vel);
+                ang_vel = limit.clamp_ang(ang_vel);
+            }
+
+            physics.set_lin_vel(body_id, lin_vel);
+            physics.set_ang_vel(body_id, ang_vel);
 
             store.saved_lin_vel = None;
             store.saved_ang_vel = None;
         }
     }
 
+    pub fn physics_stats(&self) -> PhysicsStats {
+        self.physics_thread_link.lock().unwrap().get_stats()
+    }
+
+    pub fn counts(&self) -> WorldCounts {
+        WorldCounts {
+            entities: (&*self.entities()).join().count(),
+            bodies: self.specs_world.read::<RigidBodyID>().join().count(),
+            particles: self.specs_world.read::<Particle>().join().count(),
+            effect_particles: self.specs_world.read_resource::<EffectParticles>().len(),
+        }
+    }
+
+    pub fn physics_debug_view(&self) -> PhysicsDebugView {
+        let shapes = self.physics_thread_link.lock().unwrap().get_debug_shapes();
+
+        // the contact map has every contact twice, once from each side
+        let contacts = self.specs_world
+            .read_resource::<SystemContext>()
+            .contact_map
+            .values()
+            .flat_map(|contacts| contacts.iter())
+            .filter(|contact| contact.obj1.rigid_body_id < contact.obj2.rigid_body_id)
+            .cloned()
+            .collect();
+
+        let velocities = self.specs_world
+            .read_resource::<PhysicsSnapshot>()
+            .bodies()
+            .filter(|body| !body.is_static)
+            .map(|body| (Point::from_coordinates(body.position.translation.vector), body.lin_vel))
+            .collect();
+
+        PhysicsDebugView {
+            shapes,
+            contacts,
+            velocities,
+            player_on_ground: self.player_on_ground(),
+        }
+    }
+
+    pub fn player_on_ground(&self) -> bool {
+        self.specs_world
+            .read::<GroundSensor>()
+            .get(self.player)
+            .map_or(false, |sensor| sensor.touching_ground)
+    }
+
+    pub fn seed(&self) -> u32 {
+        self.specs_world.read_resource::<GameRng>().seed()
+    }
+
     pub fn time_stop_remaining(&self) -> Option<N> {
         self.time_stop_remaining
     }
@@ -261,29 +865,127 @@ impl World {
         RigidBodyID::new(self.next_rigid_body_id.next())
     }
 
+    // Sends an `AddRigidBody` message to the physics thread and records the body in the registry.
+    fn add_rigid_body(&mut self, message: MessageToPhysicsThread) {
+        if let MessageToPhysicsThread::AddRigidBody { id, entity, .. } = message {
+            self.specs_world
+                .write_resource::<BodyRegistry>()
+                .insert(entity, id);
+        }
+
+        self.physics_thread_link.lock().unwrap().send.send(message);
+    }
+
     fn new_sensor_id(&mut self) -> SensorID {
-        SensorID(self.next_sensor_id.next())
+        SensorID::new(self.next_sensor_id.next())
     }
 
     pub fn new_ground(&mut self, rect: Rect) -> Entity {
-        let Rect { x, y, hw, hh } = rect;
+        let Rect { x, y, hw, hh, rotation } = rect;
+
+        let renderable = Renderable::new(x, y, rotation)
+            .with_layer(LAYER_GROUND)
+            .with(RenderItem::rectangle(
+                0.0,
+                0.0,
+                hw * 2.0,
+                hh * 2.0,
+                0.0,
+                [0.0, 1.0, 0.0, 1.0],
+            ));
+
+        let entity = self.new_static_collider(rect);
+        self.specs_world
+            .write::<Renderable>()
+            .insert(entity, renderable);
+
+        entity
+    }
+
+    pub fn new_hazard(&mut self, rect: Rect, kind: HazardKind, damage: Option<u16>) -> Entity {
+        let Rect { x, y, hw, hh, rotation } = rect;
+
+        let mut renderable = Renderable::new(x, y, rotation);
+        match kind {
+            HazardKind::Spikes => {
+                // a row of roughly equilateral spikes along the top edge
+                let num_spikes = ((hw / hh).round() as usize).max(1);
+                let spike_w = hw * 2.0 / num_spikes as N;
+
+                for i in 0..num_spikes {
+                    let left = -hw + spike_w * i as N;
+                    renderable = renderable.with(RenderItem::polygon(
+                        0.0,
+                        0.0,
+                        vec![[left, hh], [left + spike_w / 2.0, -hh], [left + spike_w, hh]],
+                        0.0,
+                        [0.4, 0.4, 0.45, 1.0],
+                    ));
+                }
+            }
+            HazardKind::Lava => {
+                renderable = renderable
+                    .with(RenderItem::rectangle(
+                        0.0,
+                        0.0,
+                        hw * 2.0,
+                        hh * 2.0,
+                        0.0,
+                        [0.9, 0.25, 0.0, 1.0],
+                    ))
+                    .with(RenderItem::rectangle(
+                        0.0,
+                        -hh + hh.min(0.1) / 2.0,
+                        hw * 2.0,
+                        hh.min(0.1),
+                        0.0,
+                        [1.0, 0.7, 0.1, 1.0],
+                    ));
+            }
+        }
+
+        let entity = self.new_static_collider(rect);
+        self.specs_world
+            .write::<Renderable>()
+            .insert(entity, renderable);
+        self.specs_world
+            .write::<Hazard>()
+            .insert(entity, Hazard::new(kind, damage, 0.5));
+        if kind == HazardKind::Lava {
+            self.specs_world
+                .write::<Light>()
+                .insert(entity, Light::new(hw.max(hh) + 2.0, 0.8, [1.0, 0.45, 0.1]));
+        }
+
+        entity
+    }
+
+    /// Adds a tile layer, covering its tiles with static colliders unless it is decorative.
+    pub fn add_tile_layer(&mut self, layer: TileLayer) {
+        if !layer.decorative {
+            for rect in layer.collision_rects() {
+                self.new_static_collider(rect);
+            }
+        }
+
+        self.specs_world.write_resource::<TileLayers>().0.push(layer);
+    }
+
+    pub fn effect_particles(&self) -> specs::Fetch<EffectParticles> {
+        self.specs_world.read_resource::<EffectParticles>()
+    }
+
+    pub fn tile_layers(&self) -> specs::Fetch<TileLayers> {
+        self.specs_world.read_resource::<TileLayers>()
+    }
+
+    // An invisible piece of static ground.
+    fn new_static_collider(&mut self, rect: Rect) -> Entity {
+        let Rect { x, y, hw, hh, rotation } = rect;
         let shape = Cuboid::new(Vector::new(hw - BODY_MARGIN, hh - BODY_MARGIN));
         let id = self.new_rigid_body_id();
 
-        let renderable = Renderable::new(x, y, 0.0).with(RenderItem::rectangle(
-            0.0,
-            0.0,
-            hw * 2.0,
-            hh * 2.0,
-            0.0,
-            [0.0, 1.0, 0.0, 1.0],
-        ));
-
-        let entity = self.specs_world
-            .create_entity()
-            .with(id)
-            .with(renderable)
-            .build();
+        let entity = self.specs_world.create_entity().with(id).build();
 
         let message = MessageToPhysicsThread::AddRigidBody {
             id,
@@ -293,68 +995,193 @@ impl World {
             restitution: 0.2,
             friction: 0.3,
             translation: Vector::new(x, y),
-            collision_groups_kind: CollisionGroupsKind::GenericStatic,
+            rotation,
+            collision_groups: CollisionGroupsKind::GenericStatic.into(),
             ccd: None,
+            kinematic: false,
         };
-        self.physics_thread_link.lock().unwrap().send.send(message);
+        self.add_rigid_body(message);
 
         entity
     }
 
-    pub fn new_particle(&mut self, rect: Rect, velocity: Vector<N>, ttl: N) -> Entity {
-        let Rect { x, y, hw, hh } = rect;
-        let shape = Cuboid::new(Vector::new(hw - BODY_MARGIN, hh - BODY_MARGIN));
+    /// Static ground with an arbitrary outline, given as points in world coordinates.
+    /// With three or more points the outline is closed.
+    pub fn new_terrain(&mut self, points: &[Vector<N>]) -> Entity {
+        assert!(points.len() >= 2, "terrain needs at least two points");
+
+        let centre = points.iter().fold(Vector::zero(), |sum, &p| sum + p) / points.len() as N;
+        let local_points: Vec<Vector<N>> = points.iter().map(|&p| p - centre).collect();
+
+        let num_segments = if points.len() == 2 { 1 } else { points.len() };
+        let indices = (0..num_segments)
+            .map(|i| Point2::new(i, (i + 1) % points.len()))
+            .collect();
+        let vertices = local_points
+            .iter()
+            .map(|&p| Point::from_coordinates(p))
+            .collect();
+        let shape = Polyline::new(Arc::new(vertices), Arc::new(indices), None, None);
         let id = self.new_rigid_body_id();
 
-        let renderable = Renderable::new(x, y, 0.0).with(RenderItem::rectangle(
-            0.0,
-            0.0,
-            hw * 2.0,
-            hh * 2.0,
-            0.0,
-            [1.0, 0.0, 0.0, 1.0],
-        ));
+        let renderable = Renderable::new(centre.x, centre.y, 0.0)
+            .with_layer(LAYER_GROUND)
+            .with(RenderItem::polygon(
+                0.0,
+                0.0,
+                local_points.iter().map(|p| [p.x, p.y]).collect(),
+                0.0,
+                [0.0, 1.0, 0.0, 1.0],
+            ));
 
         let entity = self.specs_world
             .create_entity()
             .with(id)
             .with(renderable)
-            .with(TimedRemove(ttl))
-            .with(TimeStopStore::new())
             .build();
 
         let message = MessageToPhysicsThread::AddRigidBody {
             id,
             entity,
             shape: ShapeHandle::new(shape),
-            mass_properties: Some((1400.0, Point::new(0.0, 0.0), AngularInertia::new(1.0))),
-            restitution: 0.0,
-            friction: 0.5,
-            translation: Vector::new(x, y),
-            collision_groups_kind: CollisionGroupsKind::Particle,
+            mass_properties: None,
+            restitution: 0.2,
+            friction: 0.3,
+            translation: centre,
+            rotation: 0.0,
+            collision_groups: CollisionGroupsKind::GenericStatic.into(),
             ccd: None,
+            kinematic: false,
         };
-        self.physics_thread_link.lock().unwrap().send.send(message);
-        self.physics_thread_link
-            .lock()
-            .unwrap()
-            .set_lin_vel(id, velocity);
+        self.add_rigid_body(message);
 
         entity
     }
 
-    // Make sure to set world.player to the returned entity!
-    fn new_player(&mut self, x: N, y: N) -> Entity {
-        let hw = PLAYER_HALF_WIDTH;
-        let hh = PLAYER_HALF_HEIGHT;
+    /// Static ground of any shape, such as a convex polygon or a compound of several pieces.
+    pub fn new_shaped_ground(&mut self, pos: Vector<N>, rotation: N, shape: &ShapeDesc) -> Entity {
+        let id = self.new_rigid_body_id();
 
+        let mut renderable = Renderable::new(pos.x, pos.y, rotation).with_layer(LAYER_GROUND);
+        for item in shape.render_items([0.0, 1.0, 0.0, 1.0]) {
+            renderable = renderable.with(item);
+        }
+
+        let entity = self.specs_world
+            .create_entity()
+            .with(id)
+            .with(renderable)
+            .build();
+
+        let message = MessageToPhysicsThread::AddRigidBody {
+            id,
+            entity,
+            shape: shape.to_shape_handle(),
+            mass_properties: None,
+            restitution: 0.2,
+            friction: 0.3,
+            translation: pos,
+            rotation,
+            collision_groups: CollisionGroupsKind::GenericStatic.into(),
+            ccd: None,
+            kinematic: false,
+        };
+        self.add_rigid_body(message);
+
+        entity
+    }
+
+    pub fn new_particle(&mut self, rect: Rect, velocity: Vector<N>, ttl: N, color: [f32; 4]) -> Entity {
+        let Rect { x, y, hw, hh, rotation } = rect;
         let shape = Cuboid::new(Vector::new(hw - BODY_MARGIN, hh - BODY_MARGIN));
         let id = self.new_rigid_body_id();
+
+        let renderable = Renderable::new(x, y, rotation)
+            .with_layer(LAYER_PARTICLES)
+            .with(RenderItem::rectangle(
+                0.0,
+                0.0,
+                hw * 2.0,
+                hh * 2.0,
+                0.0,
+                color,
+            ));
+
+        let entity = self.specs_world
+            .create_entity()
+            .with(id)
+            .with(renderable)
+            .with(TimedRemove(ttl))
+            .with(TimeStopStore::new())
+            .with(Particle)
+            .build();
+
+        let message = MessageToPhysicsThread::AddRigidBody {
+            id,
+            entity,
+            shape: ShapeHandle::new(shape),
+            mass_properties: Some((1400.0, Point::new(0.0, 0.0), AngularInertia::new(1.0))),
+            restitution: 0.0,
+            friction: 0.5,
+            translation: Vector::new(x, y),
+            rotation,
+            collision_groups: CollisionGroupsKind::Particle.into(),
+            ccd: None,
+            kinematic: false,
+        };
+        self.add_rigid_body(message);
+        self.physics_thread_link
+            .lock()
+            .unwrap()
+            .set_lin_vel(id, velocity);
+
+        entity
+    }
+
+    // Attaches a thin sensor just below a body with the given half extents, so we can tell when it's on the ground.
+    // The body must already have been sent to the physics thread.
+    fn add_ground_sensor(&mut self, entity: Entity, body_id: RigidBodyID, hw: N, hh: N) {
         let sensor_id = self.new_sensor_id();
 
+        let sensor_height = 0.03;
+        let sensor_shape = Cuboid::new(Vector::new(hw * 0.90, sensor_height));
+        let rel_pos = Isometry::from_parts(
+            Translation::from_vector(Vector::new(0.0, hh + sensor_height)),
+            Rotation::from_angle(0.0),
+        );
+
+        self.physics_thread_link.lock().unwrap().add_sensor(
+            sensor_id,
+            ShapeHandle::new(sensor_shape),
+            Some(body_id),
+            Some(rel_pos),
+            true,
+        );
+
+        self.specs_world.write::<SensorID>().insert(entity, sensor_id);
+        self.specs_world
+            .write::<GroundSensor>()
+            .insert(entity, GroundSensor::new(sensor_id));
+    }
+
+    fn init_player(&mut self, x: N, y: N) {
+        let hw = PLAYER_HALF_WIDTH;
+        let hh = PLAYER_HALF_HEIGHT;
+
+        // rounded ends so the player slides over tile seams and crate corners instead of
+        // snagging on them; it's still drawn as a rectangle
+        let radius = hw - BODY_MARGIN;
+        let shape = Capsule::new(hh - hw, radius);
+        let id = self.new_rigid_body_id();
+
         let density = 500.0;
 
-        let player = Player::new(sensor_id, 6);
+        let player = Player::new();
+        let inventory = Inventory::new(vec![
+            WeaponSlot::limited(Weapon::Knife, 6),
+            WeaponSlot::limited(Weapon::Grenade, 3),
+            WeaponSlot::unlimited(Weapon::Melee),
+        ]);
 
         let renderable = Renderable::new(x, y, 0.0)
             .with(RenderItem::rectangle(
@@ -367,14 +1194,25 @@ impl World {
             ))
             .with(RenderItem::info(0.0, -hh * 1.3, 0.0, [0.0, 0.0, 0.0, 1.0]));
 
-        let entity = self.specs_world
-            .create_entity()
-            .with(id)
-            .with(renderable)
-            .with(player)
-            .with(Hitpoints::new(5))
-            .with(Name("Player".into()))
-            .build();
+        let entity = self.player;
+        self.specs_world.write::<RigidBodyID>().insert(entity, id);
+        self.specs_world.write::<Renderable>().insert(entity, renderable);
+        self.specs_world.write::<Player>().insert(entity, player);
+        self.specs_world.write::<Team>().insert(entity, Team::Player);
+        self.specs_world.write::<Facing>().insert(entity, Facing::default());
+        self.specs_world.write::<Inventory>().insert(entity, inventory);
+        self.specs_world
+            .write::<VelocityLimit>()
+            .insert(entity, VelocityLimit::new(MAX_LIN_VEL, MAX_ANG_VEL));
+        self.specs_world
+            .write::<Hitpoints>()
+            .insert(entity, Hitpoints::new(5));
+        self.specs_world
+            .write::<Name>()
+            .insert(entity, Name("Player".into()));
+        self.specs_world
+            .write::<Light>()
+            .insert(entity, Light::new(4.0, 0.7, [1.0, 0.95, 0.8]));
 
         let message = MessageToPhysicsThread::AddRigidBody {
             id,
@@ -388,38 +1226,22 @@ impl World {
             restitution: 0.2,
             friction: 0.1,
             translation: Vector::new(x, y),
-            collision_groups_kind: CollisionGroupsKind::Player,
+            rotation: 0.0,
+            collision_groups: CollisionGroupsKind::Player.into(),
             ccd: None,
+            kinematic: false,
         };
 
-
-        let sensor_height = 0.03;
-        let sensor_shape = Cuboid::new(Vector::new(hw * 0.90, sensor_height));
-        let rel_pos = Isometry::from_parts(
-            Translation::from_vector(Vector::new(0.0, hh + sensor_height)),
-            Rotation::from_angle(0.0),
-        );
-
-        {
-            let physics = self.physics_thread_link.lock().unwrap();
-            physics.send.send(message);
-            physics.add_sensor(
-                sensor_id,
-                ShapeHandle::new(sensor_shape),
-                Some(id),
-                Some(rel_pos),
-            );
-        }
-
-        entity
+        self.add_rigid_body(message);
+        self.add_ground_sensor(entity, id, hw, hh);
     }
 
     pub fn new_crate(&mut self, rect: Rect, material: CrateMaterial) -> Entity {
-        let Rect { x, y, hw, hh } = rect;
+        let Rect { x, y, hw, hh, rotation } = rect;
         let shape = Cuboid::new(Vector::new(hw - BODY_MARGIN, hh - BODY_MARGIN));
         let id = self.new_rigid_body_id();
 
-        let renderable = Renderable::new(x, y, 0.0)
+        let renderable = Renderable::new(x, y, rotation)
             .with(RenderItem::rectangle(
                 0.0,
                 0.0,
@@ -442,8 +1264,15 @@ impl World {
             .with(id)
             .with(renderable)
             .with(TimeStopStore::new())
+            .with(VelocityLimit::new(MAX_LIN_VEL, MAX_ANG_VEL))
             .build();
 
+        if hw <= MAX_CARRYABLE_HALF_EXTENT && hh <= MAX_CARRYABLE_HALF_EXTENT {
+            self.specs_world
+                .write::<Carryable>()
+                .insert(entity, Carryable);
+        }
+
         let message = MessageToPhysicsThread::AddRigidBody {
             id,
             entity,
@@ -452,23 +1281,461 @@ impl World {
             restitution: material.restitution(),
             friction: 0.6,
             translation: Vector::new(x, y),
-            collision_groups_kind: CollisionGroupsKind::GenericDynamic,
+            rotation,
+            collision_groups: CollisionGroupsKind::GenericDynamic.into(),
             ccd: None,
+            kinematic: false,
         };
 
-        self.physics_thread_link.lock().unwrap().send.send(message);
+        self.add_rigid_body(message);
 
         entity
     }
 
+    /// A crate which breaks apart once its hitpoints run out, dropping its contents.
+    pub fn new_destructible_crate(&mut self, rect: Rect, material: CrateMaterial, hitpoints: u16, contents: Vec<Loot>) -> Entity {
+        let entity = self.new_crate(rect, material);
+
+        self.specs_world
+            .write::<Hitpoints>()
+            .insert(entity, Hitpoints::new(hitpoints));
+        self.specs_world
+            .write::<Crate>()
+            .insert(entity, Crate { material, contents });
+
+        entity
+    }
+
+    /// Spawns a kinematic platform which travels from its initial position through the waypoints.
+    pub fn new_moving_platform(&mut self, rect: Rect, waypoints: &[Vector<N>], speed: N, loop_mode: LoopMode) -> Entity {
+        let Rect { x, y, hw, hh, rotation } = rect;
+        let shape = Cuboid::new(Vector::new(hw - BODY_MARGIN, hh - BODY_MARGIN));
+        let id = self.new_rigid_body_id();
+
+        let renderable = Renderable::new(x, y, rotation).with(RenderItem::rectangle(
+            0.0,
+            0.0,
+            hw * 2.0,
+            hh * 2.0,
+            0.0,
+            [0.0, 0.6, 0.3, 1.0],
+        ));
+
+        let mut path = vec![Vector::new(x, y)];
+        path.extend_from_slice(waypoints);
+
+        let entity = self.specs_world
+            .create_entity()
+            .with(id)
+            .with(renderable)
+            .with(MovingPlatform::new(path, speed, loop_mode))
+            .build();
+
+        let message = MessageToPhysicsThread::AddRigidBody {
+            id,
+            entity,
+            mass_properties: Some(shape.mass_properties(1000.0)),
+            shape: ShapeHandle::new(shape),
+            restitution: 0.0,
+            friction: 0.8,
+            translation: Vector::new(x, y),
+            rotation,
+            collision_groups: CollisionGroupsKind::GenericStatic.into(),
+            ccd: None,
+            kinematic: true,
+        };
+
+        self.add_rigid_body(message);
+
+        entity
+    }
+
+    // Creates a free-standing sensor covering rect, for detecting bodies inside an area.
+    fn new_area_sensor(&mut self, rect: Rect) -> SensorID {
+        let Rect { x, y, hw, hh, rotation } = rect;
+        let sensor_id = self.new_sensor_id();
+
+        self.physics_thread_link.lock().unwrap().add_sensor(
+            sensor_id,
+            ShapeHandle::new(Cuboid::new(Vector::new(hw, hh))),
+            None,
+            Some(Isometry::new(Vector::new(x, y), rotation)),
+            false,
+        );
+
+        sensor_id
+    }
+
+    pub fn new_gravity_zone(&mut self, rect: Rect, gravity: Vector<N>) -> Entity {
+        let Rect { x, y, hw, hh, rotation } = rect;
+        let sensor_id = self.new_area_sensor(rect);
+
+        let renderable = Renderable::new(x, y, rotation).with(RenderItem::rectangle(
+            0.0,
+            0.0,
+            hw * 2.0,
+            hh * 2.0,
+            0.0,
+            [0.5, 0.5, 1.0, 0.2],
+        ));
+
+        self.specs_world
+            .create_entity()
+            .with(renderable)
+            .with(sensor_id)
+            .with(GravityZone { sensor_id, gravity })
+            .build()
+    }
+
+    pub fn new_goal(&mut self, rect: Rect) -> Entity {
+        let Rect { x, y, hw, hh, rotation } = rect;
+        let sensor_id = self.new_area_sensor(rect);
+
+        let renderable = Renderable::new(x, y, rotation).with(RenderItem::rectangle(
+            0.0,
+            0.0,
+            hw * 2.0,
+            hh * 2.0,
+            0.0,
+            [0.2, 0.9, 0.3, 0.4],
+        ));
+
+        self.specs_world
+            .create_entity()
+            .with(renderable)
+            .with(sensor_id)
+            .with(Goal {
+                sensor_id,
+                reached: false,
+            })
+            .build()
+    }
+
+    /// Text floating in the world, which fades in and out and is then removed.
+    pub fn new_floating_text(&mut self, pos: Vector<N>, text: &str, size: u32, color: [f32; 4], fade: TextFade) -> Entity {
+        let renderable = Renderable::new(pos.x, pos.y, 0.0).with(
+            RenderItem::text(0.0, 0.0, 0.0, color, text, size).with_text_style(TextStyle::bold().with_shadow([0.0, 0.0, 0.0, 0.5])),
+        );
+
+        self.specs_world
+            .create_entity()
+            .with(renderable)
+            .with(fade.removing())
+            .build()
+    }
+
+    /// A light on its own, like a torch on the wall.
+    pub fn new_light(&mut self, pos: Vector<N>, light: Light) -> Entity {
+        self.specs_world
+            .create_entity()
+            .with(Renderable::new(pos.x, pos.y, 0.0))
+            .with(light)
+            .build()
+    }
+
+    pub fn new_coin(&mut self, pos: Vector<N>, value: u32) -> Entity {
+        let radius = 0.2;
+        let sensor_id = self.new_area_sensor(Rect::new(pos.x, pos.y, radius, radius));
+
+        let renderable = Renderable::new(pos.x, pos.y, 0.0)
+            .with(RenderItem::ellipse(
+                0.0,
+                0.0,
+                radius * 2.0,
+                radius * 2.0,
+                0.0,
+                [0.9, 0.7, 0.0, 1.0],
+            ))
+            .with(RenderItem::ellipse(
+                0.0,
+                0.0,
+                radius * 1.4,
+                radius * 1.4,
+                0.0,
+                [1.0, 0.85, 0.2, 1.0],
+            ));
+
+        self.specs_world
+            .create_entity()
+            .with(renderable)
+            .with(sensor_id)
+            .with(Coin { sensor_id, value })
+            .build()
+    }
+
+    /// A pickup which gives the player something when they touch it.
+    /// With `respawn_time` it comes back that many seconds after being collected.
+    pub fn new_pickup(&mut self, pos: Vector<N>, kind: PickupKind, respawn_time: Option<N>) -> Entity {
+        let (hw, hh) = (0.2, 0.2);
+        let sensor_id = self.new_area_sensor(Rect::new(pos.x, pos.y, hw, hh));
+
+        let renderable = match kind {
+            PickupKind::Health(_) => Renderable::new(pos.x, pos.y, 0.0)
+                .with(RenderItem::rectangle(
+                    0.0,
+                    0.0,
+                    hw * 2.0,
+                    hh * 2.0,
+                    0.0,
+                    [1.0, 1.0, 1.0, 1.0],
+                ))
+                .with(RenderItem::rectangle(
+                    0.0,
+                    0.0,
+                    hw * 1.6,
+                    hh * 0.5,
+                    0.0,
+                    [0.9, 0.1, 0.1, 1.0],
+                ))
+                .with(RenderItem::rectangle(
+                    0.0,
+                    0.0,
+                    hw * 0.5,
+                    hh * 1.6,
+                    0.0,
+                    [0.9, 0.1, 0.1, 1.0],
+                )),
+            PickupKind::Knives(_) => Renderable::new(pos.x, pos.y, 0.0)
+                .with(RenderItem::rectangle(
+                    0.0,
+                    -hh * 0.4,
+                    hw * 1.8,
+                    hh * 0.4,
+                    0.0,
+                    [0.3, 0.3, 0.3, 1.0],
+                ))
+                .with(RenderItem::rectangle(
+                    0.0,
+                    hh * 0.4,
+                    hw * 1.8,
+                    hh * 0.4,
+                    0.0,
+                    [0.3, 0.3, 0.3, 1.0],
+                )),
+            PickupKind::Grenades(_) => Renderable::new(pos.x, pos.y, 0.0).with(RenderItem::ellipse(
+                0.0,
+                0.0,
+                hw * 1.6,
+                hh * 1.6,
+                0.0,
+                [0.2, 0.35, 0.15, 1.0],
+            )),
+            PickupKind::TimeStopEnergy(_) => Renderable::new(pos.x, pos.y, 0.0)
+                .with(RenderItem::ellipse(
+                    0.0,
+                    0.0,
+                    hw * 2.0,
+                    hh * 2.0,
+                    0.0,
+                    [0.5, 0.3, 0.9, 1.0],
+                ))
+                .with(RenderItem::ellipse(
+                    0.0,
+                    0.0,
+                    hw,
+                    hh,
+                    0.0,
+                    [0.8, 0.7, 1.0, 1.0],
+                )),
+            PickupKind::Serrated => Renderable::new(pos.x, pos.y, 0.0)
+                .with(RenderItem::rectangle(
+                    0.0,
+                    0.0,
+                    hw * 2.0,
+                    hh * 2.0,
+                    0.0,
+                    [0.7, 0.1, 0.1, 1.0],
+                ))
+                .with(RenderItem::rectangle(
+                    0.0,
+                    0.0,
+                    hw * 1.6,
+                    hh * 0.6,
+                    0.0,
+                    [0.3, 0.3, 0.3, 1.0],
+                )),
+            PickupKind::Ricochet(_) => Renderable::new(pos.x, pos.y, 0.0)
+                .with(RenderItem::rectangle(
+                    0.0,
+                    0.0,
+                    hw * 2.0,
+                    hh * 2.0,
+                    0.0,
+                    [0.2, 0.6, 0.9, 1.0],
+                ))
+                .with(RenderItem::rectangle(
+                    0.0,
+                    0.0,
+                    hw * 1.6,
+                    hh * 0.6,
+                    0.785,
+                    [0.3, 0.3, 0.3, 1.0],
+                )),
+        };
+
+        self.specs_world
+            .create_entity()
+            .with(renderable)
+            .with(sensor_id)
+            .with(Pickup::new(sensor_id, kind, pos, respawn_time))
+            .build()
+    }
+
+    pub fn new_door(&mut self, rect: Rect, id: LinkId, open: bool) -> Entity {
+        let Rect { x, y, hw, hh, rotation } = rect;
+
+        let renderable = Renderable::new(x, y, rotation).with(RenderItem::rectangle(
+            0.0,
+            0.0,
+            hw * 2.0,
+            hh * 2.0,
+            0.0,
+            [0.45, 0.3, 0.15, 1.0],
+        ));
+
+        let entity = self.new_static_collider(rect);
+        self.specs_world
+            .write::<Renderable>()
+            .insert(entity, renderable);
+        // start closed, so that DoorSystem does the opening
+        self.specs_world
+            .write::<Door>()
+            .insert(entity, Door { id: id.clone(), open: false });
+
+        if open {
+            self.specs_world
+                .write_resource::<LinkSignals>()
+                .send(&id, Signal::Open);
+        }
+
+        entity
+    }
+
+    pub fn new_switch(&mut self, rect: Rect, target: LinkId) -> Entity {
+        let Rect { x, y, hw, hh, rotation } = rect;
+        let sensor_id = self.new_area_sensor(rect);
+
+        let renderable = Renderable::new(x, y, rotation).with(RenderItem::rectangle(
+            0.0,
+            0.0,
+            hw * 2.0,
+            hh * 2.0,
+            0.0,
+            [0.8, 0.1, 0.1, 1.0],
+        ));
+
+        self.specs_world
+            .create_entity()
+            .with(renderable)
+            .with(sensor_id)
+            .with(Switch {
+                sensor_id,
+                target,
+                pressed: false,
+            })
+            .build()
+    }
+
+    /// Spawns enemies of the given kind at `rect` every `interval` seconds, while fewer than `max_alive` of them are alive.
+    /// With a `trigger` area, it only starts once the player enters that.
+    pub fn new_spawner(&mut self, rect: Rect, kind: EnemyKind, interval: N, max_alive: usize, total: Option<u32>, trigger: Option<Rect>) -> Entity {
+        let Rect { x, y, hw, hh, rotation } = rect;
+        let trigger_sensor = trigger.map(|trigger| self.new_area_sensor(trigger));
+
+        let renderable = Renderable::new(x, y, rotation).with(RenderItem::rectangle(
+            0.0,
+            0.0,
+            hw * 2.0,
+            hh * 2.0,
+            0.0,
+            [0.6, 0.2, 0.8, 0.25],
+        ));
+
+        let mut builder = self.specs_world
+            .create_entity()
+            .with(renderable)
+            .with(Spawner::new(rect, kind, interval, max_alive, total, trigger_sensor));
+        if let Some(sensor_id) = trigger_sensor {
+            builder = builder.with(sensor_id);
+        }
+
+        builder.build()
+    }
+
+    pub fn new_trigger(&mut self, rect: Rect, actions: Vec<Action>, repeat: bool) -> Entity {
+        let sensor_id = self.new_area_sensor(rect);
+
+        self.specs_world
+            .create_entity()
+            .with(sensor_id)
+            .with(Trigger {
+                sensor_id,
+                actions,
+                repeat,
+                fired: false,
+            })
+            .build()
+    }
+
+    pub fn new_key(&mut self, pos: Vector<N>, target: LinkId) -> Entity {
+        let (hw, hh) = (0.15, 0.25);
+        let sensor_id = self.new_area_sensor(Rect::new(pos.x, pos.y, hw, hh));
+
+        let renderable = Renderable::new(pos.x, pos.y, 0.0)
+            .with(RenderItem::ellipse(
+                0.0,
+                -hh + hw,
+                hw * 2.0,
+                hw * 2.0,
+                0.0,
+                [0.9, 0.75, 0.1, 1.0],
+            ))
+            .with(RenderItem::rectangle(
+                0.0,
+                hw / 2.0,
+                hw / 2.0,
+                hh * 2.0 - hw,
+                0.0,
+                [0.9, 0.75, 0.1, 1.0],
+            ));
+
+        self.specs_world
+            .create_entity()
+            .with(renderable)
+            .with(sensor_id)
+            .with(Key { sensor_id, target })
+            .build()
+    }
+
+    pub fn new_force_field(&mut self, rect: Rect, force: Vector<N>) -> Entity {
+        let Rect { x, y, hw, hh, rotation } = rect;
+        let sensor_id = self.new_area_sensor(rect);
+
+        let renderable = Renderable::new(x, y, rotation).with(RenderItem::rectangle(
+            0.0,
+            0.0,
+            hw * 2.0,
+            hh * 2.0,
+            0.0,
+            [0.7, 0.9, 0.7, 0.2],
+        ));
+
+        self.specs_world
+            .create_entity()
+            .with(renderable)
+            .with(sensor_id)
+            .with(ForceField { sensor_id, force })
+            .build()
+    }
+
     pub fn new_enemy(&mut self, rect: Rect) -> Entity {
-        let Rect { x, y, hw, hh } = rect;
+        let Rect { x, y, hw, hh, rotation } = rect;
         let shape = Cuboid::new(Vector::new(hw - BODY_MARGIN, hh - BODY_MARGIN));
         let id = self.new_rigid_body_id();
 
         let density = 1000.0;
 
-        let renderable = Renderable::new(x, y, 0.0)
+        let renderable = Renderable::new(x, y, rotation)
             .with(RenderItem::rectangle(
                 0.0,
                 0.0,
@@ -486,6 +1753,10 @@ impl World {
             .with(TimeStopStore::new())
             .with(Hitpoints::new(5))
             .with(BasicEnemy::new())
+            .with(Team::Enemy)
+            .with(Facing::default())
+            .with(VelocityLimit::new(MAX_LIN_VEL, MAX_ANG_VEL))
+            .with(ContactDamage::new(1, 1.0))
             .build();
 
         let message = MessageToPhysicsThread::AddRigidBody {
@@ -496,11 +1767,176 @@ impl World {
             restitution: 0.2,
             friction: 0.3,
             translation: Vector::new(x, y),
-            collision_groups_kind: CollisionGroupsKind::GenericDynamic,
+            rotation,
+            collision_groups: CollisionGroupsKind::GenericDynamic.into(),
             ccd: None,
+            kinematic: false,
         };
 
-        self.physics_thread_link.lock().unwrap().send.send(message);
+        self.add_rigid_body(message);
+        self.add_ground_sensor(entity, id, hw, hh);
+
+        entity
+    }
+
+    /// An enemy set up from level data.
+    pub fn new_configured_enemy(&mut self, rect: Rect, config: EnemyConfig) -> Entity {
+        let entity = match config.kind {
+            EnemyKind::Melee => self.new_enemy(rect),
+            EnemyKind::Ranged => self.new_ranged_enemy(rect),
+            EnemyKind::Flying => self.new_flying_enemy(rect),
+        };
+
+        if let Some(hitpoints) = config.hitpoints {
+            self.specs_world
+                .write::<Hitpoints>()
+                .insert(entity, Hitpoints::new(hitpoints));
+        }
+
+        self.specs_world
+            .write::<Facing>()
+            .insert(entity, config.facing);
+
+        if !config.patrol.is_empty() {
+            let mut path = vec![Vector::new(rect.x, rect.y)];
+            path.extend(config.patrol);
+            self.specs_world
+                .write::<Patrol>()
+                .insert(entity, Patrol::new(path, ENEMY_PATROL_SPEED));
+        }
+
+        if let Some(radius) = config.aggro_radius {
+            self.specs_world
+                .write::<Aggro>()
+                .insert(entity, Aggro::new(radius));
+        }
+
+        entity
+    }
+
+    pub fn new_boss(&mut self, rect: Rect, name: String, hitpoints: u16) -> Entity {
+        let entity = self.new_enemy(rect);
+
+        self.specs_world
+            .write::<Hitpoints>()
+            .insert(entity, Hitpoints::new(hitpoints));
+        self.specs_world
+            .write::<ContactDamage>()
+            .insert(entity, ContactDamage::new(2, 1.0));
+        self.specs_world
+            .write::<Boss>()
+            .insert(entity, Boss::new(name, rect.hh));
+        for item in &mut self.specs_world
+            .write::<Renderable>()
+            .get_mut(entity)
+            .unwrap()
+            .items
+        {
+            if let RenderItemKind::Rectangle { .. } = item.kind {
+                item.color = [0.6, 0.0, 0.0, 1.0];
+            }
+        }
+
+        entity
+    }
+
+    /// Draws an entity with artwork instead of coloured boxes, by replacing each rectangle it is
+    /// drawn with by the image at `image` in the media directory.
+    pub fn set_sprite(&mut self, entity: Entity, image: &str) {
+        let mut renderablec = self.specs_world.write::<Renderable>();
+        let renderable = match renderablec.get_mut(entity) {
+            Some(renderable) => renderable,
+            None => return,
+        };
+
+        for item in &mut renderable.items {
+            if let RenderItemKind::Rectangle { w, h } = item.kind {
+                *item = RenderItem::sprite(item.rel_x, item.rel_y, image, w, h, false, item.rel_rotation, [1.0; 4])
+                    .on_layer(item.layer);
+            }
+        }
+    }
+
+    /// Animates the entity's sprites from a sprite sheet. See `set_sprite`.
+    pub fn set_animation(&mut self, entity: Entity, animation: Animation) {
+        self.specs_world.write::<Animation>().insert(entity, animation);
+    }
+
+    pub fn set_player_animation(&mut self, animation: Animation) {
+        let player = self.player;
+        self.set_animation(player, animation);
+    }
+
+    pub fn set_layer(&mut self, entity: Entity, layer: i32) {
+        if let Some(renderable) = self.specs_world.write::<Renderable>().get_mut(entity) {
+            renderable.layer = layer;
+        }
+    }
+
+    /// Replaces the player's glow, or takes it away.
+    pub fn set_player_light(&mut self, light: Option<Light>) {
+        let player = self.player;
+        let mut lightc = self.specs_world.write::<Light>();
+        match light {
+            Some(light) => {
+                lightc.insert(player, light);
+            }
+            None => {
+                lightc.remove(player);
+            }
+        }
+    }
+
+    pub fn set_player_sprite(&mut self, image: &str) {
+        let player = self.player;
+        self.set_sprite(player, image);
+    }
+
+    /// The name and hitpoints of the first living boss, for the HUD.
+    pub fn boss_health(&self) -> Option<(String, u16, u16)> {
+        let bossc = self.read_component::<Boss>();
+        let hitpointsc = self.read_component::<Hitpoints>();
+
+        (&bossc, &hitpointsc)
+            .join()
+            .find(|&(_, hitpoints)| hitpoints.current() > 0)
+            .map(|(boss, hitpoints)| (boss.name.clone(), hitpoints.current(), hitpoints.max()))
+    }
+
+    /// An enemy which flies above the player and dives at them.
+    pub fn new_flying_enemy(&mut self, rect: Rect) -> Entity {
+        let entity = self.new_enemy(rect);
+
+        self.specs_world
+            .write::<GravityScale>()
+            .insert(entity, GravityScale(0.0));
+        self.specs_world
+            .write::<Flyer>()
+            .insert(entity, Flyer::new(4.0, 3.0, 9.0, 3.0));
+        for item in &mut self.specs_world
+            .write::<Renderable>()
+            .get_mut(entity)
+            .unwrap()
+            .items
+        {
+            if let RenderItemKind::Rectangle { .. } = item.kind {
+                item.color = [0.5, 0.0, 0.8, 1.0];
+            }
+        }
+
+        entity
+    }
+
+    /// An enemy that also shoots at the player.
+    pub fn new_ranged_enemy(&mut self, rect: Rect) -> Entity {
+        let entity = self.new_enemy(rect);
+
+        let bullet_radius = 0.08;
+        let muzzle_distance = (rect.hw * rect.hw + rect.hh * rect.hh).sqrt() + bullet_radius + 0.05;
+        self.specs_world.write::<RangedAttacker>().insert(
+            entity,
+            RangedAttacker::new(2.0, 12.0, 15.0, bullet_radius, muzzle_distance),
+        );
 
         entity
     }
@@ -536,11 +1972,13 @@ impl World {
             restitution: 0.2,
             friction: 0.1,
             translation: pos,
-            collision_groups_kind: CollisionGroupsKind::GenericDynamic,
-            ccd: Some(0.04),
+            rotation: 0.0,
+            collision_groups: CollisionGroupsKind::Projectile.into(),
+            ccd: Some(PROJECTILE_CCD_THRESHOLD),
+            kinematic: false,
         };
 
-        self.physics_thread_link.lock().unwrap().send.send(message);
+        self.add_rigid_body(message);
         self.physics_thread_link
             .lock()
             .unwrap()
@@ -550,20 +1988,213 @@ impl World {
     }
 
     pub fn player_throw_knife(&mut self, x: N, y: N, velocity: Vector<N>) -> Option<Entity> {
+        if !self.specs_world
+            .write::<Inventory>()
+            .get_mut(self.player)
+            .map_or(false, |inventory| inventory.take(Weapon::Knife))
         {
-            let mut playerc = self.specs_world.write::<Player>();
-            let player = playerc.get_mut(self.player).unwrap();
-            if player.num_knives() > 0 {
-                player.dec_knives();
-            } else {
-                return None;
+            return None;
+        }
+        let (bounces, serrated) = {
+            let playerc = self.read_component::<Player>();
+            let player = playerc.get(self.player).unwrap();
+            (player.knife_bounces, player.serrated_knives)
+        };
+
+        let entity = self.new_knife(x, y, velocity, bounces);
+        if serrated {
+            self.specs_world.write::<Knife>().get_mut(entity).unwrap().effect =
+                Some(StatusEffect::new(StatusKind::Bleed { damage: 1 }, SERRATED_BLEED_TIME));
+        }
+        self.specs_world
+            .write_resource::<GameEvents>()
+            .push(GameEvent::KnifeThrown);
+
+        Some(entity)
+    }
+
+    /// Lobs one of the player's grenades towards (x, y).
+    pub fn player_throw_grenade(&mut self, x: N, y: N) -> Option<Entity> {
+        let player_pos = match self.physics_thread_link
+            .lock()
+            .unwrap()
+            .get_position(self.player_rigid_body_id())
+        {
+            Ok(pos) => pos.translation.vector,
+            Err(_) => return None,
+        };
+        let pos = player_pos - Vector::new(0.0, PLAYER_HALF_HEIGHT + 0.2);
+
+        let dir = Vector::new(x, y) - pos;
+        if dir.norm() == 0.0 {
+            return None;
+        }
+
+        // only spent once the throw is sure to happen
+        if !self.specs_world
+            .write::<Inventory>()
+            .get_mut(self.player)
+            .map_or(false, |inventory| inventory.take(Weapon::Grenade))
+        {
+            return None;
+        }
+
+        let velocity = dir.normalize() * GRENADE_THROW_SPEED - Vector::new(0.0, GRENADE_LOFT);
+
+        Some(self.new_grenade(pos, velocity))
+    }
+
+    pub fn new_grenade(&mut self, pos: Vector<N>, velocity: Vector<N>) -> Entity {
+        let radius = 0.12;
+        let shape = Ball::new(radius - BODY_MARGIN);
+        let id = self.new_rigid_body_id();
+
+        let density = 2000.0;
+
+        let renderable = Renderable::new(pos.x, pos.y, 0.0)
+            .with(RenderItem::ellipse(
+                0.0,
+                0.0,
+                radius * 2.0,
+                radius * 2.0,
+                0.0,
+                [0.2, 0.35, 0.15, 1.0],
+            ))
+            .with(RenderItem::rectangle(
+                0.0,
+                -radius,
+                radius * 0.6,
+                radius * 0.5,
+                0.0,
+                [0.3, 0.3, 0.3, 1.0],
+            ));
+
+        let entity = self.specs_world
+            .create_entity()
+            .with(id)
+            .with(renderable)
+            .with(TimeStopStore::new())
+            .with(TimedRemove(GRENADE_FUSE))
+            .with(VelocityLimit::new(MAX_LIN_VEL, MAX_ANG_VEL))
+            .with(Team::Player)
+            .with(Grenade {
+                radius: 3.0,
+                damage: 4,
+                // the player's grenades are concussive
+                effect: Some(StatusEffect::new(StatusKind::Stun, GRENADE_STUN_TIME)),
+            })
+            .build();
+
+        let message = MessageToPhysicsThread::AddRigidBody {
+            id,
+            entity,
+            mass_properties: Some(shape.mass_properties(density)),
+            shape: ShapeHandle::new(shape),
+            restitution: 0.4,
+            friction: 0.6,
+            translation: pos,
+            rotation: 0.0,
+            collision_groups: CollisionGroupsKind::Projectile.into(),
+            ccd: Some(PROJECTILE_CCD_THRESHOLD),
+            kinematic: false,
+        };
+
+        self.add_rigid_body(message);
+        self.physics_thread_link
+            .lock()
+            .unwrap()
+            .set_lin_vel(id, velocity);
+
+        entity
+    }
+
+    /// Hits whatever is within reach on the side of the player facing `target_x`.
+    pub fn player_melee(&mut self, target_x: N) {
+        let physics = self.physics_thread_link.lock().unwrap();
+        let player_pos = match physics.get_position(self.player_rigid_body_id()) {
+            Ok(pos) => pos.translation.vector,
+            Err(_) => return,
+        };
+
+        let side = if target_x < player_pos.x { -1.0 } else { 1.0 };
+        let centre = player_pos + Vector::new(side * (PLAYER_HALF_WIDTH + MELEE_REACH / 2.0), 0.0);
+        let half_extents = Vector::new(MELEE_REACH / 2.0, PLAYER_HALF_HEIGHT);
+        let centre = Point::from_coordinates(centre);
+
+        let hitpointsc = self.read_component::<Hitpoints>();
+        let mut damage_events = self.specs_world.write_resource::<DamageEvents>();
+
+        for user_data in physics.query_aabb(centre - half_extents, centre + half_extents) {
+            if user_data.entity == self.player {
+                continue;
+            }
+
+            let is_alive = hitpointsc
+                .get(user_data.entity)
+                .map_or(false, |hitpoints| hitpoints.current() > 0);
+            if !is_alive {
+                continue;
+            }
+
+            let position = match physics.get_position(user_data.rigid_body_id) {
+                Ok(pos) => Point::from_coordinates(pos.translation.vector),
+                Err(_) => centre,
+            };
+            let impulse = match physics.get_inv_mass(user_data.rigid_body_id) {
+                Ok(inv_mass) if inv_mass > 0.0 => Vector::new(side * MELEE_KNOCKBACK, -MELEE_KNOCKBACK * 0.5) / inv_mass,
+                _ => Vector::zero(),
+            };
+
+            damage_events.push(
+                DamageEvent::new(user_data.entity, MELEE_DAMAGE, position)
+                    .with_impulse(impulse)
+                    .with_source_team(Some(Team::Player)),
+            );
+        }
+    }
+
+    pub fn player_is_carrying(&self) -> bool {
+        self.read_component::<Player>()
+            .get(self.player)
+            .map_or(false, |player| player.carrying.is_some())
+    }
+
+    /// Throws whatever the player is carrying towards (x, y).
+    pub fn player_throw_carried(&mut self, x: N, y: N) {
+        let carried = match self.specs_world
+            .write::<Player>()
+            .get_mut(self.player)
+            .and_then(|player| player.carrying.take())
+        {
+            Some(carried) => carried,
+            None => return,
+        };
+
+        {
+            let physics = self.physics_thread_link.lock().unwrap();
+            drop_carried(&physics, &carried);
+
+            if let Ok(pos) = physics.get_position(carried.body_id) {
+                let offset = Vector::new(x, y) - pos.translation.vector;
+                if offset.norm() > 0.0 {
+                    physics.set_lin_vel(carried.body_id, offset.normalize() * THROW_SPEED);
+                }
             }
+
+            // ThrownSystem turns this off again once it lands
+            physics.enable_ccd(carried.body_id, Some(PROJECTILE_CCD_THRESHOLD));
         }
 
-        Some(self.new_knife(x, y, velocity))
+        self.specs_world.write::<Thrown>().insert(
+            carried.entity,
+            Thrown {
+                thrower: self.player,
+                last_velocity: Vector::zero(),
+            },
+        );
     }
 
-    pub fn new_knife(&mut self, x: N, y: N, velocity: Vector<N>) -> Entity {
+    pub fn new_knife(&mut self, x: N, y: N, velocity: Vector<N>, bounces: u8) -> Entity {
         let hw = 0.18;
         let hh = 0.08;
         let shape = Cuboid::new(Vector::new(hw - BODY_MARGIN, hh - BODY_MARGIN));
@@ -590,9 +2221,8 @@ impl World {
             .with(id)
             .with(renderable)
             .with(TimeStopStore::new())
-            .with(Knife {
-                stuck_into_entity: None,
-            })
+            .with(Knife::new(bounces))
+            .with(Team::Player)
             .build();
 
         let message = MessageToPhysicsThread::AddRigidBody {
@@ -600,15 +2230,22 @@ impl World {
             entity,
             mass_properties: Some(shape.mass_properties(density)),
             shape: ShapeHandle::new(shape),
-            restitution: 0.2,
+            restitution: if bounces > 0 {
+                RICOCHET_KNIFE_RESTITUTION
+            } else {
+                KNIFE_RESTITUTION
+            },
             friction: 0.1,
             translation: Vector::new(x, y),
-            collision_groups_kind: CollisionGroupsKind::Knife,
-            ccd: Some(0.04),
+            rotation: 0.0,
+            collision_groups: CollisionGroupsKind::Knife.into(),
+            ccd: Some(PROJECTILE_CCD_THRESHOLD),
+            kinematic: false,
         };
 
+        self.add_rigid_body(message);
+
         let physics = self.physics_thread_link.lock().unwrap();
-        physics.send.send(message);
         physics.set_lin_vel(id, velocity);
         physics.set_rotation(id, rot);
 
@@ -641,21 +2278,45 @@ impl World {
     }
 
     pub fn set_player_jumping(&mut self, jumping: bool) {
-        let mut playerc = self.specs_world.write::<Player>();
-        let player = playerc.get_mut(self.player).unwrap();
+        let mut ground_sensorc = self.specs_world.write::<GroundSensor>();
+        let ground_sensor = ground_sensorc.get_mut(self.player).unwrap();
         let idc = self.read_component::<RigidBodyID>();
         let &body_id = idc.get(self.player).unwrap();
 
         let physics = self.physics_thread_link.lock().unwrap();
 
-        if jumping {
-            if player.touching_ground {
-                // player.jump(&mut world.data);
-                player.touching_ground = false;
+        let stunned = self.read_component::<StatusEffects>()
+            .get(self.player)
+            .map_or(false, |effects| effects.is_stunned());
+        let config = *self.specs_world.read_resource::<PlayerConfig>();
 
-                let mut lvel = physics.get_lin_vel(body_id);
-                lvel.y = -6.0;
-                physics.set_lin_vel(body_id, lvel);
+        let mut playerc = self.specs_world.write::<Player>();
+        let player = playerc.get_mut(self.player).unwrap();
+        let pressed = jumping && !player.jump_held;
+        player.jump_held = jumping;
+
+        if jumping && !stunned {
+            // holding jump keeps jumping off the ground, but air jumps need a fresh press
+            let can_jump = if ground_sensor.touching_ground {
+                player.jumps_used = 0;
+                true
+            } else {
+                // walking off a ledge uses up the first jump
+                player.jumps_used = player.jumps_used.max(1);
+                pressed && player.jumps_used < config.max_jumps
+            };
+
+            if can_jump {
+                ground_sensor.touching_ground = false;
+                player.jumps_used += 1;
+
+                if let Ok(mut lvel) = physics.get_lin_vel(body_id) {
+                    lvel.y = -config.jump_speed;
+                    physics.set_lin_vel(body_id, lvel);
+                    self.specs_world
+                        .write_resource::<GameEvents>()
+                        .push(GameEvent::Jumped);
+                }
             }
         } else {
             // let mut lvel = physics.get_lin_vel(body_id);
@@ -698,11 +2359,38 @@ impl CrateMaterial {
     }
 }
 
-#[derive(Debug, Clone)]
-pub enum Event {
-    SpawnParticle {
-        rect: Rect,
-        velocity: Vector<N>,
-        ttl: N,
-    },
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A bullet right under the player's feet, with nothing else to stand on, mustn't count as ground.
+    #[test]
+    fn bullet_under_player_is_not_ground() {
+        let mut world = World::new(0.0, 0.0, 1);
+        // without an air jump, any jump would have to come from the bullet
+        world.set_player_config(PlayerConfig {
+            max_jumps: 1,
+            ..PlayerConfig::default()
+        });
+
+        let radius = 0.1;
+        world.new_bullet(Vector::new(0.0, PLAYER_HALF_HEIGHT + radius), radius, Vector::zero());
+
+        for _ in 0..10 {
+            world.step(FIXED_TIMESTEP);
+            assert!(!world.player_on_ground());
+        }
+
+        world.set_player_jumping(true);
+        let lin_vel = world
+            .physics_thread_link()
+            .lock()
+            .unwrap()
+            .get_lin_vel(world.player_rigid_body_id())
+            .unwrap();
+        // up is negative y
+        assert!(lin_vel.y >= 0.0);
+
+        world.shutdown();
+    }
 }
@@ -1,18 +1,50 @@
 use engine::N;
 
+// The furthest the view moves from where it should be at full trauma, in metres.
+const MAX_SHAKE_OFFSET: N = 0.4;
+// Trauma lost per second.
+const TRAUMA_DECAY: N = 1.2;
+
+const DEFAULT_LOOKAHEAD: N = 0.25;
+const DEFAULT_MAX_LOOKAHEAD: N = 3.0;
+
 pub struct Camera {
     // the position of the world which is at the centre of the screen (in metres)
     x: N,
     y: N,
 
-    // the position in the window where the mouse pointer is
+    // where the camera was before the last update, and how far from there to (x, y) to draw
+    // the current frame, so the view moves smoothly whatever the refresh rate
+    prev_x: N,
+    prev_y: N,
+    update_dt: N,
+    interpolation_alpha: N,
+
+    // the position in the window where the mouse pointer is, in drawn pixels
     pub mouse_x: f64,
     pub mouse_y: f64,
 
+    // the size of the window in drawn pixels, which on HiDPI screens is more than its size in window coordinates
     pub win_w: u32,
     pub win_h: u32,
 
+    // at a DPI scale of 1; the world is scaled up with the DPI so it looks the same size on any screen
     pub pixels_per_metre: f64,
+    // drawn pixels per unit of window coordinates
+    dpi_scale: f64,
+
+    // how far the view leans toward the mouse pointer, as a fraction of its distance from the
+    // centre of the screen, and the most it leans in metres
+    pub lookahead: N,
+    pub max_lookahead: N,
+
+    // from 0 to 1; the shake grows with its square, so small knocks stay subtle
+    trauma: N,
+    shake_time: N,
+    shake_offset: (N, N),
+
+    // (left, top, right, bottom) of the area the view is kept inside
+    bounds: Option<(N, N, N, N)>,
 }
 
 impl Camera {
@@ -20,16 +52,60 @@ impl Camera {
         Camera {
             x: x,
             y: y,
-            mouse_x: 0.0, // TODO ???
-            mouse_y: 0.0,
+            prev_x: x,
+            prev_y: y,
+            update_dt: 0.0,
+            interpolation_alpha: 1.0,
+            // the middle of the window, so the view doesn't lean anywhere until the mouse moves
+            mouse_x: (win_w / 2) as f64,
+            mouse_y: (win_h / 2) as f64,
             win_w: win_w,
             win_h: win_h,
             pixels_per_metre: pixels_per_metre,
+            dpi_scale: 1.0,
+            lookahead: DEFAULT_LOOKAHEAD,
+            max_lookahead: DEFAULT_MAX_LOOKAHEAD,
+            trauma: 0.0,
+            shake_time: 0.0,
+            shake_offset: (0.0, 0.0),
+            bounds: None,
         }
     }
 
+    /// Keeps the view inside the given (left, top, right, bottom) edges, or anywhere if `None`.
+    pub fn set_bounds(&mut self, bounds: Option<(N, N, N, N)>) {
+        self.bounds = bounds;
+    }
+
+    /// Shakes the view. `amount` is from 0 (nothing) to 1 (as hard as it gets), and adds to
+    /// any shake already going on.
+    pub fn add_trauma(&mut self, amount: N) {
+        self.trauma = (self.trauma + amount).min(1.0);
+    }
+
+    /// Moves the shake along. Call once per update, whatever else the camera is doing.
+    pub fn update(&mut self, dt: N) {
+        self.update_dt = dt;
+        self.shake_time += dt;
+        self.trauma = (self.trauma - TRAUMA_DECAY * dt).max(0.0);
+
+        // out of step sine waves look random enough, and keep the rng for the game
+        let t = self.shake_time;
+        let shake = self.trauma * self.trauma * MAX_SHAKE_OFFSET;
+        self.shake_offset = (
+            shake * ((t * 37.0).sin() * 0.7 + (t * 59.0).sin() * 0.3),
+            shake * ((t * 43.0).sin() * 0.7 + (t * 71.0).cos() * 0.3),
+        );
+    }
+
+    /// Follows a target, such as the player, leaning toward where the mouse is pointing.
     pub fn set_pos_smooth(&mut self, x: N, y: N) {
+        self.prev_x = self.x;
+        self.prev_y = self.y;
+
         let (vw, vh) = self.game_viewport_size();
+        let (lx, ly) = self.lookahead_offset();
+        let (x, y) = (x + lx, y + ly);
 
         let scroll_window_w = vw * 0.1;
         let scroll_window_h = vh * 0.1;
@@ -44,15 +120,72 @@ impl Camera {
         if dy.abs() > scroll_window_h {
             self.y += (dy - scroll_window_h * dy.signum()) * 0.1;
         }
+
+        if let Some((left, top, right, bottom)) = self.bounds {
+            self.x = clamp_to_bounds(self.x, vw, left, right);
+            self.y = clamp_to_bounds(self.y, vh, top, bottom);
+        }
     }
 
+    // Measured from the centre of the screen rather than from the target, so that moving the
+    // view doesn't move the point it is leaning toward.
+    fn lookahead_offset(&self) -> (N, N) {
+        let (mx, my) = self.pair_pixels_to_metres(
+            self.mouse_x - (self.win_w / 2) as f64,
+            self.mouse_y - (self.win_h / 2) as f64,
+        );
+        let (lx, ly) = (mx * self.lookahead, my * self.lookahead);
+
+        let length = (lx * lx + ly * ly).sqrt();
+        if length > self.max_lookahead {
+            let scale = self.max_lookahead / length;
+            (lx * scale, ly * scale)
+        } else {
+            (lx, ly)
+        }
+    }
+
+    /// Takes the size of the window in drawn pixels.
     pub fn set_window_dimensions(&mut self, w: u32, h: u32) {
         self.win_w = w;
         self.win_h = h;
     }
 
+    pub fn set_dpi_scale(&mut self, scale: f64) {
+        self.dpi_scale = scale;
+    }
+
+    pub fn dpi_scale(&self) -> f64 {
+        self.dpi_scale
+    }
+
+    /// Takes the mouse position in window coordinates, as mouse events give it.
+    pub fn set_mouse_pos(&mut self, x: f64, y: f64) {
+        self.mouse_x = x * self.dpi_scale;
+        self.mouse_y = y * self.dpi_scale;
+    }
+
+    /// Sets how long it has been since the last update, for the frame about to be drawn.
+    pub fn set_time_since_update(&mut self, time: N) {
+        self.interpolation_alpha = if self.update_dt > 0.0 {
+            (time / self.update_dt).min(1.0)
+        } else {
+            1.0
+        };
+    }
+
+    /// How far the current frame is from the state before the last update to the state after it,
+    /// from 0 to 1. Things in the world are drawn this far between their previous and current positions.
+    pub fn interpolation_alpha(&self) -> N {
+        self.interpolation_alpha
+    }
+
+    /// The point at the centre of the screen, including any shake.
     pub fn pos(&self) -> (N, N) {
-        (self.x, self.y)
+        let alpha = self.interpolation_alpha;
+        let x = self.prev_x + (self.x - self.prev_x) * alpha;
+        let y = self.prev_y + (self.y - self.prev_y) * alpha;
+        (x + self.shake_offset.0, y + self.shake_offset.1)
     }
 
     pub fn game_viewport_size(&self) -> (N, N) {
@@ -60,11 +193,11 @@ impl Camera {
     }
 
     pub fn metres_to_pixels(&self, val: N) -> f64 {
-        val as f64 * self.pixels_per_metre
+        val as f64 * self.pixels_per_metre * self.dpi_scale
     }
 
     pub fn pixels_to_metres(&self, val: f64) -> N {
-        (val / self.pixels_per_metre) as N
+        (val / (self.pixels_per_metre * self.dpi_scale)) as N
     }
 
     pub fn pair_metres_to_pixels(&self, x: N, y: N) -> (f64, f64) {
@@ -76,22 +209,36 @@ impl Camera {
     }
 
     pub fn pos_to_screen(&self, x: N, y: N) -> (f64, f64) {
-        let (px, py) = self.pair_metres_to_pixels(x - self.x, y - self.y);
+        let (cx, cy) = self.pos();
+        let (px, py) = self.pair_metres_to_pixels(x - cx, y - cy);
         (px + (self.win_w / 2) as f64, py + (self.win_h / 2) as f64)
     }
 
+    /// Takes a position in drawn pixels, like `mouse_x` and `mouse_y`.
     pub fn screen_to_pos(&self, x: f64, y: f64) -> (N, N) {
+        let (cx, cy) = self.pos();
         let (wx, wy) = self.pair_pixels_to_metres(x - (self.win_w / 2) as f64, y - (self.win_h / 2) as f64);
-        (wx + self.x, wy + self.y)
+        (wx + cx, wy + cy)
     }
 
     pub fn array_pos_to_screen(&self, pos: [N; 4]) -> [f64; 4] {
+        let (cx, cy) = self.pos();
         let mut npos = [0.0; 4];
-        npos[0] = self.metres_to_pixels(pos[0] - self.x) + (self.win_w / 2) as f64;
-        npos[1] = self.metres_to_pixels(pos[1] - self.y) + (self.win_h / 2) as f64;
+        npos[0] = self.metres_to_pixels(pos[0] - cx) + (self.win_w / 2) as f64;
+        npos[1] = self.metres_to_pixels(pos[1] - cy) + (self.win_h / 2) as f64;
         npos[2] = self.metres_to_pixels(pos[2]);
         npos[3] = self.metres_to_pixels(pos[3]);
 
         npos
     }
 }
+
+// Keeps a view of the given size between min and max, centring it if it is bigger than the gap.
+fn clamp_to_bounds(centre: N, view_size: N, min: N, max: N) -> N {
+    let half = view_size / 2.0;
+    if max - min <= view_size {
+        (min + max) / 2.0
+    } else {
+        centre.max(min + half).min(max - half)
+    }
+}
@@ -0,0 +1,165 @@
+use piston_window::Key;
+
+use config::{self, Config, Control, ALL_CONTROLS};
+
+// Multisampling settings to cycle through.
+const SAMPLE_COUNTS: &'static [u8] = &[0, 2, 4, 8];
+const VOLUME_STEP: f32 = 0.1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettingsItem {
+    Vsync,
+    Samples,
+    Fullscreen,
+    MasterVolume,
+    SfxVolume,
+    MusicVolume,
+    Mute,
+    PlayerWidgets,
+    Control(Control),
+}
+
+/// What the game should do after the settings screen has handled a key press.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettingsResponse {
+    Nothing,
+    /// The config changed, so should be saved.
+    Changed,
+    Close,
+}
+
+/// The settings screen, opened with Escape. The game is paused while it's open.
+#[derive(Debug, Clone, Default)]
+pub struct SettingsMenu {
+    selected: usize,
+    /// Waiting for the key to bind the selected control to.
+    rebinding: bool,
+}
+
+impl SettingsMenu {
+    pub fn new() -> Self {
+        SettingsMenu::default()
+    }
+
+    pub fn items(&self) -> Vec<SettingsItem> {
+        let mut items = vec![
+            SettingsItem::Vsync,
+            SettingsItem::Samples,
+            SettingsItem::Fullscreen,
+            SettingsItem::MasterVolume,
+            SettingsItem::SfxVolume,
+            SettingsItem::MusicVolume,
+            SettingsItem::Mute,
+            SettingsItem::PlayerWidgets,
+        ];
+        items.extend(ALL_CONTROLS.iter().map(|&control| SettingsItem::Control(control)));
+        items
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn handle_key(&mut self, key: Key, config: &mut Config) -> SettingsResponse {
+        let items = self.items();
+        let item = items[self.selected];
+
+        if self.rebinding {
+            self.rebinding = false;
+            return match (item, config::parse_key(&config::key_name(key))) {
+                (SettingsItem::Control(control), Some(key)) => {
+                    *config.controls.key_mut(control) = key;
+                    SettingsResponse::Changed
+                }
+                _ => SettingsResponse::Nothing,
+            };
+        }
+
+        match key {
+            Key::Escape => SettingsResponse::Close,
+            Key::Up => {
+                self.selected = (self.selected + items.len() - 1) % items.len();
+                SettingsResponse::Nothing
+            }
+            Key::Down => {
+                self.selected = (self.selected + 1) % items.len();
+                SettingsResponse::Nothing
+            }
+            Key::Left => adjust(item, config, -1),
+            Key::Right => adjust(item, config, 1),
+            Key::Return => match item {
+                SettingsItem::Control(_) => {
+                    self.rebinding = true;
+                    SettingsResponse::Nothing
+                }
+                _ => adjust(item, config, 1),
+            },
+            _ => SettingsResponse::Nothing,
+        }
+    }
+
+    /// The name and current value of an item, for display.
+    pub fn label(&self, index: usize, config: &Config) -> (String, String) {
+        let item = self.items()[index];
+        let on_off = |on: bool| if on { "on".to_owned() } else { "off".to_owned() };
+        let percent = |volume: f32| format!("{:.0}%", volume * 100.0);
+
+        match item {
+            SettingsItem::Vsync => ("Vsync (on restart)".to_owned(), on_off(config.video.vsync)),
+            SettingsItem::Samples => (
+                "Antialiasing (on restart)".to_owned(),
+                match config.video.samples {
+                    0 => "off".to_owned(),
+                    samples => format!("{}x", samples),
+                },
+            ),
+            SettingsItem::Fullscreen => ("Fullscreen (F11)".to_owned(), on_off(config.video.fullscreen)),
+            SettingsItem::MasterVolume => ("Volume".to_owned(), percent(config.audio.master_volume)),
+            SettingsItem::SfxVolume => ("Effects volume".to_owned(), percent(config.audio.sfx_volume)),
+            SettingsItem::MusicVolume => ("Music volume".to_owned(), percent(config.audio.music_volume)),
+            SettingsItem::Mute => ("Mute (M)".to_owned(), on_off(config.audio.muted)),
+            SettingsItem::PlayerWidgets => (
+                "Ammo by the player".to_owned(),
+                on_off(config.hud.widgets_follow_player),
+            ),
+            SettingsItem::Control(control) => (
+                control.name().to_owned(),
+                if self.rebinding && index == self.selected {
+                    "press a key...".to_owned()
+                } else {
+                    config::key_name(config.controls.key(control))
+                },
+            ),
+        }
+    }
+}
+
+// Steps a setting up or down; on/off settings just flip.
+fn adjust(item: SettingsItem, config: &mut Config, direction: i32) -> SettingsResponse {
+    match item {
+        SettingsItem::Vsync => config.video.vsync = !config.video.vsync,
+        SettingsItem::Fullscreen => config.video.fullscreen = !config.video.fullscreen,
+        SettingsItem::Mute => config.audio.muted = !config.audio.muted,
+        SettingsItem::PlayerWidgets => config.hud.widgets_follow_player = !config.hud.widgets_follow_player,
+        SettingsItem::Samples => {
+            let current = SAMPLE_COUNTS
+                .iter()
+                .position(|&samples| samples == config.video.samples)
+                .unwrap_or(0) as i32;
+            let next = (current + direction).max(0).min(SAMPLE_COUNTS.len() as i32 - 1);
+            config.video.samples = SAMPLE_COUNTS[next as usize];
+        }
+        SettingsItem::MasterVolume => step_volume(&mut config.audio.master_volume, direction),
+        SettingsItem::SfxVolume => step_volume(&mut config.audio.sfx_volume, direction),
+        SettingsItem::MusicVolume => step_volume(&mut config.audio.music_volume, direction),
+        SettingsItem::Control(_) => return SettingsResponse::Nothing,
+    }
+
+    SettingsResponse::Changed
+}
+
+fn step_volume(volume: &mut f32, direction: i32) {
+    // rounded, so repeated steps don't drift away from whole percentages
+    let stepped = *volume + VOLUME_STEP * direction as f32;
+    *volume = ((stepped * 10.0).round() / 10.0).max(0.0).min(1.0);
+}
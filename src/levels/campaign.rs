@@ -0,0 +1,99 @@
+use std::fs::OpenOptions;
+use std::io::Read;
+
+use serde_json;
+
+use media;
+use super::{level_file_path, LevelError};
+
+/// An ordered set of levels, loaded from e.g. `campaign.json` in the levels directory.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Campaign {
+    pub name: String,
+    pub levels: Vec<CampaignLevel>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CampaignLevel {
+    /// Path of the level file, relative to the levels directory.
+    pub path: String,
+    /// Shown to the player instead of the path.
+    pub name: String,
+    #[serde(default)]
+    pub unlock: UnlockRequirement,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum UnlockRequirement {
+    Always,
+    /// The level before this one in the campaign has been completed.
+    Previous,
+    /// The level with this path has been completed.
+    Level(String),
+    /// At least this many levels of the campaign have been completed.
+    NumCompleted(usize),
+}
+
+impl Default for UnlockRequirement {
+    fn default() -> Self {
+        UnlockRequirement::Previous
+    }
+}
+
+impl Campaign {
+    pub fn load(media_handle: &media::MediaHandle, path: &str) -> Result<Campaign, LevelError> {
+        let full_path = level_file_path(media_handle, path);
+
+        let mut file = OpenOptions::new().read(true).open(full_path)?;
+
+        let mut text = String::new();
+        file.read_to_string(&mut text)?;
+        let campaign: Campaign = serde_json::from_str(&text)?;
+        // there'd be nothing to start playing
+        if campaign.levels.is_empty() {
+            return Err(LevelError::Invalid {
+                path: path.to_owned(),
+                position: None,
+                entity: None,
+                message: "campaign has no levels".to_owned(),
+            });
+        }
+
+        println!("Loaded campaign `{}`", campaign.name);
+
+        Ok(campaign)
+    }
+
+    pub fn position(&self, path: &str) -> Option<usize> {
+        self.levels.iter().position(|level| level.path == path)
+    }
+
+    /// `completed` holds the paths of every level the player has completed.
+    pub fn is_unlocked(&self, index: usize, completed: &[String]) -> bool {
+        let is_completed = |path: &str| completed.iter().any(|c| c == path);
+
+        match self.levels[index].unlock {
+            UnlockRequirement::Always => true,
+            UnlockRequirement::Previous => index == 0 || is_completed(&self.levels[index - 1].path),
+            UnlockRequirement::Level(ref path) => is_completed(path),
+            UnlockRequirement::NumCompleted(n) => {
+                self.levels
+                    .iter()
+                    .filter(|level| is_completed(&level.path))
+                    .count() >= n
+            }
+        }
+    }
+
+    /// The first unlocked level that hasn't been completed yet, to continue the campaign from.
+    pub fn first_incomplete(&self, completed: &[String]) -> Option<usize> {
+        (0..self.levels.len()).find(|&i| {
+            self.is_unlocked(i, completed) && !completed.iter().any(|c| *c == self.levels[i].path)
+        })
+    }
+
+    /// The first unlocked level after the given one.
+    pub fn next_unlocked(&self, index: usize, completed: &[String]) -> Option<usize> {
+        (index + 1..self.levels.len()).find(|&i| self.is_unlocked(i, completed))
+    }
+}
@@ -0,0 +1,122 @@
+use std::fs;
+use std::time::SystemTime;
+
+use media;
+use super::{level_file_path, Campaign, Level, LevelError, LoadMode};
+
+/// Keeps track of which level of a campaign is being played, and loads the next one once it's complete.
+pub struct LevelManager {
+    campaign: Campaign,
+    // index into the campaign, or None if the level isn't part of it
+    current: Option<usize>,
+    level: Level,
+    level_path: String,
+    load_mode: LoadMode,
+    // when the level file was last modified, as of loading it
+    level_modified: Option<SystemTime>,
+}
+
+impl LevelManager {
+    /// Loads a campaign (e.g. `campaign.json`) from the levels directory, starting at its first level.
+    pub fn load(media_handle: &media::MediaHandle, campaign_path: &str, load_mode: LoadMode) -> Result<LevelManager, LevelError> {
+        let campaign = Campaign::load(media_handle, campaign_path)?;
+
+        let first = campaign.levels[0].path.clone();
+        let level = Level::load_with_mode(media_handle, &first, load_mode)?;
+
+        Ok(LevelManager {
+            campaign,
+            current: Some(0),
+            level,
+            level_modified: modified_time(media_handle, &first),
+            level_path: first,
+            load_mode,
+        })
+    }
+
+    /// Switches to the given level. If it is in the campaign, progression continues from there.
+    pub fn jump_to(&mut self, media_handle: &media::MediaHandle, path: &str) -> Result<(), LevelError> {
+        self.level = Level::load_with_mode(media_handle, path, self.load_mode)?;
+        self.level_path = path.to_owned();
+        self.level_modified = modified_time(media_handle, path);
+        self.current = self.campaign.position(path);
+
+        Ok(())
+    }
+
+    /// Re-reads the current level from disk. On error, the previously loaded level is kept.
+    pub fn reload(&mut self, media_handle: &media::MediaHandle) -> Result<(), LevelError> {
+        // remember the new modification time even if loading fails, so that a broken file isn't retried every frame
+        self.level_modified = modified_time(media_handle, &self.level_path);
+
+        let path = self.level_path.clone();
+        self.jump_to(media_handle, &path)
+    }
+
+    /// Writes the current level back to its file.
+    pub fn save_level(&mut self, media_handle: &media::MediaHandle) -> Result<(), LevelError> {
+        self.level.save(media_handle, &self.level_path)?;
+        // don't treat our own write as an edit to reload
+        self.level_modified = modified_time(media_handle, &self.level_path);
+        Ok(())
+    }
+
+    /// Returns true if the level file has been modified since it was loaded.
+    pub fn level_file_changed(&self, media_handle: &media::MediaHandle) -> bool {
+        let modified = modified_time(media_handle, &self.level_path);
+        modified.is_some() && modified != self.level_modified
+    }
+
+    /// Switches to the first level of the campaign the player hasn't completed yet, if there is one.
+    pub fn continue_campaign(&mut self, media_handle: &media::MediaHandle, completed: &[String]) -> Result<(), LevelError> {
+        if let Some(index) = self.campaign.first_incomplete(completed) {
+            let path = self.campaign.levels[index].path.clone();
+            self.jump_to(media_handle, &path)?;
+        }
+
+        Ok(())
+    }
+
+    /// Loads the next unlocked level, returning false if there isn't one.
+    pub fn advance(&mut self, media_handle: &media::MediaHandle, completed: &[String]) -> Result<bool, LevelError> {
+        let next = match self.current {
+            Some(current) => self.campaign.next_unlocked(current, completed),
+            None => None,
+        };
+
+        let path = match next {
+            Some(next) => self.campaign.levels[next].path.clone(),
+            None => return Ok(false),
+        };
+
+        self.jump_to(media_handle, &path)?;
+
+        Ok(true)
+    }
+
+    pub fn campaign(&self) -> &Campaign {
+        &self.campaign
+    }
+
+    pub fn level(&self) -> &Level {
+        &self.level
+    }
+
+    pub fn level_path(&self) -> &str {
+        &self.level_path
+    }
+
+    /// The name of the current level as given by the campaign, falling back to the level's own name.
+    pub fn display_name(&self) -> &str {
+        match self.current {
+            Some(current) => &self.campaign.levels[current].name,
+            None => &self.level.name,
+        }
+    }
+}
+
+fn modified_time(media_handle: &media::MediaHandle, path: &str) -> Option<SystemTime> {
+    fs::metadata(level_file_path(media_handle, path))
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
@@ -1,24 +1,102 @@
 use std::fs::OpenOptions;
+use std::path::PathBuf;
 use std::io::{self, Read, Write};
 use std::error::Error as StdError;
 use std::fmt::{self, Display, Formatter};
 
+use serde::{Deserialize, Deserializer};
+use serde::de::Error as DeError;
 use serde_json;
+use ron;
+use toml;
+use rand;
 
 use nphysics::math::Vector;
 
 use media;
 use engine::*;
 
+mod manager;
+pub use self::manager::*;
+
+mod campaign;
+pub use self::campaign::*;
+
 #[derive(Debug)]
 pub enum LevelError {
     IoError(io::Error),
     SerdeError(serde_json::Error),
+    RonSerError(ron::ser::Error),
+    TomlSerError(toml::ser::Error),
+    /// The path doesn't end in `.json`, `.ron` or `.toml`.
+    UnknownFormat(String),
+    /// A level file that couldn't be parsed into a level.
+    Invalid {
+        path: String,
+        /// Line and column, for the formats which report them.
+        position: Option<(usize, usize)>,
+        /// Index into the level's entities of the one at fault, if it was one of them. Only known for JSON.
+        entity: Option<usize>,
+        message: String,
+    },
+}
+
+impl LevelError {
+    fn invalid(path: &str, err: &serde_json::Error, entity: Option<usize>) -> LevelError {
+        LevelError::Invalid {
+            path: path.to_owned(),
+            position: Some((err.line(), err.column())),
+            entity,
+            message: err.to_string(),
+        }
+    }
+
+    fn invalid_ron(path: &str, err: &ron::de::Error) -> LevelError {
+        LevelError::Invalid {
+            path: path.to_owned(),
+            position: None,
+            entity: None,
+            message: err.to_string(),
+        }
+    }
+
+    fn invalid_toml(path: &str, err: &toml::de::Error) -> LevelError {
+        LevelError::Invalid {
+            path: path.to_owned(),
+            // toml counts from zero
+            position: err.line_col().map(|(line, column)| (line + 1, column + 1)),
+            entity: None,
+            message: err.to_string(),
+        }
+    }
 }
 
 impl Display for LevelError {
     fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
-        fmt::Debug::fmt(&self, f)
+        match *self {
+            LevelError::Invalid {
+                ref path,
+                position,
+                entity,
+                ref message,
+            } => {
+                write!(f, "{}", path)?;
+                if let Some((line, column)) = position {
+                    write!(f, ":{}:{}", line, column)?;
+                }
+                write!(f, ": ")?;
+                if let Some(entity) = entity {
+                    write!(f, "entity #{}: ", entity)?;
+                }
+                write!(f, "{}", message)
+            }
+            LevelError::UnknownFormat(ref path) => write!(
+                f,
+                "{}: unknown level format (expected .json, .ron or .toml)",
+                path
+            ),
+            _ => fmt::Debug::fmt(&self, f),
+        }
     }
 }
 
@@ -30,6 +108,8 @@ impl StdError for LevelError {
     fn cause(&self) -> Option<&StdError> {
         match *self {
             LevelError::SerdeError(ref e) => Some(e),
+            LevelError::RonSerError(ref e) => Some(e),
+            LevelError::TomlSerError(ref e) => Some(e),
             _ => None,
         }
     }
@@ -47,11 +127,119 @@ impl From<io::Error> for LevelError {
     }
 }
 
+impl From<ron::ser::Error> for LevelError {
+    fn from(err: ron::ser::Error) -> LevelError {
+        LevelError::RonSerError(err)
+    }
+}
+
+impl From<toml::ser::Error> for LevelError {
+    fn from(err: toml::ser::Error) -> LevelError {
+        LevelError::TomlSerError(err)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadMode {
+    /// Any invalid entity fails the whole level.
+    Strict,
+    /// Invalid entities are skipped with a warning. Only supported for JSON levels.
+    SkipInvalidEntities,
+}
+
+/// A level whose entities haven't been parsed yet, so that they can be checked one at a time.
+#[derive(Deserialize)]
+struct RawLevel {
+    name: String,
+    #[serde(default)]
+    seed: Option<u32>,
+    player_start_pos: (N, N),
+    entities: Vec<serde_json::Value>,
+    #[serde(default)]
+    tile_layers: Vec<TileLayer>,
+    #[serde(default)]
+    time_stop: Option<TimeStopConfig>,
+    #[serde(default)]
+    player: Option<PlayerConfig>,
+    #[serde(default)]
+    player_sprite: Option<String>,
+    #[serde(default)]
+    player_animation: Option<Animation>,
+    #[serde(default)]
+    camera_bounds: Option<Rect>,
+    #[serde(default)]
+    ambient_light: Option<f32>,
+    #[serde(default)]
+    player_light: Option<Light>,
+    #[serde(default)]
+    music: Option<String>,
+}
+
+/// The path of a file in the levels directory.
+pub fn level_file_path(media_handle: &media::MediaHandle, path: &str) -> PathBuf {
+    let mut full_path = media_handle.base_path.clone();
+    full_path.push("levels/");
+    full_path.push(path);
+    full_path
+}
+
+/// The file formats levels can be stored in, chosen by file extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LevelFormat {
+    Json,
+    Ron,
+    Toml,
+}
+
+impl LevelFormat {
+    pub fn from_path(path: &str) -> Result<LevelFormat, LevelError> {
+        if path.ends_with(".json") {
+            Ok(LevelFormat::Json)
+        } else if path.ends_with(".ron") {
+            Ok(LevelFormat::Ron)
+        } else if path.ends_with(".toml") {
+            Ok(LevelFormat::Toml)
+        } else {
+            Err(LevelError::UnknownFormat(path.to_owned()))
+        }
+    }
+}
+
+// Plain values come before tables, as TOML needs them to.
 #[derive(Default, Clone, Serialize, Deserialize)]
 pub struct Level {
     pub name: String,
+    /// If unset, a random seed is used each time the level is played.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u32>,
     pub player_start_pos: (N, N),
+    /// Image to draw the player with, instead of a plain rectangle.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub player_sprite: Option<String>,
+    /// How lit the level is away from any lights, from 0 (pitch black) to 1. Without this the level isn't darkened.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ambient_light: Option<f32>,
+    /// A file in `media/music/`, looped while the level is played.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub music: Option<String>,
+    /// Overrides the default time stop meter settings.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_stop: Option<TimeStopConfig>,
+    /// Overrides the default player movement settings.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub player: Option<PlayerConfig>,
+    /// Frames of `player_sprite`, if it is a sprite sheet.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub player_animation: Option<Animation>,
+    /// The area the camera is kept inside. Rotation is ignored.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub camera_bounds: Option<Rect>,
+    /// Replaces the player's usual glow.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub player_light: Option<Light>,
     pub entities: Vec<LevelEntity>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tile_layers: Vec<TileLayer>,
 }
 
 #[derive(Debug, Copy, Clone, Serialize, Deserialize)]
@@ -66,33 +254,267 @@ impl LevelVector {
     }
 }
 
+// Terrain needs a line at least, so fewer points is rejected along with any other invalid entity.
+fn deserialize_terrain_points<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<LevelVector>, D::Error> {
+    let points = Vec::<LevelVector>::deserialize(deserializer)?;
+    if points.len() < 2 {
+        return Err(D::Error::custom(format!(
+            "terrain needs at least two points, but has {}",
+            points.len()
+        )));
+    }
+    Ok(points)
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum LevelEntity {
-    Ground { rect: Rect },
-    Crate { rect: Rect, material: CrateMaterial },
-    Enemy { rect: Rect },
+    Ground {
+        rect: Rect,
+        /// Draw layer, in place of the usual `LAYER_GROUND`.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        layer: Option<i32>,
+    },
+    Crate {
+        rect: Rect,
+        material: CrateMaterial,
+        /// Crates with hitpoints can be destroyed, dropping their contents.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        hitpoints: Option<u16>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        contents: Vec<Loot>,
+        /// Overrides the usual collision groups for a crate.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        collision_groups: Option<CollisionGroupsSpec>,
+        /// Image to draw the crate with, instead of a plain rectangle.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        sprite: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        layer: Option<i32>,
+    },
+    Enemy {
+        rect: Rect,
+        #[serde(default)]
+        kind: EnemyKind,
+        #[serde(default)]
+        facing: Facing,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        hitpoints: Option<u16>,
+        /// Points to walk between, after the starting position.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        patrol: Vec<LevelVector>,
+        /// Without one, the enemy is always on the attack.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        aggro_radius: Option<N>,
+        /// Image to draw the enemy with, instead of a plain rectangle.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        sprite: Option<String>,
+        /// Frames of `sprite`, if it is a sprite sheet.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        animation: Option<Animation>,
+    },
     Bullet {
         pos: LevelVector,
         radius: N,
         velocity: LevelVector,
     },
+    GravityZone { rect: Rect, gravity: LevelVector },
+    ForceField { rect: Rect, force: LevelVector },
+    /// Reaching this completes the level.
+    Goal { rect: Rect },
+    /// Static ground outlined by the points, for slopes and other non-rectangular shapes.
+    Terrain {
+        #[serde(deserialize_with = "deserialize_terrain_points")]
+        points: Vec<LevelVector>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        layer: Option<i32>,
+    },
+    /// Static ground made of convex polygons, balls and boxes, possibly several joined together.
+    ShapedGround {
+        pos: LevelVector,
+        /// Clockwise, in radians, around `pos`.
+        #[serde(default)]
+        rotation: N,
+        shape: ShapeDesc,
+        /// Overrides the usual collision groups for static ground.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        collision_groups: Option<CollisionGroupsSpec>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        layer: Option<i32>,
+    },
+    /// Blocks the way until opened by a switch or key whose `target` is `id`.
+    Door {
+        rect: Rect,
+        id: LinkId,
+        #[serde(default)]
+        open: bool,
+    },
+    /// Opens `target` when touched by the player or hit by a knife.
+    Switch { rect: Rect, target: LinkId },
+    Boss {
+        rect: Rect,
+        #[serde(default)]
+        name: String,
+        #[serde(default = "default_boss_hitpoints")]
+        hitpoints: u16,
+    },
+    /// Spawns enemies at `rect` every `interval` seconds while fewer than `max_alive` are alive,
+    /// `total` times at most. With a `trigger`, nothing happens until the player enters that area.
+    Spawner {
+        rect: Rect,
+        #[serde(default)]
+        kind: EnemyKind,
+        interval: N,
+        max_alive: usize,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        total: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        trigger: Option<Rect>,
+    },
+    /// Runs the actions when the player enters the area.
+    Trigger {
+        rect: Rect,
+        on_enter: Vec<Action>,
+        /// Whether the actions run every time the player enters, rather than just the first.
+        #[serde(default)]
+        repeat: bool,
+    },
+    /// Opens `target` when picked up.
+    Key { pos: LevelVector, target: LinkId },
+    /// Lights up its surroundings when the level has `ambient_light` set.
+    Light { pos: LevelVector, light: Light },
+    Coin {
+        pos: LevelVector,
+        #[serde(default = "default_coin_value")]
+        value: u32,
+    },
+    /// Gives the player health, ammo or time stop energy when touched.
+    Pickup {
+        pos: LevelVector,
+        kind: PickupKind,
+        /// Seconds until the pickup comes back after being collected. Without this it's gone for good.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        respawn: Option<N>,
+    },
+    /// Makes thrown knives bounce off walls `bounces` times before they embed.
+    RicochetPickup {
+        pos: LevelVector,
+        #[serde(default = "default_ricochet_bounces")]
+        bounces: u8,
+    },
+    /// Hurts anything touching it. Without `damage` it kills instantly.
+    Hazard {
+        rect: Rect,
+        #[serde(default)]
+        damage: Option<u16>,
+        kind: HazardKind,
+    },
+    /// A platform which travels from its starting position through the waypoints, carrying anything on top.
+    MovingPlatform {
+        rect: Rect,
+        waypoints: Vec<LevelVector>,
+        speed: N,
+        #[serde(default)]
+        loop_mode: LoopMode,
+    },
+}
+
+fn default_coin_value() -> u32 {
+    1
+}
+
+fn default_ricochet_bounces() -> u8 {
+    3
+}
+
+fn default_boss_hitpoints() -> u16 {
+    30
 }
 
 impl Level {
     pub fn to_world(&self) -> World {
+        self.to_world_with_seed(self.seed.unwrap_or_else(rand::random))
+    }
+
+    pub fn to_world_with_seed(&self, seed: u32) -> World {
         let (px, py) = self.player_start_pos;
-        let mut world = World::new(px, py);
+        let mut world = World::new(px, py, seed);
+
+        if let Some(config) = self.time_stop {
+            world.set_time_stop_config(config);
+        }
+        if let Some(config) = self.player {
+            world.set_player_config(config);
+        }
+        if let Some(ref sprite) = self.player_sprite {
+            world.set_player_sprite(sprite);
+        }
+        if let Some(ref animation) = self.player_animation {
+            world.set_player_animation(animation.clone());
+        }
+        world.set_ambient_light(self.ambient_light);
+        if let Some(light) = self.player_light {
+            world.set_player_light(Some(light));
+        }
+
+        for layer in &self.tile_layers {
+            world.add_tile_layer(layer.clone());
+        }
 
         for e in &self.entities {
             match *e {
-                LevelEntity::Ground { rect } => {
-                    world.new_ground(rect);
+                LevelEntity::Ground { rect, layer } => {
+                    let entity = world.new_ground(rect);
+                    if let Some(layer) = layer {
+                        world.set_layer(entity, layer);
+                    }
                 }
-                LevelEntity::Crate { rect, material } => {
-                    world.new_crate(rect, material);
+                LevelEntity::Crate {
+                    rect,
+                    material,
+                    hitpoints,
+                    ref contents,
+                    ref collision_groups,
+                    ref sprite,
+                    layer,
+                } => {
+                    let entity = match hitpoints {
+                        Some(hitpoints) => world.new_destructible_crate(rect, material, hitpoints, contents.clone()),
+                        None => world.new_crate(rect, material),
+                    };
+                    if let Some(ref spec) = *collision_groups {
+                        world.set_collision_groups(entity, spec.clone());
+                    }
+                    if let Some(ref sprite) = *sprite {
+                        world.set_sprite(entity, sprite);
+                    }
+                    if let Some(layer) = layer {
+                        world.set_layer(entity, layer);
+                    }
                 }
-                LevelEntity::Enemy { rect } => {
-                    world.new_enemy(rect);
+                LevelEntity::Enemy {
+                    rect,
+                    kind,
+                    facing,
+                    hitpoints,
+                    ref patrol,
+                    aggro_radius,
+                    ref sprite,
+                    ref animation,
+                } => {
+                    let config = EnemyConfig {
+                        kind,
+                        facing,
+                        hitpoints,
+                        patrol: patrol.iter().map(|p| p.to_vector()).collect(),
+                        aggro_radius,
+                    };
+                    let entity = world.new_configured_enemy(rect, config);
+                    if let Some(ref sprite) = *sprite {
+                        world.set_sprite(entity, sprite);
+                    }
+                    if let Some(ref animation) = *animation {
+                        world.set_animation(entity, animation.clone());
+                    }
                 }
                 LevelEntity::Bullet {
                     pos,
@@ -101,33 +523,184 @@ impl Level {
                 } => {
                     world.new_bullet(pos.to_vector(), radius, velocity.to_vector());
                 }
+                LevelEntity::GravityZone { rect, gravity } => {
+                    world.new_gravity_zone(rect, gravity.to_vector());
+                }
+                LevelEntity::ForceField { rect, force } => {
+                    world.new_force_field(rect, force.to_vector());
+                }
+                LevelEntity::Goal { rect } => {
+                    world.new_goal(rect);
+                }
+                LevelEntity::Terrain { ref points, layer } => {
+                    let points: Vec<_> = points.iter().map(|p| p.to_vector()).collect();
+                    let entity = world.new_terrain(&points);
+                    if let Some(layer) = layer {
+                        world.set_layer(entity, layer);
+                    }
+                }
+                LevelEntity::ShapedGround {
+                    pos,
+                    rotation,
+                    ref shape,
+                    ref collision_groups,
+                    layer,
+                } => {
+                    let entity = world.new_shaped_ground(pos.to_vector(), rotation, shape);
+                    if let Some(ref spec) = *collision_groups {
+                        world.set_collision_groups(entity, spec.clone());
+                    }
+                    if let Some(layer) = layer {
+                        world.set_layer(entity, layer);
+                    }
+                }
+                LevelEntity::Door { rect, ref id, open } => {
+                    world.new_door(rect, id.clone(), open);
+                }
+                LevelEntity::Switch { rect, ref target } => {
+                    world.new_switch(rect, target.clone());
+                }
+                LevelEntity::Boss {
+                    rect,
+                    ref name,
+                    hitpoints,
+                } => {
+                    world.new_boss(rect, name.clone(), hitpoints);
+                }
+                LevelEntity::Spawner {
+                    rect,
+                    kind,
+                    interval,
+                    max_alive,
+                    total,
+                    trigger,
+                } => {
+                    world.new_spawner(rect, kind, interval, max_alive, total, trigger);
+                }
+                LevelEntity::Trigger {
+                    rect,
+                    ref on_enter,
+                    repeat,
+                } => {
+                    world.new_trigger(rect, on_enter.clone(), repeat);
+                }
+                LevelEntity::Key { pos, ref target } => {
+                    world.new_key(pos.to_vector(), target.clone());
+                }
+                LevelEntity::Light { pos, light } => {
+                    world.new_light(pos.to_vector(), light);
+                }
+                LevelEntity::Coin { pos, value } => {
+                    world.new_coin(pos.to_vector(), value);
+                }
+                LevelEntity::RicochetPickup { pos, bounces } => {
+                    world.new_pickup(pos.to_vector(), PickupKind::Ricochet(bounces), None);
+                }
+                LevelEntity::Pickup { pos, kind, respawn } => {
+                    world.new_pickup(pos.to_vector(), kind, respawn);
+                }
+                LevelEntity::Hazard { rect, damage, kind } => {
+                    world.new_hazard(rect, kind, damage);
+                }
+                LevelEntity::MovingPlatform {
+                    rect,
+                    ref waypoints,
+                    speed,
+                    loop_mode,
+                } => {
+                    let waypoints: Vec<_> = waypoints.iter().map(|p| p.to_vector()).collect();
+                    world.new_moving_platform(rect, &waypoints, speed, loop_mode);
+                }
             }
         }
 
         world
     }
 
+    /// Loads a level from the levels directory, in the format given by its extension
+    /// (`.level.json`, `.level.ron` or `.level.toml`).
     pub fn load(media_handle: &media::MediaHandle, path: &str) -> Result<Level, LevelError> {
-        let mut full_path = media_handle.base_path.clone();
-        full_path.push("levels/");
-        full_path.push(path);
+        Level::load_with_mode(media_handle, path, LoadMode::Strict)
+    }
+
+    pub fn load_with_mode(media_handle: &media::MediaHandle, path: &str, mode: LoadMode) -> Result<Level, LevelError> {
+        let format = LevelFormat::from_path(path)?;
+
+        let full_path = level_file_path(media_handle, path);
 
         let mut file = OpenOptions::new().read(true).open(full_path)?;
 
         let mut text = String::new();
         file.read_to_string(&mut text)?;
-        let level: Level = serde_json::from_str(&text)?;
+        let parsed = match format {
+            LevelFormat::Json => Level::from_json(path, &text, mode),
+            LevelFormat::Ron => ron::de::from_str(&text).map_err(|err| LevelError::invalid_ron(path, &err)),
+            LevelFormat::Toml => toml::from_str(&text).map_err(|err| LevelError::invalid_toml(path, &err)),
+        };
+        let level: Level = match parsed {
+            Ok(level) => level,
+            Err(err) => {
+                if format != LevelFormat::Json && mode == LoadMode::SkipInvalidEntities {
+                    eprintln!("Warning: {}: invalid entities can only be skipped in JSON levels", path);
+                }
+                return Err(err);
+            }
+        };
 
         println!("Loaded level `{}`", level.name);
 
         Ok(level)
     }
 
+    fn from_json(path: &str, text: &str, mode: LoadMode) -> Result<Level, LevelError> {
+        let err = match serde_json::from_str::<Level>(text) {
+            Ok(level) => return Ok(level),
+            Err(err) => err,
+        };
+
+        // Go through the entities one by one, to find which of them are invalid.
+        let raw: RawLevel = serde_json::from_str(text).map_err(|raw_err| LevelError::invalid(path, &raw_err, None))?;
+
+        let mut entities = Vec::new();
+        for (i, value) in raw.entities.into_iter().enumerate() {
+            match serde_json::from_value(value) {
+                Ok(entity) => entities.push(entity),
+                Err(entity_err) => match mode {
+                    // the error from parsing the whole file has the line and column
+                    LoadMode::Strict => return Err(LevelError::invalid(path, &err, Some(i))),
+                    LoadMode::SkipInvalidEntities => {
+                        eprintln!("Warning: {}: skipping entity #{}: {}", path, i, entity_err);
+                    }
+                },
+            }
+        }
+
+        if mode == LoadMode::Strict {
+            return Err(LevelError::invalid(path, &err, None));
+        }
+
+        Ok(Level {
+            name: raw.name,
+            seed: raw.seed,
+            player_start_pos: raw.player_start_pos,
+            entities,
+            tile_layers: raw.tile_layers,
+            time_stop: raw.time_stop,
+            player: raw.player,
+            player_sprite: raw.player_sprite,
+            player_animation: raw.player_animation,
+            camera_bounds: raw.camera_bounds,
+            ambient_light: raw.ambient_light,
+            player_light: raw.player_light,
+            music: raw.music,
+        })
+    }
+
     pub fn save(&self, media_handle: &media::MediaHandle, path: &str) -> Result<(), LevelError> {
         println!("saving...");
-        let mut full_path = media_handle.base_path.clone();
-        full_path.push("levels/");
-        full_path.push(path);
+        let format = LevelFormat::from_path(path)?;
+
+        let full_path = level_file_path(media_handle, path);
 
         let mut file = OpenOptions::new()
             .write(true)
@@ -135,7 +708,15 @@ impl Level {
             .create(true)
             .open(full_path)?;
 
-        let text = serde_json::to_string_pretty(self)?;
+        let text = match format {
+            LevelFormat::Json => serde_json::to_string_pretty(self)?,
+            LevelFormat::Ron => ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?,
+            // toml can't serialize enum variants with fields, such as the entities, so go through JSON's values
+            LevelFormat::Toml => {
+                let value = json_to_toml(serde_json::to_value(self)?).unwrap_or(toml::Value::Table(Default::default()));
+                toml::to_string_pretty(&value)?
+            }
+        };
         writeln!(file, "{}", text)?;
 
         println!("Saved level `{}`", self.name);
@@ -143,3 +724,23 @@ impl Level {
         Ok(())
     }
 }
+
+// Nulls have no TOML equivalent, and are left out.
+fn json_to_toml(value: serde_json::Value) -> Option<toml::Value> {
+    use serde_json::Value as Json;
+
+    match value {
+        Json::Null => None,
+        Json::Bool(b) => Some(toml::Value::Boolean(b)),
+        Json::Number(n) => n.as_i64()
+            .map(toml::Value::Integer)
+            .or_else(|| n.as_f64().map(toml::Value::Float)),
+        Json::String(s) => Some(toml::Value::String(s)),
+        Json::Array(values) => Some(toml::Value::Array(values.into_iter().filter_map(json_to_toml).collect())),
+        Json::Object(map) => Some(toml::Value::Table(
+            map.into_iter()
+                .filter_map(|(key, value)| json_to_toml(value).map(|value| (key, value)))
+                .collect(),
+        )),
+    }
+}
@@ -2,10 +2,9 @@
 #![allow(unused_imports)]
 #![allow(unused_variables)]
 
-// TODO fix player jumping on bullets
-
 extern crate chan;
 extern crate gfx_device_gl;
+extern crate image;
 extern crate nalgebra as na;
 extern crate ncollide;
 extern crate nphysics2d as nphysics;
@@ -13,6 +12,8 @@ extern crate num;
 extern crate piston_window;
 extern crate rand;
 extern crate rodio;
+extern crate ron;
+extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 extern crate serde_json;
@@ -20,6 +21,7 @@ extern crate shred;
 #[macro_use]
 extern crate shred_derive;
 extern crate specs;
+extern crate toml;
 
 use piston_window::*;
 use nphysics::math::Vector;
@@ -31,35 +33,36 @@ mod media;
 mod audio;
 mod stat;
 mod levels;
+mod replay;
+mod bench;
+mod config;
 
 use engine::*;
 
 use levels::*;
 
 use interface::camera::Camera;
+use interface::settings::{SettingsMenu, SettingsResponse};
 
-use std::collections::HashSet;
+use replay::{InputFrame, Playback, Replay};
 
-const INIT_WIN_WIDTH: u32 = 800;
-const INIT_WIN_HEIGHT: u32 = 600;
+use std::collections::HashSet;
+use std::time::Instant;
 
 fn main() {
-    audio::init();
-
-    let opengl = OpenGL::V2_1;
-
+    if std::env::args().any(|arg| arg == "--bench") {
+        bench::run();
+        return;
+    }
 
     let mut stats_handler = stat::Handler::new();
     let mut stats = stats_handler.get();
     stats.num_startups += 1;
     stats_handler.set(stats);
 
-    let mut window: PistonWindow = WindowSettings::new("dio", [INIT_WIN_WIDTH, INIT_WIN_HEIGHT])
-        .exit_on_esc(true)
-        .samples(4)
-        .vsync(true)
-        .build()
-        .unwrap();
+    let mut config = config::Config::load_or_default();
+    let mut window = build_window(&config);
+    let mut window_is_fullscreen = config.video.fullscreen;
 
     // let level = levels::Level {
     //     name: String::from("Test Level"),
@@ -85,20 +88,66 @@ fn main() {
     //     ],
     // };
 
-    let media_handle = media::MediaHandle::new(window.factory.clone());
+    let mut media_handle = media::MediaHandle::new(window.factory.clone());
 
-    let level = Level::load(&media_handle, "default.level.json").unwrap();
+    let mut playback = arg_value("--replay").map(|path| match Replay::load(&path) {
+        Ok(replay) => Playback::new(replay),
+        Err(err) => {
+            eprintln!("Error loading replay `{}`: {}", path, err);
+            std::process::exit(1)
+        }
+    });
 
-    let mut world = level.to_world();
+    let load_mode = if std::env::args().any(|arg| arg == "--lenient") {
+        LoadMode::SkipInvalidEntities
+    } else {
+        LoadMode::Strict
+    };
 
-    let mut cam = Camera::new(0.0, 0.0, INIT_WIN_WIDTH, INIT_WIN_HEIGHT, 50.0);
+    let mut levels = exit_on_level_error(LevelManager::load(&media_handle, "campaign.json", load_mode));
+    exit_on_level_error(match playback {
+        Some(ref playback) => levels.jump_to(&media_handle, &playback.replay().level),
+        None => levels.continue_campaign(&media_handle, &stats_handler.get().completed_levels),
+    });
 
-    let mut fonts = render::Fonts::new(&media_handle);
-    level.save(&media_handle, "default.level.json").unwrap();
+    let seed = match playback {
+        Some(ref playback) => Some(playback.replay().seed),
+        None => seed_from_args(),
+    };
+    let mut world = match seed {
+        Some(seed) => levels.level().to_world_with_seed(seed),
+        None => levels.level().to_world(),
+    };
+    println!("Using seed {}", world.seed());
 
-    window.set_ups(60);
+    let record_path = arg_value("--record");
+    let mut recording = record_path
+        .as_ref()
+        .map(|_| Replay::new(levels.level_path(), world.seed()));
+
+    let mut audio = audio::AudioManager::new();
+    audio.set_volumes(&config.audio);
+
+    let mut cam = Camera::new(0.0, 0.0, config.window.width, config.window.height, 50.0);
+    cam.set_dpi_scale(render::dpi_scale(&window));
+
+    let mut fonts = render::Fonts::new(&media_handle);
+    // a leniently loaded level is missing its invalid entities, so writing it back would lose them for good
+    if load_mode == LoadMode::Strict {
+        exit_on_level_error(levels.save_level(&media_handle));
+    }
 
     let mut keys_down = HashSet::new();
+    let mut input = InputFrame::default();
+    let mut pose_drag = None;
+    let mut debug_overlay = false;
+    let mut diagnostics = render::Diagnostics::new();
+    let mut settings_menu = None;
+    let mut level_stats = stat::LevelStats::default();
+    let mut level_results = None;
+    let mut transition = render::Transition::new();
+    transition.fade_in();
+    let mut screenshot_requested = false;
 
     'outer: while let Some(e) = window.next() {
         let mut stats = stats_handler.get();
@@ -110,45 +159,251 @@ fn main() {
             &mut stats,
             &mut fonts,
             &mut keys_down,
+            &media_handle,
+            &mut levels,
+            &mut input,
+            &mut pose_drag,
+            &mut debug_overlay,
+            &mut diagnostics,
+            &mut config,
+            &mut settings_menu,
+            &mut level_stats,
+            &mut level_results,
+            &mut transition,
+            &mut screenshot_requested,
+            &mut playback,
+            &mut recording,
+            &mut audio,
         ) {
             break 'outer;
         }
 
-        if keys_down.contains(&Key::Space) || keys_down.contains(&Key::W) {
-            world.set_player_jumping(true);
-        } else {
-            world.set_player_jumping(false);
+        stats_handler.set(stats);
+
+        // switching in or out of fullscreen needs a new window, and so a new GL context,
+        // which the textures and glyph caches have to be recreated for
+        if config.video.fullscreen != window_is_fullscreen {
+            if !window_is_fullscreen {
+                remember_window(&window, &mut config.window);
+                save_config(&config);
+            }
+
+            window = build_window(&config);
+            window_is_fullscreen = config.video.fullscreen;
+            media_handle = media::MediaHandle::new(window.factory.clone());
+            fonts = render::Fonts::new(&media_handle);
         }
+    }
 
-        stats_handler.set(stats);
+    if !window_is_fullscreen {
+        remember_window(&window, &mut config.window);
+    }
+    save_config(&config);
+
+    if let (Some(recording), Some(path)) = (recording, record_path) {
+        if let Err(err) = recording.save(&path) {
+            eprintln!("Error saving replay `{}`: {}", path, err);
+        }
     }
 
+    world.shutdown();
     stats_handler.finish();
 }
 
+// Escape opens the settings instead of quitting.
+fn build_window(config: &config::Config) -> PistonWindow {
+    let window_config = &config.window;
+    let mut window: PistonWindow = WindowSettings::new("dio", [window_config.width, window_config.height])
+        .exit_on_esc(false)
+        .samples(config.video.samples)
+        .vsync(config.video.vsync)
+        .fullscreen(config.video.fullscreen)
+        .build()
+        .unwrap();
+
+    if let (Some(x), Some(y), false) = (window_config.x, window_config.y, config.video.fullscreen) {
+        window.window.window.set_position(x, y);
+    }
+    window.set_ups(60);
+
+    window
+}
+
+// Keeps the size and position of a window that isn't fullscreen, to open the next one the same way.
+fn remember_window(window: &PistonWindow, window_config: &mut config::WindowConfig) {
+    let size = window.size();
+    window_config.width = size.width;
+    window_config.height = size.height;
+
+    if let Some((x, y)) = window.window.window.get_position() {
+        window_config.x = Some(x);
+        window_config.y = Some(y);
+    }
+}
+
+// When the player widgets don't follow the player, they sit in the bottom right corner.
+fn hud_layout(config: &config::Config) -> render::HudLayout {
+    let anchor = if config.hud.widgets_follow_player {
+        render::WidgetAnchor::Player
+    } else {
+        render::WidgetAnchor::Screen(render::HudPlacement::new(render::Anchor::BottomRight, 20.0, 20.0))
+    };
+    render::HudLayout::default().with_player_widgets(anchor)
+}
+
+fn save_config(config: &config::Config) {
+    if let Err(err) = config.save() {
+        eprintln!("Error saving config: {}", err);
+    }
+}
+
+// Returns the value following `name` on the command line, e.g. `--record run.replay.json`.
+fn arg_value(name: &str) -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    args.iter()
+        .position(|arg| arg == name)
+        .and_then(|i| args.get(i + 1))
+        .cloned()
+}
+
+// Prints a level loading error readably, rather than as the Debug dump unwrap() gives.
+fn exit_on_level_error<T>(result: Result<T, LevelError>) -> T {
+    result.unwrap_or_else(|err| {
+        eprintln!("Error loading level: {}", err);
+        std::process::exit(1)
+    })
+}
+
+// Parses `--seed <n>` from the command line, for reproducing a run.
+fn seed_from_args() -> Option<u32> {
+    arg_value("--seed").map(|seed| seed.parse().expect("--seed must be a number"))
+}
+
 pub const KNIFE_INIT_SPEED: N = 14.0;
 
-fn spawn_knife(world: &mut World, cam: &mut Camera) {
-    let (kx, ky) = cam.screen_to_pos(cam.mouse_x, cam.mouse_y);
+// Camera shake when the player is hurt, and how far away an impact can still be felt.
+const PLAYER_HURT_TRAUMA: N = 0.3;
+const IMPACT_SHAKE_RANGE: N = 15.0;
 
+fn spawn_knife(world: &mut World, kx: N, ky: N) -> Option<specs::Entity> {
     let physics = world.physics_thread_link();
-    let pos = physics
+    let pos = match physics
         .lock()
         .unwrap()
-        .get_position(world.player_rigid_body_id());
+        .get_position(world.player_rigid_body_id())
+    {
+        Ok(pos) => pos,
+        Err(_) => return None,
+    };
     let px = pos.translation.vector.x;
     let py = pos.translation.vector.y;
 
-    let sx = if kx < px {
-        px - PLAYER_HALF_WIDTH * 1.6
-    } else {
-        px + PLAYER_HALF_WIDTH * 1.6
-    };
+    // the knife leaves from the hand on the side the player is facing
+    let sx = px + world.player_facing().sign() * PLAYER_HALF_WIDTH * 1.6;
     let sy = py - PLAYER_HALF_HEIGHT * 0.32;
 
     let vel = Vector::new(kx - sx, ky - sy).normalize() * KNIFE_INIT_SPEED;
 
-    world.player_throw_knife(sx, sy, vel);
+    world.player_throw_knife(sx, sy, vel)
+}
+
+// Rebuilds the world from the level.
+// The seed is kept the same so that replays which include a restart stay in sync.
+fn restart(world: &mut World, level: &Level) {
+    let seed = world.seed();
+    replace_world(world, level.to_world_with_seed(seed));
+}
+
+// Rebuilds the world from the level file on disk, for quickly iterating on levels.
+// The camera is left alone, so the view stays where it was.
+fn reload_level(world: &mut World, levels: &mut LevelManager, media_handle: &media::MediaHandle) {
+    match levels.reload(media_handle) {
+        Ok(()) => {
+            let seed = world.seed();
+            replace_world(world, levels.level().to_world_with_seed(seed));
+            println!("Reloaded level `{}`", levels.level_path());
+        }
+        Err(err) => eprintln!("Error reloading level: {}", err),
+    }
+}
+
+// Things that happen behind a screen transition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SceneChange {
+    NextLevel,
+}
+
+// Moves on to the next level of the campaign, if there is one.
+fn advance_level(world: &mut World, levels: &mut LevelManager, media_handle: &media::MediaHandle, stats: &stat::Stats) {
+    if exit_on_level_error(levels.advance(media_handle, &stats.completed_levels)) {
+        // keep the seed, so that replays spanning several levels stay in sync
+        let seed = levels.level().seed.unwrap_or(world.seed());
+        replace_world(world, levels.level().to_world_with_seed(seed));
+    } else {
+        println!("Campaign complete!");
+    }
+}
+
+fn replace_world(world: &mut World, new_world: World) {
+    let old_world = std::mem::replace(world, new_world);
+    old_world.shutdown();
+}
+
+// Everything the player can do goes through here, so that replays reproduce it exactly.
+fn apply_input(world: &mut World, frame: &InputFrame, stats: &mut stat::Stats, level_stats: &mut stat::LevelStats) {
+    // the dead can only wait for a restart
+    let idle = InputFrame::default();
+    let frame = if world.player_is_dead() {
+        &idle
+    } else {
+        frame
+    };
+
+    world.set_player_moving_left(frame.has(replay::MOVING_LEFT));
+    world.set_player_moving_right(frame.has(replay::MOVING_RIGHT));
+    world.set_player_picking_up(frame.has(replay::PICKING_UP));
+    world.set_player_jumping(frame.has(replay::JUMPING));
+
+    if frame.has(replay::SPAWN_BULLET) {
+        world.new_bullet(Vector::new(0.0, 1.5), 0.08, Vector::new(20.0, 0.0)); // XXX
+    }
+
+    if let Some(index) = frame.select_weapon {
+        world.player_select_weapon(index as usize);
+    }
+
+    if let Some((kx, ky)) = frame.knife_target {
+        world.player_face_towards(kx);
+
+        if world.player_is_carrying() {
+            world.player_throw_carried(kx, ky);
+        } else {
+            match world.player_weapon() {
+                Weapon::Knife => {
+                    if spawn_knife(world, kx, ky).is_some() {
+                        stats.num_knives_spawned += 1;
+                        level_stats.knives_thrown += 1;
+                    }
+                }
+                Weapon::Grenade => {
+                    world.player_throw_grenade(kx, ky);
+                }
+                Weapon::Melee => world.player_melee(kx),
+            }
+        }
+    }
+
+    if let Some((gx, gy)) = frame.grenade_target {
+        world.player_throw_grenade(gx, gy);
+    }
+
+    if let Some((grab, aim)) = frame.pose {
+        world.pose_frozen(grab, aim);
+    }
+
+    if frame.has(replay::STOP_TIME) && world.player_toggle_time_stop() {
+        stats.num_time_stops += 1;
+    }
 }
 
 // if returns false, exit event loop
@@ -160,28 +415,178 @@ fn process_event(
     stats: &mut stat::Stats,
     fonts: &mut render::Fonts,
     keys_down: &mut HashSet<Key>,
+    media_handle: &media::MediaHandle,
+    levels: &mut LevelManager,
+    input: &mut InputFrame,
+    pose_drag: &mut Option<(N, N)>,
+    debug_overlay: &mut bool,
+    diagnostics: &mut render::Diagnostics,
+    config: &mut config::Config,
+    settings_menu: &mut Option<SettingsMenu>,
+    level_stats: &mut stat::LevelStats,
+    level_results: &mut Option<stat::LevelResults>,
+    transition: &mut render::Transition<SceneChange>,
+    screenshot_requested: &mut bool,
+    playback: &mut Option<Playback>,
+    recording: &mut Option<Replay>,
+    audio: &mut audio::AudioManager,
 ) -> bool {
     if let &Input::Update(UpdateArgs { dt }) = event {
-        world.tick(dt as N);
+        if let Some(change) = transition.update(dt) {
+            match change {
+                SceneChange::NextLevel => {
+                    *level_results = None;
+                    advance_level(world, levels, media_handle, stats);
+                }
+            }
+        }
+
+        // paused while the settings or the results of a level are up
+        if settings_menu.is_some() || level_results.is_some() {
+            return true;
+        }
+
+        input.dt = dt as N;
+        input.set(
+            replay::JUMPING,
+            keys_down.contains(&config.controls.jump) || keys_down.contains(&config.controls.alt_jump),
+        );
+
+        // While playing back, live input is ignored (apart from quitting).
+        let frame = match *playback {
+            Some(ref mut playback) => match playback.next_frame() {
+                Some(frame) => frame,
+                None => {
+                    println!("Replay finished");
+                    return false;
+                }
+            },
+            None => *input,
+        };
+        input.clear_actions();
+
+        if levels.level_file_changed(media_handle) {
+            reload_level(world, levels, media_handle);
+            *level_stats = stat::LevelStats::default();
+        }
+
+        if frame.has(replay::RESTART) && world.player_is_dead() {
+            restart(world, levels.level());
+            *level_stats = stat::LevelStats::default();
+            transition.fade_in();
+        }
+
+        if !world.player_is_dead() {
+            level_stats.time += frame.dt as f64;
+        }
+        apply_input(world, &frame, stats, level_stats);
+        if let Some(ref mut recording) = *recording {
+            recording.frames.push(frame);
+        }
+
+        let update_start = Instant::now();
+        world.tick(frame.dt);
+        diagnostics.record_update(update_start.elapsed());
+
+        for event in world.drain_events() {
+            audio.handle_event(&event, cam.pos());
+
+            match event {
+                GameEvent::EntityDied { .. } => {
+                    stats.num_enemies_killed += 1;
+                    level_stats.enemies_killed += 1;
+                }
+                GameEvent::Damaged { entity, .. } => if entity == world.player_entity() {
+                    cam.add_trauma(PLAYER_HURT_TRAUMA);
+                },
+                GameEvent::Impact { position, strength } => {
+                    // fades out with distance from the middle of the screen
+                    let (cx, cy) = cam.pos();
+                    let distance = ((position.x - cx).powi(2) + (position.y - cy).powi(2)).sqrt();
+                    cam.add_trauma(strength * (1.0 - distance / IMPACT_SHAKE_RANGE).max(0.0));
+                }
+                GameEvent::PlayerDied => stats.num_deaths += 1,
+                GameEvent::CoinCollected { .. } => {
+                    stats.num_coins_collected += 1;
+                    level_stats.coins_collected += 1;
+                }
+                GameEvent::PickupCollected { .. } => stats.num_pickups_collected += 1,
+                // only the audio manager cares about these
+                GameEvent::PlaySound { .. }
+                | GameEvent::KnifeThrown
+                | GameEvent::Jumped
+                | GameEvent::TimeStopStarted
+                | GameEvent::TimeStopEnded => {}
+                // the boss's own behaviour is all that changes between phases
+                GameEvent::BossPhaseChanged { .. } => {}
+                GameEvent::LevelComplete => {
+                    println!("Completed level `{}`", levels.display_name());
+                    stats.complete_level(levels.level_path());
+
+                    let results = stat::LevelResults {
+                        level_name: levels.display_name().to_owned(),
+                        stats: std::mem::replace(level_stats, stat::LevelStats::default()),
+                    };
+
+                    // replays can't press a key to move on, so they skip the results
+                    if playback.is_some() {
+                        advance_level(world, levels, media_handle, stats);
+                    } else {
+                        *level_results = Some(results);
+                        transition.fade_in();
+                    }
+                }
+            }
+        }
+
+        audio.update_music(levels.level().music.as_ref().map(|track| &track[..]));
 
         let win_draw_size = window.draw_size();
         cam.set_window_dimensions(win_draw_size.width, win_draw_size.height);
+        cam.set_dpi_scale(render::dpi_scale(window));
         let physics = world.physics_thread_link();
         let pos = physics
             .lock()
             .unwrap()
             .get_position(world.player_rigid_body_id());
-        let px = pos.translation.vector.x;
-        let py = pos.translation.vector.y;
-        cam.set_pos_smooth(px, py);
+        cam.set_bounds(levels.level().camera_bounds.map(|bounds| bounds.edges()));
+        // the camera stays put if the player's body has gone
+        if let Ok(pos) = pos {
+            cam.set_pos_smooth(pos.translation.vector.x, pos.translation.vector.y);
+        }
+        cam.update(dt as N);
 
         stats.total_game_time += dt;
         return true;
     }
 
     match *event {
-        Input::Render(_) => {
-            render::render(window, cam, world, event, fonts);
+        Input::Render(RenderArgs { ext_dt, .. }) => {
+            cam.set_time_since_update(ext_dt as N);
+            diagnostics.frame_rendered();
+            let settings = settings_menu.as_ref().map(|menu| (menu, &*config));
+            render::render(
+                window,
+                cam,
+                world,
+                event,
+                fonts,
+                media_handle,
+                *debug_overlay,
+                diagnostics,
+                &hud_layout(config),
+                settings,
+                level_results.as_ref(),
+                transition.alpha(),
+            );
+
+            if *screenshot_requested {
+                *screenshot_requested = false;
+                match render::take_screenshot(window) {
+                    Ok(path) => println!("Saved screenshot to {}", path.display()),
+                    Err(err) => eprintln!("Error saving screenshot: {}", err),
+                }
+            }
         }
         Input::Resize(w, h) => {
             cam.win_w = w;
@@ -189,30 +594,83 @@ fn process_event(
         }
         Input::Move(ref motion) => match *motion {
             Motion::MouseCursor(x, y) => {
-                cam.mouse_x = x;
-                cam.mouse_y = y;
+                cam.set_mouse_pos(x, y);
             }
+            Motion::MouseScroll(_, dy) => if dy != 0.0 {
+                let step = if dy > 0.0 { -1 } else { 1 };
+                input.select_weapon = Some(world.player_inventory().offset_index(step) as u8);
+            },
             _ => {}
         },
         Input::Press(ref button) => match *button {
             Button::Mouse(mbutton) => {
                 stats.num_clicks += 1;
                 if mbutton == MouseButton::Left {
-                    stats.num_knives_spawned += 1;
-                    spawn_knife(world, cam);
+                    let (x, y) = cam.screen_to_pos(cam.mouse_x, cam.mouse_y);
+
+                    // during time stop, clicking on a frozen knife or bullet starts re-aiming it
+                    if world.poseable_at(x, y).is_some() {
+                        *pose_drag = Some((x, y));
+                    } else {
+                        input.knife_target = Some((x, y));
+                    }
                 }
             }
             Button::Keyboard(key) => {
                 stats.num_key_presses += 1;
+
+                let response = settings_menu.as_mut().map(|menu| menu.handle_key(key, config));
+                match response {
+                    Some(SettingsResponse::Nothing) => return true,
+                    Some(SettingsResponse::Changed) => {
+                        audio.set_volumes(&config.audio);
+                        save_config(config);
+                        return true;
+                    }
+                    Some(SettingsResponse::Close) => {
+                        *settings_menu = None;
+                        return true;
+                    }
+                    None => {}
+                }
+
+                if level_results.is_some() {
+                    if key == Key::Return {
+                        transition.fade_out_then(SceneChange::NextLevel);
+                    }
+                    return true;
+                }
+
                 keys_down.insert(key);
+                let controls = &config.controls;
 
                 match key {
                     Key::Q => return false,
-                    Key::A => world.set_player_moving_left(true),
-                    Key::D => world.set_player_moving_right(true),
-                    Key::C => world.set_player_picking_up(true),
-                    Key::E => {
-                        world.new_bullet(Vector::new(0.0, 1.5), 0.08, Vector::new(20.0, 0.0)); // XXX
+                    Key::Escape => *settings_menu = Some(SettingsMenu::new()),
+                    Key::M => {
+                        config.audio.muted = !config.audio.muted;
+                        audio.set_volumes(&config.audio);
+                        save_config(config);
+                    }
+                    k if k == controls.move_left => input.set(replay::MOVING_LEFT, true),
+                    k if k == controls.move_right => input.set(replay::MOVING_RIGHT, true),
+                    k if k == controls.pick_up => input.set(replay::PICKING_UP, true),
+                    k if k == controls.spawn_bullet => input.set(replay::SPAWN_BULLET, true),
+                    k if k == controls.grenade => input.grenade_target = Some(cam.screen_to_pos(cam.mouse_x, cam.mouse_y)),
+                    k if k == controls.restart => input.set(replay::RESTART, true),
+                    Key::D1 => input.select_weapon = Some(0),
+                    Key::D2 => input.select_weapon = Some(1),
+                    Key::D3 => input.select_weapon = Some(2),
+                    Key::F2 => diagnostics.toggle(),
+                    Key::F3 => *debug_overlay = !*debug_overlay,
+                    Key::F12 => *screenshot_requested = true,
+                    Key::F11 => {
+                        config.video.fullscreen = !config.video.fullscreen;
+                        save_config(config);
+                    }
+                    Key::F5 => {
+                        reload_level(world, levels, media_handle);
+                        *level_stats = stat::LevelStats::default();
                     }
                     _ => {}
                 }
@@ -220,16 +678,18 @@ fn process_event(
             _ => {}
         },
         Input::Release(ref button) => match *button {
+            Button::Mouse(MouseButton::Left) => if let Some(grab) = pose_drag.take() {
+                input.pose = Some((grab, cam.screen_to_pos(cam.mouse_x, cam.mouse_y)));
+            },
             Button::Keyboard(key) => {
                 keys_down.remove(&key);
 
+                let controls = &config.controls;
                 match key {
-                    Key::A => world.set_player_moving_left(false),
-                    Key::D => world.set_player_moving_right(false),
-                    Key::C => world.set_player_picking_up(false),
-                    Key::F => if world.stop_time(5.0) {
-                        stats.num_time_stops += 1;
-                    },
+                    k if k == controls.move_left => input.set(replay::MOVING_LEFT, false),
+                    k if k == controls.move_right => input.set(replay::MOVING_RIGHT, false),
+                    k if k == controls.pick_up => input.set(replay::PICKING_UP, false),
+                    k if k == controls.stop_time => input.set(replay::STOP_TIME, true),
                     _ => {}
                 }
             }
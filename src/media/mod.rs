@@ -1,7 +1,10 @@
 mod image;
 pub use self::image::*;
 
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::path::PathBuf;
+use std::rc::Rc;
 
 use gfx_device_gl::*;
 use piston_window::*;
@@ -10,6 +13,8 @@ pub struct MediaHandle {
     pub base_path: PathBuf,
 
     factory: Factory,
+    // None for images that failed to load, so the error is only printed once
+    textures: RefCell<HashMap<String, Option<Rc<ImageHandle>>>>,
 }
 
 impl MediaHandle {
@@ -17,12 +22,29 @@ impl MediaHandle {
         MediaHandle {
             base_path: PathBuf::from("media/"),
             factory: factory,
+            textures: RefCell::new(HashMap::new()),
         }
     }
 
     pub fn factory_clone(&self) -> Factory {
         self.factory.clone()
     }
+
+    /// Loads the image at `path` in the media directory the first time it is asked for,
+    /// and shares the same texture after that.
+    pub fn get_texture(&self, path: &str) -> Option<Rc<ImageHandle>> {
+        self.textures
+            .borrow_mut()
+            .entry(path.to_owned())
+            .or_insert_with(|| match ImageHandle::new(self, path) {
+                Ok(image) => Some(Rc::new(image)),
+                Err(e) => {
+                    eprintln!("Failed to load image {}: {}", path, e);
+                    None
+                }
+            })
+            .clone()
+    }
 }
 
 
@@ -0,0 +1,39 @@
+use std::time::{Duration, Instant};
+
+/// Frame and update timings for the diagnostics line, measured in the main loop.
+#[derive(Debug, Default)]
+pub struct Diagnostics {
+    pub visible: bool,
+    /// Time between the last two rendered frames.
+    pub frame_time: Duration,
+    /// Time the last update spent ticking the world.
+    pub update_time: Duration,
+    last_frame: Option<Instant>,
+}
+
+impl Diagnostics {
+    pub fn new() -> Self {
+        Diagnostics::default()
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    /// Call once per rendered frame.
+    pub fn frame_rendered(&mut self) {
+        let now = Instant::now();
+        if let Some(last_frame) = self.last_frame {
+            self.frame_time = now - last_frame;
+        }
+        self.last_frame = Some(now);
+    }
+
+    pub fn record_update(&mut self, time: Duration) {
+        self.update_time = time;
+    }
+}
+
+pub fn duration_ms(duration: Duration) -> f64 {
+    duration.as_secs() as f64 * 1000.0 + duration.subsec_nanos() as f64 / 1_000_000.0
+}
@@ -0,0 +1,349 @@
+use piston_window::*;
+
+use std::f64::consts::PI;
+
+use engine::{FontWeight, Weapon, World, PLAYER_HALF_HEIGHT};
+use interface::camera::Camera;
+use render::Fonts;
+
+/// The corner or edge of the window a HUD element is placed against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Anchor {
+    TopLeft,
+    TopCentre,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// Where a HUD element goes, in window pixels. The camera doesn't affect it.
+#[derive(Debug, Clone, Copy)]
+pub struct HudPlacement {
+    pub anchor: Anchor,
+    /// Distance in from the anchor, so positive values always move towards the middle of the window.
+    /// The horizontal offset is ignored for `TopCentre`.
+    pub offset: (f64, f64),
+}
+
+impl HudPlacement {
+    pub fn new(anchor: Anchor, x: f64, y: f64) -> Self {
+        HudPlacement {
+            anchor,
+            offset: (x, y),
+        }
+    }
+
+    /// The top left corner of an element of the given size, in a window of the given size.
+    pub fn top_left(&self, (w, h): (f64, f64), (win_w, win_h): (f64, f64)) -> (f64, f64) {
+        let (ox, oy) = self.offset;
+        match self.anchor {
+            Anchor::TopLeft => (ox, oy),
+            Anchor::TopCentre => ((win_w - w) / 2.0, oy),
+            Anchor::TopRight => (win_w - w - ox, oy),
+            Anchor::BottomLeft => (ox, win_h - h - oy),
+            Anchor::BottomRight => (win_w - w - ox, win_h - h - oy),
+        }
+    }
+}
+
+/// Where the small widgets for the player's knives and time stop go.
+#[derive(Debug, Clone, Copy)]
+pub enum WidgetAnchor {
+    /// Just below the player's feet, following them around the world.
+    Player,
+    Screen(HudPlacement),
+}
+
+/// Where each part of the HUD goes.
+#[derive(Debug, Clone, Copy)]
+pub struct HudLayout {
+    pub time_stop_meter: HudPlacement,
+    pub health: HudPlacement,
+    /// The score, with the weapon slots underneath it.
+    pub inventory: HudPlacement,
+    pub boss_health: HudPlacement,
+    pub message: HudPlacement,
+    /// Knife pips and the time stop ring.
+    pub player_widgets: WidgetAnchor,
+}
+
+impl HudLayout {
+    pub fn with_player_widgets(mut self, anchor: WidgetAnchor) -> Self {
+        self.player_widgets = anchor;
+        self
+    }
+}
+
+impl Default for HudLayout {
+    fn default() -> Self {
+        HudLayout {
+            time_stop_meter: HudPlacement::new(Anchor::TopLeft, 20.0, 20.0),
+            health: HudPlacement::new(Anchor::TopLeft, 20.0, 48.0),
+            inventory: HudPlacement::new(Anchor::BottomLeft, 20.0, 15.0),
+            boss_health: HudPlacement::new(Anchor::TopCentre, 0.0, 8.0),
+            message: HudPlacement::new(Anchor::TopCentre, 0.0, 36.0),
+            player_widgets: WidgetAnchor::Player,
+        }
+    }
+}
+
+const TIME_STOP_METER_HEIGHT: f64 = 20.0;
+const HEALTH_CELL_SIZE: f64 = 16.0;
+const HEALTH_CELL_GAP: f64 = 4.0;
+// At or below this fraction of their hitpoints, the edges of the screen go red.
+const LOW_HEALTH_FRACTION: f64 = 0.34;
+const VIGNETTE_BANDS: usize = 10;
+const VIGNETTE_BAND_WIDTH: f64 = 6.0;
+const SCORE_SIZE: u32 = 18;
+const SLOT_SIZE: u32 = 16;
+const SLOT_SPACING: f64 = 20.0;
+const BOSS_NAME_SIZE: u32 = 16;
+const BOSS_BAR_HEIGHT: f64 = 16.0;
+const MESSAGE_SIZE: u32 = 24;
+const PIP_SIZE: f64 = 4.0;
+const PIP_GAP: f64 = 2.0;
+const RING_RADIUS: f64 = 6.0;
+const RING_THICKNESS: f64 = 2.0;
+// Gap between the player's feet and the widgets under them.
+const WIDGET_DROP: f64 = 6.0;
+
+/// Draws everything that sits on top of the world in screen space: meters, inventory, messages.
+pub fn draw_hud(
+    c: Context,
+    g: &mut G2d,
+    fonts: &mut Fonts,
+    world: &World,
+    cam: &Camera,
+    layout: &HudLayout,
+    win_size: (f64, f64),
+) {
+    let (health, max_health) = world.player_hitpoints();
+    if health > 0 && (health as f64) <= max_health as f64 * LOW_HEALTH_FRACTION {
+        draw_low_health_vignette(c, g, win_size);
+    }
+
+    draw_time_stop_meter(c, g, world, layout.time_stop_meter, win_size);
+    draw_health(c, g, layout.health, win_size, health, max_health, world.player_hit_flash());
+    draw_inventory(c, g, fonts, world, layout.inventory, win_size);
+    if !world.player_is_dead() {
+        draw_player_widgets(c, g, world, cam, fonts.ui_scale, layout.player_widgets, win_size);
+    }
+
+    if let Some((name, current, max)) = world.boss_health() {
+        draw_boss_health(c, g, fonts, layout.boss_health, win_size, &name, current, max);
+    }
+
+    if let Some(message) = world.message() {
+        let width = fonts.width(FontWeight::Bold, MESSAGE_SIZE, message);
+        let (x, y) = layout.message.top_left((width, MESSAGE_SIZE as f64), win_size);
+        fonts.text(
+            FontWeight::Bold,
+            [0.0, 0.0, 0.0, 1.0],
+            MESSAGE_SIZE,
+            message,
+            c.transform.trans(x, y + MESSAGE_SIZE as f64),
+            g,
+        );
+    }
+}
+
+// A fifth of the window wide, filling up as the meter recharges.
+fn draw_time_stop_meter(c: Context, g: &mut G2d, world: &World, placement: HudPlacement, win_size: (f64, f64)) {
+    let meter = world.time_stop_meter();
+    let full_width = 0.2 * win_size.0;
+    let (x, y) = placement.top_left((full_width, TIME_STOP_METER_HEIGHT), win_size);
+    let color = if world.time_stop_remaining().is_some() {
+        [0.5, 0.7, 1.0, 1.0]
+    } else {
+        [0.5, 0.7, 1.0, 0.5]
+    };
+
+    rectangle([0.0, 0.0, 0.0, 0.15], [x, y, full_width, TIME_STOP_METER_HEIGHT], c.transform, g);
+    rectangle(
+        color,
+        [x, y, meter.fraction() as f64 * full_width, TIME_STOP_METER_HEIGHT],
+        c.transform,
+        g,
+    );
+}
+
+// One square per hitpoint, red while it's left. They all flash white when the player is hurt.
+fn draw_health(c: Context, g: &mut G2d, placement: HudPlacement, win_size: (f64, f64), current: u16, max: u16, flashing: bool) {
+    let width = (HEALTH_CELL_SIZE + HEALTH_CELL_GAP) * max as f64 - HEALTH_CELL_GAP;
+    let (x, y) = placement.top_left((width, HEALTH_CELL_SIZE), win_size);
+
+    for i in 0..max {
+        let color = if i >= current {
+            [0.0, 0.0, 0.0, 0.15]
+        } else if flashing {
+            [1.0, 0.8, 0.8, 1.0]
+        } else {
+            [0.85, 0.1, 0.1, 1.0]
+        };
+
+        let cell_x = x + (HEALTH_CELL_SIZE + HEALTH_CELL_GAP) * i as f64;
+        rectangle(color, [cell_x, y, HEALTH_CELL_SIZE, HEALTH_CELL_SIZE], c.transform, g);
+    }
+}
+
+// Red edges fading towards the middle of the window, built up from nested frames.
+fn draw_low_health_vignette(c: Context, g: &mut G2d, (w, h): (f64, f64)) {
+    for i in 0..VIGNETTE_BANDS {
+        let inset = VIGNETTE_BAND_WIDTH * i as f64;
+        let alpha = 0.3 * (1.0 - i as f32 / VIGNETTE_BANDS as f32);
+        let color = [0.8, 0.0, 0.0, alpha];
+        let band = VIGNETTE_BAND_WIDTH;
+
+        rectangle(color, [inset, inset, w - inset * 2.0, band], c.transform, g);
+        rectangle(color, [inset, h - inset - band, w - inset * 2.0, band], c.transform, g);
+        rectangle(color, [inset, inset + band, band, h - (inset + band) * 2.0], c.transform, g);
+        rectangle(color, [w - inset - band, inset + band, band, h - (inset + band) * 2.0], c.transform, g);
+    }
+}
+
+// The score, then one line per weapon slot with the selected one darker.
+fn draw_inventory(c: Context, g: &mut G2d, fonts: &mut Fonts, world: &World, placement: HudPlacement, win_size: (f64, f64)) {
+    let inventory = world.player_inventory();
+    let slots = inventory.slots();
+
+    // the first slot sits a line and a bit below the score
+    let slots_top = SCORE_SIZE as f64 + 25.0;
+    let height = slots_top + SLOT_SPACING * slots.len() as f64 - 15.0;
+    let (x, y) = placement.top_left((200.0, height), win_size);
+
+    fonts.text(
+        FontWeight::Bold,
+        [0.0, 0.0, 0.0, 1.0],
+        SCORE_SIZE,
+        &format!("Score: {}", world.score().points),
+        c.transform.trans(x, y + SCORE_SIZE as f64),
+        g,
+    );
+
+    for (i, slot) in slots.iter().enumerate() {
+        let slot_text = match slot.ammo {
+            Some(ammo) => format!("{} {}: {}/{}", i + 1, slot.weapon.name(), ammo.current, ammo.max),
+            None => format!("{} {}", i + 1, slot.weapon.name()),
+        };
+        let color = if i == inventory.selected_index() {
+            [0.0, 0.0, 0.0, 1.0]
+        } else {
+            [0.0, 0.0, 0.0, 0.4]
+        };
+
+        fonts.text(
+            FontWeight::Bold,
+            color,
+            SLOT_SIZE,
+            &slot_text,
+            c.transform.trans(x, y + slots_top + SLOT_SPACING * i as f64),
+            g,
+        );
+    }
+}
+
+// A pip per knife, dimmed once thrown, then a ring for the time stop meter while it isn't full.
+fn draw_player_widgets(
+    c: Context,
+    g: &mut G2d,
+    world: &World,
+    cam: &Camera,
+    ui_scale: f64,
+    anchor: WidgetAnchor,
+    win_size: (f64, f64),
+) {
+    let knives = world
+        .player_inventory()
+        .slots()
+        .iter()
+        .find(|slot| slot.weapon == Weapon::Knife)
+        .and_then(|slot| slot.ammo);
+    let meter = world.time_stop_meter();
+    let show_ring = meter.fraction() < 1.0 || world.time_stop_remaining().is_some();
+
+    let pips_w = knives.map_or(0.0, |ammo| (PIP_SIZE + PIP_GAP) * ammo.max as f64);
+    let ring_w = if show_ring { RING_RADIUS * 2.0 } else { 0.0 };
+    let size = (pips_w + ring_w, RING_RADIUS * 2.0);
+    if size.0 == 0.0 {
+        return;
+    }
+
+    let (x, y) = match anchor {
+        WidgetAnchor::Player => {
+            let (px, py) = match world.player_render_pos(cam.interpolation_alpha()) {
+                Some(pos) => pos,
+                None => return,
+            };
+            // the camera works in drawn pixels, and the HUD in UI units
+            let (sx, sy) = cam.pos_to_screen(px, py);
+            let (sx, sy) = (sx / ui_scale, sy / ui_scale);
+            let feet = sy + cam.metres_to_pixels(PLAYER_HALF_HEIGHT) / ui_scale;
+            (sx - size.0 / 2.0, feet + WIDGET_DROP)
+        }
+        WidgetAnchor::Screen(placement) => placement.top_left(size, win_size),
+    };
+
+    if let Some(ammo) = knives {
+        let pip_y = y + (size.1 - PIP_SIZE) / 2.0;
+        for i in 0..ammo.max {
+            let color = if i < ammo.current {
+                [0.2, 0.2, 0.2, 0.9]
+            } else {
+                [0.2, 0.2, 0.2, 0.25]
+            };
+            let pip_x = x + (PIP_SIZE + PIP_GAP) * i as f64;
+            rectangle(color, [pip_x, pip_y, PIP_SIZE, PIP_SIZE], c.transform, g);
+        }
+    }
+
+    if show_ring {
+        let rect = [x + pips_w, y, RING_RADIUS * 2.0, RING_RADIUS * 2.0];
+        let color = if world.time_stop_remaining().is_some() {
+            [0.5, 0.7, 1.0, 1.0]
+        } else {
+            [0.5, 0.7, 1.0, 0.7]
+        };
+
+        circle_arc([0.0, 0.0, 0.0, 0.15], RING_THICKNESS / 2.0, 0.0, 2.0 * PI - 0.001, rect, c.transform, g);
+        let fraction = meter.fraction() as f64;
+        if fraction > 0.0 {
+            // clockwise from the top
+            circle_arc(color, RING_THICKNESS / 2.0, -PI / 2.0, -PI / 2.0 + 2.0 * PI * fraction, rect, c.transform, g);
+        }
+    }
+}
+
+// A bar three fifths of the window wide, with the boss's name above it.
+fn draw_boss_health(
+    c: Context,
+    g: &mut G2d,
+    fonts: &mut Fonts,
+    placement: HudPlacement,
+    win_size: (f64, f64),
+    name: &str,
+    current: u16,
+    max: u16,
+) {
+    let bar_w = win_size.0 * 0.6;
+    let bar_top = BOSS_NAME_SIZE as f64 + 6.0;
+    let (x, y) = placement.top_left((bar_w, bar_top + BOSS_BAR_HEIGHT), win_size);
+    let fraction = current as f64 / max as f64;
+
+    rectangle([0.2, 0.0, 0.0, 0.8], [x, y + bar_top, bar_w, BOSS_BAR_HEIGHT], c.transform, g);
+    rectangle(
+        [0.8, 0.0, 0.0, 1.0],
+        [x, y + bar_top, bar_w * fraction, BOSS_BAR_HEIGHT],
+        c.transform,
+        g,
+    );
+
+    let width = fonts.width(FontWeight::Bold, BOSS_NAME_SIZE, name);
+    fonts.text(
+        FontWeight::Bold,
+        [0.0, 0.0, 0.0, 1.0],
+        BOSS_NAME_SIZE,
+        name,
+        c.transform.trans(x + (bar_w - width) / 2.0, y + BOSS_NAME_SIZE as f64),
+        g,
+    );
+}
@@ -0,0 +1,69 @@
+use piston_window::*;
+
+use engine::{Light, N};
+use interface::camera::Camera;
+
+/// Size in pixels of the squares the darkness is worked out over. Smaller is smoother but slower.
+const CELL_SIZE: f64 = 12.0;
+/// How strongly coloured lights tint the darkness around them.
+const GLOW: f32 = 0.6;
+
+/// A light at its place in the world.
+#[derive(Debug, Clone, Copy)]
+pub struct PlacedLight {
+    pub x: N,
+    pub y: N,
+    pub light: Light,
+}
+
+/// Darkens the screen down to the `ambient` level, except around the lights.
+/// The brightness is worked out per cell on the CPU and drawn as a grid of translucent squares.
+pub fn draw_lighting(c: Context, g: &mut G2d, cam: &Camera, ambient: f32, lights: &[PlacedLight], (w, h): (f64, f64)) {
+    // in screen space, leaving out anything that can't reach the screen
+    let lights: Vec<(f64, f64, &Light)> = lights
+        .iter()
+        .filter_map(|placed| {
+            let (x, y) = cam.pos_to_screen(placed.x, placed.y);
+            let radius = cam.metres_to_pixels(placed.light.radius);
+            if x + radius < 0.0 || x - radius > w || y + radius < 0.0 || y - radius > h {
+                None
+            } else {
+                Some((x, y, &placed.light))
+            }
+        })
+        .collect();
+
+    let mut y = 0.0;
+    while y < h {
+        let mut x = 0.0;
+        while x < w {
+            let (cx, cy) = (x + CELL_SIZE / 2.0, y + CELL_SIZE / 2.0);
+
+            let mut brightness = ambient;
+            let mut tint = [0.0f32; 3];
+            for &(lx, ly, light) in &lights {
+                let distance = cam.pixels_to_metres(((cx - lx).powi(2) + (cy - ly).powi(2)).sqrt());
+                let amount = light.falloff(distance);
+                if amount > 0.0 {
+                    brightness += amount;
+                    for i in 0..3 {
+                        tint[i] += light.color[i] * amount * GLOW;
+                    }
+                }
+            }
+
+            let darkness = 1.0 - brightness.min(1.0);
+            if darkness > 0.0 {
+                rectangle(
+                    [tint[0].min(1.0), tint[1].min(1.0), tint[2].min(1.0), darkness],
+                    [x, y, CELL_SIZE, CELL_SIZE],
+                    c.transform,
+                    g,
+                );
+            }
+
+            x += CELL_SIZE;
+        }
+        y += CELL_SIZE;
+    }
+}
@@ -1,2 +1,17 @@
 mod render;
 pub use render::render::*;
+
+mod diagnostics;
+pub use render::diagnostics::*;
+
+mod hud;
+pub use render::hud::*;
+
+mod lighting;
+pub use render::lighting::*;
+
+mod transition;
+pub use render::transition::*;
+
+mod screenshot;
+pub use render::screenshot::*;
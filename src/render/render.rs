@@ -3,14 +3,40 @@ use piston_window::character::CharacterCache;
 use specs::Join;
 
 use engine::World;
-use engine::{Hitpoints, Name, RenderItem, RenderItemKind, Renderable, N};
+use engine::{DebugShapeKind, Facing, FontWeight, Hitpoints, HitFlash, Invulnerable, Light, PhysicsDebugView, PhysicsStats, TileLayer, WorldCounts, Name, RenderItem, RenderItemKind, Renderable, TextStyle, N};
 use interface::camera::Camera;
 use media::*;
-
-pub fn render(win: &mut PistonWindow, cam: &Camera, world: &mut World, input: &Input, fonts: &mut Fonts) {
+use config::Config;
+use interface::settings::SettingsMenu;
+use stat::LevelResults;
+use render::{draw_hud, draw_lighting, duration_ms, Diagnostics, HudLayout, PlacedLight};
+
+pub fn render(
+    win: &mut PistonWindow,
+    cam: &Camera,
+    world: &mut World,
+    input: &Input,
+    fonts: &mut Fonts,
+    media: &MediaHandle,
+    debug_overlay: bool,
+    diagnostics: &Diagnostics,
+    hud_layout: &HudLayout,
+    settings: Option<(&SettingsMenu, &Config)>,
+    level_results: Option<&LevelResults>,
+    transition_alpha: f32,
+) {
     let win_draw_size = win.draw_size();
+    let counts = if diagnostics.visible { Some(world.counts()) } else { None };
+    let physics_stats = if debug_overlay { Some(world.physics_stats()) } else { None };
+    let physics_debug = if debug_overlay { Some(world.physics_debug_view()) } else { None };
+    let dpi = dpi_scale(win);
+    fonts.ui_scale = dpi;
 
     win.draw_2d(input, |c, g| {
+        // the world is drawn in drawn pixels, which are smaller than window coordinates on HiDPI screens
+        let ui = c;
+        let c = c.scale(1.0 / dpi, 1.0 / dpi);
+
         clear([0.0; 4], g);
 
         rectangle(
@@ -20,131 +46,536 @@ pub fn render(win: &mut PistonWindow, cam: &Camera, world: &mut World, input: &I
             g,
         );
 
-        for (entity, renderable) in (&*world.entities(), &world.read_component::<Renderable>()).join() {
-            let x = renderable.x;
-            let y = renderable.y;
-            let rotation = renderable.rotation;
+        for layer in &world.tile_layers().0 {
+            draw_tile_layer(c, g, cam, media, layer);
+        }
+
+        let alpha = cam.interpolation_alpha();
+        let hit_flashc = world.read_component::<HitFlash>();
+        let invulnerablec = world.read_component::<Invulnerable>();
+        let facingc = world.read_component::<Facing>();
+
+        let (cam_x, cam_y) = cam.pos();
+        let (view_w, view_h) = cam.game_viewport_size();
+        let (view_hw, view_hh) = (view_w / 2.0, view_h / 2.0);
+
+        // back to front; the sort is stable, so things on the same layer keep their usual order
+        let renderablec = world.read_component::<Renderable>();
+        let mut draws = Vec::new();
+        for (entity, renderable) in (&*world.entities(), &renderablec).join() {
+            if invulnerablec.get(entity).map_or(false, |invulnerable| invulnerable.blink_hidden()) {
+                continue;
+            }
 
+            let transform = renderable.interpolated(alpha);
             for item in &renderable.items {
-                let &RenderItem {
-                    rel_x,
-                    rel_y,
-                    rel_rotation,
-                    color,
-                    ..
-                } = item;
-
-                if rel_rotation != 0.0 {
-                    eprintln!("Relative rendering rotations don't work yet!");
+                // skip anything entirely off screen
+                let (x, y, rotation) = transform;
+                if let Some((hw, hh)) = item.half_extents(rotation) {
+                    if (x + item.rel_x - cam_x).abs() > view_hw + hw || (y + item.rel_y - cam_y).abs() > view_hh + hh {
+                        continue;
+                    }
                 }
 
-                let abs_x = x + rel_x;
-                let abs_y = y + rel_y;
+                draws.push((renderable.layer + item.layer, entity, transform, item));
+            }
+        }
+        draws.sort_by_key(|&(layer, ..)| layer);
+
+        for (_, entity, (x, y, rotation), item) in draws {
+            let flashing = hit_flashc.get(entity).is_some();
+            let &RenderItem {
+                rel_x,
+                rel_y,
+                rel_rotation,
+                color,
+                ..
+            } = item;
+
+            if rel_rotation != 0.0 {
+                eprintln!("Relative rendering rotations don't work yet!");
+            }
 
-                match item.kind {
-                    RenderItemKind::Rectangle { w, h } => {
-                        fill_rectangle(c, g, cam, color, abs_x, abs_y, w, h, rotation);
-                    }
-                    RenderItemKind::Ellipse { w, h } => {
-                        fill_ellipse(c, g, cam, color, abs_x, abs_y, w, h, rotation);
-                    }
-                    RenderItemKind::Text { ref text, size } => {
-                        let args = DrawTextArgs {
-                            color,
-                            x: abs_x,
-                            y: abs_y,
-                            center_coords: true,
-                            scale: true,
-                            size,
-                            rot: rotation,
-                            origin_x: x,
-                            origin_y: y,
-                        };
-
-                        draw_text(c, g, cam, fonts, &text, args);
+            // things that were just hit flash white
+            let color = if flashing {
+                [
+                    color[0] + (1.0 - color[0]) * 0.7,
+                    color[1] + (1.0 - color[1]) * 0.7,
+                    color[2] + (1.0 - color[2]) * 0.7,
+                    color[3],
+                ]
+            } else {
+                color
+            };
+
+            let abs_x = x + rel_x;
+            let abs_y = y + rel_y;
+
+            match item.kind {
+                RenderItemKind::Rectangle { w, h } => {
+                    fill_rectangle(c, g, cam, color, abs_x, abs_y, w, h, rotation);
+                }
+                RenderItemKind::Ellipse { w, h } => {
+                    fill_ellipse(c, g, cam, color, abs_x, abs_y, w, h, rotation);
+                }
+                RenderItemKind::Polygon { ref points } => {
+                    fill_polygon(c, g, cam, color, abs_x, abs_y, points, rotation);
+                }
+                RenderItemKind::Line { end, width } => {
+                    draw_line(c, g, cam, color, abs_x, abs_y, end, width, rotation);
+                }
+                RenderItemKind::Arc {
+                    radius,
+                    thickness,
+                    start,
+                    sweep,
+                } => {
+                    draw_arc(c, g, cam, color, abs_x, abs_y, radius, thickness, start, sweep, rotation);
+                }
+                RenderItemKind::Sprite {
+                    ref image,
+                    w,
+                    h,
+                    flip_x,
+                    source,
+                } => {
+                    let facing_left = facingc.get(entity) == Some(&Facing::Left);
+                    let flip_x = flip_x != facing_left;
+                    fill_sprite(c, g, cam, media, color, image, source, abs_x, abs_y, w, h, flip_x, rotation);
+                }
+                RenderItemKind::Text { ref text, size, style } => {
+                    let args = DrawTextArgs {
+                        color,
+                        x: abs_x,
+                        y: abs_y,
+                        center_coords: true,
+                        scale: true,
+                        size,
+                        rot: rotation,
+                        origin_x: x,
+                        origin_y: y,
+                        style,
+                    };
+
+                    draw_text(c, g, cam, fonts, &text, args);
+                }
+                RenderItemKind::Info => {
+                    let hitpointsc = world.read_component::<Hitpoints>();
+                    let hp = hitpointsc.get(entity);
+
+                    let namec = world.read_component::<Name>();
+                    let name = namec.get(entity);
+
+                    let mut abs_y = abs_y;
+
+                    let mut args = DrawTextArgs {
+                        color,
+                        x: abs_x,
+                        y: abs_y,
+                        center_coords: true,
+                        scale: true,
+                        size: 14,
+                        rot: rotation,
+                        origin_x: x,
+                        origin_y: y,
+                        // in-world labels are lighter than the UI, and outlined to stand out from the level
+                        style: TextStyle::regular().with_outline([1.0, 1.0, 1.0, 0.6]),
+                    };
+
+                    if let Some(hp) = hp {
+                        draw_text(
+                            c,
+                            g,
+                            cam,
+                            fonts,
+                            &format!("{}/{}", hp.current(), hp.max()),
+                            args,
+                        );
+                        abs_y -= cam.pixels_to_metres(16.0 * fonts.ui_scale);
+                        args.y = abs_y;
                     }
-                    RenderItemKind::Info => {
-                        let hitpointsc = world.read_component::<Hitpoints>();
-                        let hp = hitpointsc.get(entity);
-
-                        let namec = world.read_component::<Name>();
-                        let name = namec.get(entity);
-
-                        let mut abs_y = abs_y;
-
-                        let mut args = DrawTextArgs {
-                            color,
-                            x: abs_x,
-                            y: abs_y,
-                            center_coords: true,
-                            scale: true,
-                            size: 14,
-                            rot: rotation,
-                            origin_x: x,
-                            origin_y: y,
-                        };
-
-                        if let Some(hp) = hp {
-                            draw_text(
-                                c,
-                                g,
-                                cam,
-                                fonts,
-                                &format!("{}/{}", hp.current(), hp.max()),
-                                args,
-                            );
-                            abs_y -= cam.pixels_to_metres(16.0);
-                            args.y = abs_y;
-                        }
-
-                        if let Some(name) = name {
-                            draw_text(c, g, cam, fonts, &format!("{}", name.0), args);
-                        }
+
+                    if let Some(name) = name {
+                        draw_text(c, g, cam, fonts, &format!("{}", name.0), args);
                     }
                 }
             }
         }
 
-        if let Some(time_stop_remaining) = world.time_stop_remaining() {
-            let width = time_stop_remaining as f64 / 5.0 * 0.2 * win_draw_size.width as f64;
+        for particle in world.effect_particles().iter() {
+            let pos = particle.interpolated(alpha);
+            fill_rectangle(c, g, cam, particle.color, pos.x, pos.y, particle.size, particle.size, 0.0);
+        }
 
-            rectangle(
-                [0.5, 0.7, 1.0, 1.0],
-                [20.0, 20.0, width, 20.0],
+        if let Some(ambient) = world.ambient_light() {
+            let lightc = world.read_component::<Light>();
+            let lights: Vec<_> = (&renderablec, &lightc)
+                .join()
+                .map(|(renderable, &light)| {
+                    let (x, y, _) = renderable.interpolated(alpha);
+                    PlacedLight { x, y, light }
+                })
+                .collect();
+
+            draw_lighting(
+                c,
+                g,
+                cam,
+                ambient,
+                &lights,
+                (win_draw_size.width as f64, win_draw_size.height as f64),
+            );
+        }
+
+        if let Some(ref physics_debug) = physics_debug {
+            draw_physics_debug(c, g, cam, physics_debug);
+        }
+
+        // where frozen knives and bullets will go when time starts again
+        for (pos, vel) in world.frozen_velocities() {
+            let end = pos + vel * 0.15;
+            let (x1, y1) = cam.pos_to_screen(pos.x, pos.y);
+            let (x2, y2) = cam.pos_to_screen(end.x, end.y);
+
+            line([0.5, 0.7, 1.0, 0.8], 1.5, [x1, y1, x2, y2], c.transform, g);
+            ellipse(
+                [0.5, 0.7, 1.0, 0.8],
+                ellipse::circle(x2, y2, 3.0),
                 c.transform,
                 g,
             );
         }
 
-        let player = world.clone_player_component();
-        let knives_text = &format!(
-            "Knives: {}/{}",
-            player.num_knives(),
-            player.max_num_knives()
-        );
-        let width = fonts.bold.glyphs.width(16, knives_text);
+        // the HUD and menus are laid out in UI units, which are window coordinates,
+        // so they're the same size whatever the pixel density
+        let (w, h) = (win_draw_size.width as f64 / dpi, win_draw_size.height as f64 / dpi);
 
-        text(
+        draw_hud(ui, g, fonts, world, cam, hud_layout, (w, h));
+
+        if !world.player_is_dead() {
+            draw_crosshair(ui, g, cam.mouse_x / dpi, cam.mouse_y / dpi);
+        }
+
+        if let (&Some(ref physics_stats), &Some(ref physics_debug)) = (&physics_stats, &physics_debug) {
+            draw_physics_stats(ui, g, fonts, w, physics_stats, physics_debug.player_on_ground);
+        }
+
+        if let Some(ref counts) = counts {
+            draw_diagnostics(ui, g, fonts, diagnostics, counts);
+        }
+
+        if let Some((menu, config)) = settings {
+            draw_settings(ui, g, fonts, w, h, menu, config);
+        } else if let Some(results) = level_results {
+            draw_results(ui, g, fonts, w, h, results);
+        } else if world.player_is_dead() {
+            draw_overlay(ui, g, fonts, w, h, "Game over", "Press R to restart");
+        } else if world.level_complete() {
+            draw_overlay(ui, g, fonts, w, h, "Level complete", "");
+        }
+
+        // last, so that it covers everything
+        if transition_alpha > 0.0 {
+            rectangle([0.0, 0.0, 0.0, transition_alpha], [0.0, 0.0, w, h], ui.transform, g);
+        }
+    });
+}
+
+// Shows where the mouse is aiming, at (x, y) on the screen.
+fn draw_crosshair(c: Context, g: &mut G2d, x: f64, y: f64) {
+    let color = [0.1, 0.1, 0.1, 0.8];
+    let (gap, len) = (4.0, 8.0);
+
+    line(color, 1.0, [x - gap - len, y, x - gap, y], c.transform, g);
+    line(color, 1.0, [x + gap, y, x + gap + len, y], c.transform, g);
+    line(color, 1.0, [x, y - gap - len, x, y - gap], c.transform, g);
+    line(color, 1.0, [x, y + gap, x, y + gap + len], c.transform, g);
+    ellipse(color, ellipse::circle(x, y, 1.5), c.transform, g);
+}
+
+// Collider outlines, contacts with their normals, and velocities as arrows, over the top of the world.
+fn draw_physics_debug(c: Context, g: &mut G2d, cam: &Camera, view: &PhysicsDebugView) {
+    for shape in &view.shapes {
+        let color = match shape.kind {
+            DebugShapeKind::Static => [0.2, 0.2, 0.2, 0.8],
+            DebugShapeKind::Dynamic => [0.0, 0.3, 1.0, 0.8],
+            DebugShapeKind::Sensor { touching: false } => [1.0, 0.6, 0.0, 0.8],
+            DebugShapeKind::Sensor { touching: true } => [0.0, 0.8, 0.0, 0.8],
+        };
+
+        let points: Vec<(f64, f64)> = shape.outline.iter().map(|p| cam.pos_to_screen(p.x, p.y)).collect();
+        for i in 0..points.len() {
+            let (x1, y1) = points[i];
+            let (x2, y2) = points[(i + 1) % points.len()];
+            line(color, 0.5, [x1, y1, x2, y2], c.transform, g);
+        }
+    }
+
+    for contact in &view.contacts {
+        let (x, y) = cam.pos_to_screen(contact.position1.x, contact.position1.y);
+        let end = contact.position1 + contact.normal * 0.3;
+        let (ex, ey) = cam.pos_to_screen(end.x, end.y);
+
+        ellipse([1.0, 0.0, 0.0, 0.9], ellipse::circle(x, y, 2.5), c.transform, g);
+        line([1.0, 0.0, 0.0, 0.9], 0.5, [x, y, ex, ey], c.transform, g);
+    }
+
+    for &(pos, vel) in &view.velocities {
+        let end = pos + vel * 0.1;
+        let (x1, y1) = cam.pos_to_screen(pos.x, pos.y);
+        let (x2, y2) = cam.pos_to_screen(end.x, end.y);
+        if (x2 - x1).abs() < 1.0 && (y2 - y1).abs() < 1.0 {
+            continue;
+        }
+
+        let arrow = Line::new([0.6, 0.0, 0.8, 0.8], 0.5);
+        arrow.draw_arrow([x1, y1, x2, y2], 5.0, &c.draw_state, c.transform, g);
+    }
+}
+
+// Physics thread counters, in the top right corner.
+fn draw_physics_stats(c: Context, g: &mut G2d, fonts: &mut Fonts, w: f64, stats: &PhysicsStats, player_on_ground: bool) {
+    let lines = [
+        format!("step: {:.2} ms", duration_ms(stats.step_time)),
+        format!("messages: {}", stats.messages),
+        format!("contacts: {}", stats.contacts),
+        format!("bodies: {}", stats.bodies),
+        format!("sensors: {}", stats.sensors),
+        format!("on ground: {}", if player_on_ground { "yes" } else { "no" }),
+    ];
+
+    for (i, line) in lines.iter().enumerate() {
+        let width = fonts.width(FontWeight::Regular, 14, line);
+        fonts.text(
+            FontWeight::Regular,
             [0.0, 0.0, 0.0, 1.0],
-            18,
-            knives_text,
-            &mut fonts.bold.glyphs,
-            c.transform.trans(20.0, win_draw_size.height as f64 - 20.0),
+            14,
+            line,
+            c.transform.trans(w - width - 20.0, 30.0 + 18.0 * i as f64),
             g,
         );
-    });
+    }
+}
+
+// Timings and counts on one line, under the HUD in the top left.
+fn draw_diagnostics(c: Context, g: &mut G2d, fonts: &mut Fonts, diagnostics: &Diagnostics, counts: &WorldCounts) {
+    let frame_ms = duration_ms(diagnostics.frame_time);
+    let fps = if frame_ms > 0.0 { 1000.0 / frame_ms } else { 0.0 };
+    let line = format!(
+        "frame: {:.1} ms ({:.0} fps)  update: {:.2} ms  entities: {}  bodies: {}  particles: {} + {}",
+        frame_ms,
+        fps,
+        duration_ms(diagnostics.update_time),
+        counts.entities,
+        counts.bodies,
+        counts.particles,
+        counts.effect_particles,
+    );
+
+    fonts.text(
+        FontWeight::Regular,
+        [0.0, 0.0, 0.0, 1.0],
+        14,
+        &line,
+        c.transform.trans(20.0, 90.0),
+        g,
+    );
+}
+
+// The settings list, one item per line with the selected one highlighted.
+fn draw_settings(c: Context, g: &mut G2d, fonts: &mut Fonts, w: f64, h: f64, menu: &SettingsMenu, config: &Config) {
+    rectangle([0.0, 0.0, 0.0, 0.75], [0.0, 0.0, w, h], c.transform, g);
+
+    let title = "Settings";
+    let width = fonts.width(FontWeight::Bold, 32, title);
+    fonts.text(
+        FontWeight::Bold,
+        [1.0, 1.0, 1.0, 1.0],
+        32,
+        title,
+        c.transform.trans((w - width) / 2.0, 60.0),
+        g,
+    );
+
+    let (name_x, value_x) = (w / 2.0 - 220.0, w / 2.0 + 80.0);
+    for i in 0..menu.items().len() {
+        let (name, value) = menu.label(i, config);
+        let y = 110.0 + 26.0 * i as f64;
+        let color = if i == menu.selected() {
+            [1.0, 0.8, 0.1, 1.0]
+        } else {
+            [1.0, 1.0, 1.0, 0.8]
+        };
+
+        fonts.text(FontWeight::Regular, color, 18, &name, c.transform.trans(name_x, y), g);
+        fonts.text(FontWeight::Bold, color, 18, &value, c.transform.trans(value_x, y), g);
+    }
+
+    let hint = "Up/Down to choose, Left/Right to change, Enter to rebind a key, Escape to go back";
+    let width = fonts.width(FontWeight::Regular, 14, hint);
+    fonts.text(
+        FontWeight::Regular,
+        [1.0, 1.0, 1.0, 0.6],
+        14,
+        hint,
+        c.transform.trans((w - width) / 2.0, h - 20.0),
+        g,
+    );
+}
+
+// What the player did in the level they just finished.
+fn draw_results(c: Context, g: &mut G2d, fonts: &mut Fonts, w: f64, h: f64, results: &LevelResults) {
+    rectangle([0.0, 0.0, 0.0, 0.75], [0.0, 0.0, w, h], c.transform, g);
+
+    let title = format!("{} complete", results.level_name);
+    let width = fonts.width(FontWeight::Bold, 40, &title);
+    fonts.text(
+        FontWeight::Bold,
+        [1.0, 1.0, 1.0, 1.0],
+        40,
+        &title,
+        c.transform.trans((w - width) / 2.0, h / 2.0 - 100.0),
+        g,
+    );
+
+    let stats = &results.stats;
+    let minutes = (stats.time / 60.0).floor();
+    let lines = [
+        ("Time", format!("{}:{:05.2}", minutes, stats.time - minutes * 60.0)),
+        ("Knives thrown", stats.knives_thrown.to_string()),
+        ("Enemies killed", stats.enemies_killed.to_string()),
+        ("Coins collected", stats.coins_collected.to_string()),
+    ];
+
+    for (i, &(name, ref value)) in lines.iter().enumerate() {
+        let y = h / 2.0 - 40.0 + 30.0 * i as f64;
+        fonts.text(FontWeight::Regular, [1.0, 1.0, 1.0, 0.8], 20, name, c.transform.trans(w / 2.0 - 160.0, y), g);
+        fonts.text(FontWeight::Bold, [1.0, 1.0, 1.0, 1.0], 20, value, c.transform.trans(w / 2.0 + 80.0, y), g);
+    }
+
+    let hint = "Press Enter to continue";
+    let width = fonts.width(FontWeight::Regular, 20, hint);
+    fonts.text(
+        FontWeight::Regular,
+        [1.0, 1.0, 1.0, 1.0],
+        20,
+        hint,
+        c.transform.trans((w - width) / 2.0, h / 2.0 + 120.0),
+        g,
+    );
+}
+
+// Darkens the whole window and shows a title with a hint underneath it.
+fn draw_overlay(c: Context, g: &mut G2d, fonts: &mut Fonts, w: f64, h: f64, title: &str, hint: &str) {
+    rectangle([0.0, 0.0, 0.0, 0.6], [0.0, 0.0, w, h], c.transform, g);
+
+    let width = fonts.width(FontWeight::Bold, 48, title);
+    fonts.text(
+        FontWeight::Bold,
+        [1.0, 1.0, 1.0, 1.0],
+        48,
+        title,
+        c.transform.trans((w - width) / 2.0, h / 2.0),
+        g,
+    );
+
+    let width = fonts.width(FontWeight::Regular, 20, hint);
+    fonts.text(
+        FontWeight::Regular,
+        [1.0, 1.0, 1.0, 1.0],
+        20,
+        hint,
+        c.transform.trans((w - width) / 2.0, h / 2.0 + 40.0),
+        g,
+    );
 }
 
 pub struct Fonts {
     pub regular: FontHandle,
     pub bold: FontHandle,
+    /// Drawn pixels per UI unit, set each frame. UI text is rasterised at this scale so it stays sharp.
+    pub ui_scale: f64,
 }
 
 impl Fonts {
     pub fn new(media: &MediaHandle) -> Self {
         let regular = FontHandle::new(media, "NotoSans-unhinted/NotoSans-Regular.ttf");
         let bold = FontHandle::new(media, "NotoSans-unhinted/NotoSans-Bold.ttf");
-        Fonts { regular, bold }
+        Fonts {
+            regular,
+            bold,
+            ui_scale: 1.0,
+        }
+    }
+
+    pub fn glyphs(&mut self, weight: FontWeight) -> &mut Glyphs {
+        match weight {
+            FontWeight::Regular => &mut self.regular.glyphs,
+            FontWeight::Bold => &mut self.bold.glyphs,
+        }
+    }
+
+    /// Draws UI text, with `size` and `transform` in UI units.
+    pub fn text(&mut self, weight: FontWeight, color: [f32; 4], size: u32, text_: &str, transform: math::Matrix2d, g: &mut G2d) {
+        let scale = self.ui_scale;
+        let scaled_size = (size as f64 * scale).round() as u32;
+        text(
+            color,
+            scaled_size,
+            text_,
+            self.glyphs(weight),
+            transform.scale(1.0 / scale, 1.0 / scale),
+            g,
+        );
+    }
+
+    /// The width of some UI text, in UI units.
+    pub fn width(&mut self, weight: FontWeight, size: u32, text_: &str) -> f64 {
+        let scale = self.ui_scale;
+        let scaled_size = (size as f64 * scale).round() as u32;
+        self.glyphs(weight).width(scaled_size, text_) / scale
+    }
+}
+
+/// Drawn pixels per unit of window coordinates; 2 on a typical HiDPI screen.
+pub fn dpi_scale(win: &PistonWindow) -> f64 {
+    let size = win.size();
+    if size.width == 0 {
+        return 1.0;
+    }
+    win.draw_size().width as f64 / size.width as f64
+}
+
+// Draws the tiles that are on screen, all from the one tileset texture.
+fn draw_tile_layer(c: Context, g: &mut G2d, cam: &Camera, media: &MediaHandle, layer: &TileLayer) {
+    let image = match media.get_texture(&layer.tileset) {
+        Some(image) => image,
+        None => return,
+    };
+    let texture = image.borrow_texture();
+
+    let (cam_x, cam_y) = cam.pos();
+    let (view_w, view_h) = cam.game_viewport_size();
+    let size = layer.tile_size;
+
+    let first_col = ((cam_x - view_w / 2.0 - layer.origin.0) / size).floor().max(0.0) as usize;
+    let last_col = ((cam_x + view_w / 2.0 - layer.origin.0) / size).ceil().max(0.0) as usize;
+    let first_row = ((cam_y - view_h / 2.0 - layer.origin.1) / size).floor().max(0.0) as usize;
+    let last_row = ((cam_y + view_h / 2.0 - layer.origin.1) / size).ceil().max(0.0) as usize;
+
+    for row in first_row..last_row.min(layer.tiles.len()) {
+        for col in first_col..last_col.min(layer.tiles[row].len()) {
+            let tile = layer.tiles[row][col];
+            if tile == 0 {
+                continue;
+            }
+
+            let (x, y) = layer.tile_pos(row, col);
+            Image::new()
+                .src_rect(layer.source_rect(tile))
+                .rect(cam.array_pos_to_screen([x, y, size, size]))
+                .draw(texture, &c.draw_state, c.transform, g);
+        }
     }
 }
 
@@ -189,6 +620,96 @@ pub fn fill_ellipse(c: Context, g: &mut G2d, cam: &Camera, colour: [f32; 4], cx:
     );
 }
 
+// points are relative to (cx, cy), which is also the centre of rotation
+pub fn fill_polygon(c: Context, g: &mut G2d, cam: &Camera, colour: [f32; 4], cx: N, cy: N, points: &[[N; 2]], rot: N) {
+    let (zx, zy) = cam.pos_to_screen(cx, cy);
+    let screen_points: Vec<[f64; 2]> = points
+        .iter()
+        .map(|p| {
+            let (x, y) = cam.pair_metres_to_pixels(p[0], p[1]);
+            [x, y]
+        })
+        .collect();
+
+    polygon(
+        colour,
+        &screen_points,
+        c.transform.trans(zx, zy).rot_rad(rot as f64),
+        g,
+    );
+}
+
+// Draws an image stretched over a w by h rectangle centred on (cx, cy).
+// Images that can't be loaded are drawn as a magenta rectangle, so they're easy to spot.
+pub fn fill_sprite(
+    c: Context,
+    g: &mut G2d,
+    cam: &Camera,
+    media: &MediaHandle,
+    colour: [f32; 4],
+    image: &str,
+    source: Option<[N; 4]>,
+    cx: N,
+    cy: N,
+    w: N,
+    h: N,
+    flip_x: bool,
+    rot: N,
+) {
+    let image = match media.get_texture(image) {
+        Some(image) => image,
+        None => {
+            fill_rectangle(c, g, cam, [1.0, 0.0, 1.0, colour[3]], cx, cy, w, h, rot);
+            return;
+        }
+    };
+    let texture = image.borrow_texture();
+
+    let (zx, zy) = cam.pos_to_screen(cx, cy);
+    let (w, h) = cam.pair_metres_to_pixels(w, h);
+    let mut transform = c.transform.trans(zx, zy).rot_rad(rot as f64);
+    if flip_x {
+        transform = transform.flip_h();
+    }
+
+    let mut sprite = Image::new_color(colour).rect([-w / 2.0, -h / 2.0, w, h]);
+    if let Some(source) = source {
+        sprite = sprite.src_rect([source[0] as f64, source[1] as f64, source[2] as f64, source[3] as f64]);
+    }
+    sprite.draw(texture, &c.draw_state, transform, g);
+}
+
+// end is relative to (x, y), which is also the centre of rotation
+pub fn draw_line(c: Context, g: &mut G2d, cam: &Camera, colour: [f32; 4], x: N, y: N, end: [N; 2], width: N, rot: N) {
+    let (zx, zy) = cam.pos_to_screen(x, y);
+    let (ex, ey) = cam.pair_metres_to_pixels(end[0], end[1]);
+
+    line(
+        colour,
+        cam.metres_to_pixels(width) / 2.0,
+        [0.0, 0.0, ex, ey],
+        c.transform.trans(zx, zy).rot_rad(rot as f64),
+        g,
+    );
+}
+
+// angles are in radians, clockwise from the right
+pub fn draw_arc(c: Context, g: &mut G2d, cam: &Camera, colour: [f32; 4], cx: N, cy: N, radius: N, thickness: N, start: N, sweep: N, rot: N) {
+    let (zx, zy) = cam.pos_to_screen(cx, cy);
+    let r = cam.metres_to_pixels(radius);
+    let start = (start + rot) as f64;
+
+    circle_arc(
+        colour,
+        cam.metres_to_pixels(thickness) / 2.0,
+        start,
+        start + sweep as f64,
+        [zx - r, zy - r, r * 2.0, r * 2.0],
+        c.transform,
+        g,
+    );
+}
+
 // TODO support for origin coords
 pub fn fill_rectangle(c: Context, g: &mut G2d, cam: &Camera, colour: [f32; 4], cx: N, cy: N, w: N, h: N, rot: N) {
     let (zx, zy) = cam.pos_to_screen(cx, cy);
@@ -216,8 +737,13 @@ pub struct DrawTextArgs {
     rot: N,
     origin_x: N,
     origin_y: N,
+    style: TextStyle,
 }
 
+// How far outlines and shadows are drawn from the text, in pixels.
+const TEXT_OUTLINE_OFFSET: f64 = 1.0;
+const TEXT_SHADOW_OFFSET: f64 = 1.5;
+
 /// origin coords are used as origin for rotation
 pub fn draw_text(c: Context, g: &mut G2d, cam: &Camera, fonts: &mut Fonts, text_: &str, args: DrawTextArgs) {
     let (zx, zy) = if args.scale {
@@ -237,17 +763,41 @@ pub fn draw_text(c: Context, g: &mut G2d, cam: &Camera, fonts: &mut Fonts, text_
         .rot_rad(args.rot as f64)
         .trans(zx - origin_zx, zy - origin_zy);
 
+    // in-world text is drawn straight in drawn pixels, so it's sized up here instead
+    let size = (args.size as f64 * fonts.ui_scale).round() as u32;
+    let glyphs = fonts.glyphs(args.style.weight);
+
     if args.center_coords {
-        let width = fonts.bold.glyphs.width(args.size, text_);
+        let width = glyphs.width(size, text_);
         transform = transform.trans(-width / 2.0, 0.0)
     }
 
-    text(
-        args.color,
-        args.size,
-        text_,
-        &mut fonts.bold.glyphs,
-        transform,
-        g,
-    );
+    // nothing to draw once faded out
+    let alpha = args.color[3];
+    if alpha <= 0.0 {
+        return;
+    }
+
+    // outlines and shadows fade along with the text
+    let faded = |color: [f32; 4]| [color[0], color[1], color[2], color[3] * alpha];
+
+    if let Some(shadow) = args.style.shadow {
+        let offset = TEXT_SHADOW_OFFSET;
+        Text::new_color(faded(shadow), size).draw(text_, glyphs, &c.draw_state, transform.trans(offset, offset), g);
+    }
+
+    if let Some(outline) = args.style.outline {
+        let offset = TEXT_OUTLINE_OFFSET;
+        for &(dx, dy) in &[(-1.0, 0.0), (1.0, 0.0), (0.0, -1.0), (0.0, 1.0)] {
+            Text::new_color(faded(outline), size).draw(
+                text_,
+                glyphs,
+                &c.draw_state,
+                transform.trans(dx * offset, dy * offset),
+                g,
+            );
+        }
+    }
+
+    Text::new_color(args.color, size).draw(text_, glyphs, &c.draw_state, transform, g);
 }
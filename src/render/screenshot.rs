@@ -0,0 +1,62 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use gfx_device_gl::gl;
+use image::{ImageBuffer, Rgba};
+use piston_window::{PistonWindow, Window};
+
+const DIRECTORY: &'static str = "screenshots";
+
+/// Saves the frame that was just drawn as a PNG in `screenshots/`, named after the time, and returns its path.
+/// Call after drawing, before the frame is swapped onto the screen.
+pub fn take_screenshot(window: &mut PistonWindow) -> io::Result<PathBuf> {
+    let size = window.draw_size();
+    let (w, h) = (size.width, size.height);
+    let mut pixels = vec![0u8; (w * h * 4) as usize];
+
+    unsafe {
+        window.device.with_gl(|gl| {
+            gl.PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl.ReadPixels(
+                0,
+                0,
+                w as i32,
+                h as i32,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.as_mut_ptr() as *mut _,
+            );
+        });
+    }
+
+    // GL gives the rows bottom first; the framebuffer's alpha isn't meaningful, so it's dropped
+    let image = ImageBuffer::from_fn(w, h, |x, y| {
+        let i = (((h - 1 - y) * w + x) * 4) as usize;
+        Rgba([pixels[i], pixels[i + 1], pixels[i + 2], 255])
+    });
+
+    fs::create_dir_all(DIRECTORY)?;
+    let path = screenshot_path();
+    image.save(&path)?;
+
+    Ok(path)
+}
+
+fn screenshot_path() -> PathBuf {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let stamp = format!("{}-{:03}", now.as_secs(), now.subsec_nanos() / 1_000_000);
+
+    // two in the same millisecond shouldn't overwrite each other
+    let mut path = Path::new(DIRECTORY).join(format!("dio-{}.png", stamp));
+    let mut n = 1;
+    while path.exists() {
+        path = Path::new(DIRECTORY).join(format!("dio-{}-{}.png", stamp, n));
+        n += 1;
+    }
+
+    path
+}
@@ -0,0 +1,80 @@
+// Seconds to fade all the way out, or all the way back in.
+const FADE_TIME: f64 = 0.35;
+
+#[derive(Debug, Clone, Copy)]
+enum TransitionState<A> {
+    Idle,
+    /// Going to black; `action` is handed back once the screen is covered.
+    FadingOut { elapsed: f64, action: A },
+    FadingIn { elapsed: f64 },
+}
+
+/// Fades the screen to black and back, to hide changes of scene like loading the next level.
+/// `A` is whatever should happen while the screen is covered.
+#[derive(Debug, Clone)]
+pub struct Transition<A> {
+    state: TransitionState<A>,
+}
+
+impl<A: Copy> Transition<A> {
+    pub fn new() -> Self {
+        Transition {
+            state: TransitionState::Idle,
+        }
+    }
+
+    /// Fades out, then returns `action` from `update` and fades back in.
+    /// Ignored if a transition is already going, so holding a key can't queue up several.
+    pub fn fade_out_then(&mut self, action: A) {
+        if !self.is_active() {
+            self.state = TransitionState::FadingOut { elapsed: 0.0, action };
+        }
+    }
+
+    /// Starts from black and fades in, for changes that have already happened.
+    pub fn fade_in(&mut self) {
+        self.state = TransitionState::FadingIn { elapsed: 0.0 };
+    }
+
+    pub fn is_active(&self) -> bool {
+        match self.state {
+            TransitionState::Idle => false,
+            _ => true,
+        }
+    }
+
+    /// Moves the fade along, returning the action once the screen has gone black.
+    pub fn update(&mut self, dt: f64) -> Option<A> {
+        match self.state {
+            TransitionState::Idle => None,
+            TransitionState::FadingOut { elapsed, action } => {
+                let elapsed = elapsed + dt;
+                if elapsed >= FADE_TIME {
+                    self.state = TransitionState::FadingIn { elapsed: 0.0 };
+                    Some(action)
+                } else {
+                    self.state = TransitionState::FadingOut { elapsed, action };
+                    None
+                }
+            }
+            TransitionState::FadingIn { elapsed } => {
+                let elapsed = elapsed + dt;
+                self.state = if elapsed >= FADE_TIME {
+                    TransitionState::Idle
+                } else {
+                    TransitionState::FadingIn { elapsed }
+                };
+                None
+            }
+        }
+    }
+
+    /// How much of the screen the black overlay hides, from 0 to 1.
+    pub fn alpha(&self) -> f32 {
+        match self.state {
+            TransitionState::Idle => 0.0,
+            TransitionState::FadingOut { elapsed, .. } => (elapsed / FADE_TIME).min(1.0) as f32,
+            TransitionState::FadingIn { elapsed } => (1.0 - elapsed / FADE_TIME).max(0.0) as f32,
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use std::error::Error as StdError;
+use std::fmt::{self, Display, Formatter};
+use std::fs::OpenOptions;
+use std::io::{self, Read, Write};
+
+use serde_json;
+
+use engine::N;
+
+#[derive(Debug)]
+pub enum ReplayError {
+    IoError(io::Error),
+    SerdeError(serde_json::Error),
+}
+
+impl Display for ReplayError {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            ReplayError::IoError(ref e) => write!(f, "{}", e),
+            ReplayError::SerdeError(ref e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl StdError for ReplayError {
+    fn description(&self) -> &str {
+        "replay error"
+    }
+
+    fn cause(&self) -> Option<&StdError> {
+        match *self {
+            ReplayError::IoError(ref e) => Some(e),
+            ReplayError::SerdeError(ref e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for ReplayError {
+    fn from(err: io::Error) -> ReplayError {
+        ReplayError::IoError(err)
+    }
+}
+
+impl From<serde_json::Error> for ReplayError {
+    fn from(err: serde_json::Error) -> ReplayError {
+        ReplayError::SerdeError(err)
+    }
+}
+
+pub const MOVING_LEFT: u8 = 1 << 0;
+pub const MOVING_RIGHT: u8 = 1 << 1;
+pub const PICKING_UP: u8 = 1 << 2;
+pub const JUMPING: u8 = 1 << 3;
+pub const STOP_TIME: u8 = 1 << 4;
+pub const SPAWN_BULLET: u8 = 1 << 5;
+pub const RESTART: u8 = 1 << 6;
+
+/// The player's input for a single tick.
+/// Held inputs and one-off actions are packed into `flags` to keep replay files small.
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct InputFrame {
+    #[serde(rename = "t")]
+    pub dt: N,
+    #[serde(rename = "f")]
+    pub flags: u8,
+    /// World position the player used their weapon towards during this tick, if any.
+    /// Named after the knife, which was once the only weapon, to keep old replays working.
+    #[serde(rename = "k", default, skip_serializing_if = "Option::is_none")]
+    pub knife_target: Option<(N, N)>,
+    /// A frozen object dragged during time stop: where it was grabbed, and where it was aimed.
+    #[serde(rename = "p", default, skip_serializing_if = "Option::is_none")]
+    pub pose: Option<((N, N), (N, N))>,
+    /// World position the player lobbed a grenade towards during this tick, if any.
+    #[serde(rename = "g", default, skip_serializing_if = "Option::is_none")]
+    pub grenade_target: Option<(N, N)>,
+    /// Inventory slot the player switched to during this tick, if any.
+    #[serde(rename = "w", default, skip_serializing_if = "Option::is_none")]
+    pub select_weapon: Option<u8>,
+}
+
+impl InputFrame {
+    pub fn has(&self, flag: u8) -> bool {
+        self.flags & flag != 0
+    }
+
+    pub fn set(&mut self, flag: u8, value: bool) {
+        if value {
+            self.flags |= flag;
+        } else {
+            self.flags &= !flag;
+        }
+    }
+
+    /// Clears the one-off actions, keeping held inputs.
+    pub fn clear_actions(&mut self) {
+        self.set(STOP_TIME, false);
+        self.set(SPAWN_BULLET, false);
+        self.set(RESTART, false);
+        self.knife_target = None;
+        self.pose = None;
+        self.grenade_target = None;
+        self.select_weapon = None;
+    }
+}
+
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct Replay {
+    /// Path of the level, relative to the levels directory.
+    pub level: String,
+    pub seed: u32,
+    pub frames: Vec<InputFrame>,
+}
+
+impl Replay {
+    pub fn new(level: &str, seed: u32) -> Replay {
+        Replay {
+            level: level.to_owned(),
+            seed: seed,
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn load(path: &str) -> Result<Replay, ReplayError> {
+        let mut file = OpenOptions::new().read(true).open(path)?;
+
+        let mut text = String::new();
+        file.read_to_string(&mut text)?;
+        let replay: Replay = serde_json::from_str(&text)?;
+
+        println!("Loaded replay of `{}` ({} ticks)", replay.level, replay.frames.len());
+
+        Ok(replay)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), ReplayError> {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .truncate(true)
+            .create(true)
+            .open(path)?;
+
+        let text = serde_json::to_string(self)?;
+        writeln!(file, "{}", text)?;
+
+        println!("Saved replay to `{}` ({} ticks)", path, self.frames.len());
+
+        Ok(())
+    }
+}
+
+/// Feeds the frames of a recorded replay back one tick at a time.
+pub struct Playback {
+    replay: Replay,
+    next: usize,
+}
+
+impl Playback {
+    pub fn new(replay: Replay) -> Playback {
+        Playback {
+            replay: replay,
+            next: 0,
+        }
+    }
+
+    pub fn replay(&self) -> &Replay {
+        &self.replay
+    }
+
+    /// Returns `None` once every recorded frame has been played.
+    pub fn next_frame(&mut self) -> Option<InputFrame> {
+        let frame = self.replay.frames.get(self.next).cloned();
+        self.next += 1;
+        frame
+    }
+}
@@ -5,7 +5,7 @@ use std::thread;
 
 use serde_json;
 
-#[derive(Default, Clone, Copy, Serialize, Deserialize)]
+#[derive(Default, Clone, Serialize, Deserialize)]
 pub struct Stats {
     pub num_time_stops: u64,
     pub num_clicks: u64,
@@ -13,6 +13,43 @@ pub struct Stats {
     pub num_startups: u64,
     pub num_knives_spawned: u64,
     pub total_game_time: f64,
+    #[serde(default)]
+    pub num_enemies_killed: u64,
+    #[serde(default)]
+    pub num_deaths: u64,
+    #[serde(default)]
+    pub num_coins_collected: u64,
+    #[serde(default)]
+    pub num_pickups_collected: u64,
+    /// Paths of the campaign levels the player has completed, used to unlock later levels.
+    #[serde(default)]
+    pub completed_levels: Vec<String>,
+}
+
+impl Stats {
+    pub fn complete_level(&mut self, path: &str) {
+        if !self.completed_levels.iter().any(|level| level == path) {
+            self.completed_levels.push(path.to_owned());
+        }
+    }
+}
+
+/// Counts for the level being played, for the results screen. Unlike `Stats`, these are never saved,
+/// and start again from zero whenever a level is started or restarted.
+#[derive(Debug, Default, Clone)]
+pub struct LevelStats {
+    /// Seconds spent playing, not counting pauses or time dead.
+    pub time: f64,
+    pub knives_thrown: u64,
+    pub enemies_killed: u64,
+    pub coins_collected: u64,
+}
+
+/// A finished level, shown on the results screen until the player moves on.
+#[derive(Debug, Clone)]
+pub struct LevelResults {
+    pub level_name: String,
+    pub stats: LevelStats,
 }
 
 enum Message {
@@ -92,12 +129,12 @@ impl Handler {
     }
 
     pub fn get(&self) -> Stats {
-        self.latest_stats
+        self.latest_stats.clone()
     }
 
     /// asynchronous
     pub fn set(&mut self, stats: Stats) {
-        self.latest_stats = stats;
+        self.latest_stats = stats.clone();
         self.send_counter += 1;
         if self.send_counter > 60 {
             self.send_counter = 0;